@@ -0,0 +1,13 @@
+DROP TABLE transaction_detail;
+DROP TABLE transaction;
+DROP TABLE giro;
+DROP TABLE place;
+DROP TABLE detail;
+DROP TABLE causal;
+DROP TABLE account_user;
+DROP TABLE account;
+DROP TABLE transaction_type;
+DROP TABLE account_type;
+DROP TABLE currency;
+DROP TABLE auth;
+DROP TABLE "user";
@@ -0,0 +1,143 @@
+-- The schema of the first release, it was created by hand from "db schema/money-manager-postgres.sql".
+-- IF NOT EXISTS: on the databases created that way this migration only gets recorded.
+
+CREATE TABLE IF NOT EXISTS "user" (
+    id bigserial NOT NULL,
+    name character varying(32) NOT NULL,
+    surname character varying(32) NOT NULL,
+    phone character varying(16),
+    country character varying(64),
+    address character varying(128),
+    birthdate date,
+    note character varying(255),
+    CONSTRAINT "User_pkey" PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS auth (
+    id bigint NOT NULL,
+    email character varying(255) NOT NULL,
+    iteration smallint NOT NULL,
+    salt character(64) NOT NULL,
+    stored_key character(64) NOT NULL,
+    last_login timestamp with time zone,
+    CONSTRAINT "Auth_pkey" PRIMARY KEY (id),
+    CONSTRAINT auth_user_fk FOREIGN KEY (id) REFERENCES "user"(id)
+);
+
+CREATE TABLE IF NOT EXISTS currency (
+    id smallserial NOT NULL,
+    name character varying(64) NOT NULL,
+    code character(3) NOT NULL,
+    number smallint NOT NULL,
+    CONSTRAINT "Currency_pkey" PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS account_type (
+    id serial NOT NULL,
+    type character varying(32) NOT NULL,
+    CONSTRAINT "AccountType_pkey" PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS transaction_type (
+    id serial NOT NULL,
+    type character varying(32) NOT NULL,
+    CONSTRAINT "TransactionType_pkey" PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS account (
+    id bigserial NOT NULL,
+    name character varying(64) NOT NULL,
+    status boolean NOT NULL,
+    note character varying(255),
+    current_balance double precision NOT NULL,
+    initial_balance double precision NOT NULL,
+    creation_date timestamp with time zone NOT NULL,
+    id_account_type integer NOT NULL,
+    id_currency smallint NOT NULL,
+    CONSTRAINT "Account_pkey" PRIMARY KEY (id),
+    CONSTRAINT account_account_type_fk FOREIGN KEY (id_account_type) REFERENCES account_type(id),
+    CONSTRAINT account_currency_fk FOREIGN KEY (id_currency) REFERENCES currency(id)
+);
+
+CREATE TABLE IF NOT EXISTS account_user (
+    id_account bigint NOT NULL,
+    id_user bigint NOT NULL,
+    CONSTRAINT "AccountUser_pkey" PRIMARY KEY (id_account, id_user),
+    CONSTRAINT account__user_account_fk FOREIGN KEY (id_account) REFERENCES account(id),
+    CONSTRAINT account__user_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+CREATE TABLE IF NOT EXISTS causal (
+    id bigserial NOT NULL,
+    description character varying(255) NOT NULL,
+    id_user bigint,
+    CONSTRAINT "Causal_pkey" PRIMARY KEY (id),
+    CONSTRAINT causal_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+CREATE TABLE IF NOT EXISTS detail (
+    id bigserial NOT NULL,
+    description character varying(32) NOT NULL,
+    id_user bigint,
+    CONSTRAINT "Detail_pkey" PRIMARY KEY (id),
+    CONSTRAINT detail_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+CREATE TABLE IF NOT EXISTS place (
+    id bigserial NOT NULL,
+    name character varying(64) NOT NULL,
+    address character varying(128),
+    country character varying(64),
+    email character varying(255),
+    website character varying(128),
+    phone character varying(16),
+    note character varying(255),
+    id_user bigint,
+    CONSTRAINT "Place_pkey" PRIMARY KEY (id),
+    CONSTRAINT place_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+CREATE TABLE IF NOT EXISTS giro (
+    id bigserial NOT NULL,
+    id_source_account bigint NOT NULL,
+    id_destination_account bigint NOT NULL,
+    data timestamp with time zone NOT NULL,
+    note character varying(255),
+    amount double precision NOT NULL,
+    expense double precision,
+    id_currency smallint NOT NULL,
+    CONSTRAINT "Giro_pkey" PRIMARY KEY (id),
+    CONSTRAINT giro_account_1_fk FOREIGN KEY (id_source_account) REFERENCES account(id),
+    CONSTRAINT giro_account_2_fk FOREIGN KEY (id_destination_account) REFERENCES account(id),
+    CONSTRAINT giro_currency_fk FOREIGN KEY (id_currency) REFERENCES currency(id)
+);
+
+CREATE TABLE IF NOT EXISTS transaction (
+    id bigserial NOT NULL,
+    id_account bigint NOT NULL,
+    id_transaction_type integer NOT NULL,
+    id_place bigint,
+    id_beneficiary bigint,
+    note character varying(255),
+    amount double precision NOT NULL,
+    data timestamp with time zone NOT NULL,
+    id_currency smallint NOT NULL,
+    expense double precision,
+    id_causal bigint NOT NULL,
+    CONSTRAINT "Transaction_pkey" PRIMARY KEY (id),
+    CONSTRAINT transaction_account_fk FOREIGN KEY (id_account) REFERENCES account(id),
+    CONSTRAINT transaction_beneficiary_fk FOREIGN KEY (id_beneficiary) REFERENCES account(id),
+    CONSTRAINT transaction_transaction_type_fk FOREIGN KEY (id_transaction_type) REFERENCES transaction_type(id),
+    CONSTRAINT transaction_place_fk FOREIGN KEY (id_place) REFERENCES place(id),
+    CONSTRAINT transaction_currency_fk FOREIGN KEY (id_currency) REFERENCES currency(id),
+    CONSTRAINT transaction_causal_fk FOREIGN KEY (id_causal) REFERENCES causal(id)
+);
+
+CREATE TABLE IF NOT EXISTS transaction_detail (
+    id_detail bigint NOT NULL,
+    id_transaction bigint NOT NULL,
+    amount smallint,
+    CONSTRAINT "TransactionDetail_pkey" PRIMARY KEY (id_detail, id_transaction),
+    CONSTRAINT transaction_detail_detail_fk FOREIGN KEY (id_detail) REFERENCES detail(id),
+    CONSTRAINT transaction_detail_transaction_fk FOREIGN KEY (id_transaction) REFERENCES transaction(id)
+);
@@ -0,0 +1,2 @@
+-- the reference data can be used by the accounts and the transactions, it is not removed
+SELECT 1;
@@ -0,0 +1,33 @@
+-- Reference data, the administrators can change it through /admin.
+-- The rows already there are kept.
+
+INSERT INTO currency (id, name, code, number) VALUES
+    (1, 'Euro', 'EUR', 978),
+    (2, 'US Dollar', 'USD', 840),
+    (3, 'Pound Sterling', 'GBP', 826),
+    (4, 'Swiss Franc', 'CHF', 756),
+    (5, 'Yen', 'JPY', 392)
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO account_type (id, type) VALUES
+    (1, 'Cash'),
+    (2, 'Bank account'),
+    (3, 'Credit card'),
+    (4, 'Prepaid card'),
+    (5, 'Savings'),
+    (6, 'Investment')
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO transaction_type (id, type) VALUES
+    (1, 'Cash'),
+    (2, 'Debit card'),
+    (3, 'Credit card'),
+    (4, 'Bank transfer'),
+    (5, 'Direct debit'),
+    (6, 'Cheque')
+ON CONFLICT (id) DO NOTHING;
+
+-- the ids were explicit, the sequences must not return them again
+SELECT setval(pg_get_serial_sequence('currency', 'id'), (SELECT MAX(id) FROM currency));
+SELECT setval(pg_get_serial_sequence('account_type', 'id'), (SELECT MAX(id) FROM account_type));
+SELECT setval(pg_get_serial_sequence('transaction_type', 'id'), (SELECT MAX(id) FROM transaction_type));
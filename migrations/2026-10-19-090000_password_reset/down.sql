@@ -0,0 +1,3 @@
+DROP TABLE password_reset;
+
+ALTER TABLE auth DROP COLUMN tokens_valid_after;
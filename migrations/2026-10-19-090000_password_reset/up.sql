@@ -0,0 +1,14 @@
+ALTER TABLE auth ADD COLUMN tokens_valid_after timestamp with time zone;
+
+CREATE TABLE password_reset (
+    id bigserial NOT NULL,
+    id_user bigint NOT NULL,
+    token_hash character(64) NOT NULL,
+    expiration_date timestamp with time zone NOT NULL,
+    used_date timestamp with time zone,
+    creation_date timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT password_reset_pkey PRIMARY KEY (id),
+    CONSTRAINT password_reset_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+CREATE UNIQUE INDEX password_reset_token_hash_idx ON password_reset (token_hash);
@@ -0,0 +1,14 @@
+CREATE TABLE access_token (
+    id bigserial NOT NULL,
+    id_user bigint NOT NULL,
+    name character varying(64) NOT NULL,
+    token_hash character(64) NOT NULL,
+    scopes character varying(255) NOT NULL,
+    creation_date timestamp with time zone NOT NULL DEFAULT now(),
+    expiration_date timestamp with time zone,
+    last_used timestamp with time zone,
+    CONSTRAINT access_token_pkey PRIMARY KEY (id),
+    CONSTRAINT access_token_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+CREATE UNIQUE INDEX access_token_token_hash_idx ON access_token (token_hash);
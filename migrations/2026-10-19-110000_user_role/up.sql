@@ -0,0 +1,2 @@
+ALTER TABLE "user" ADD COLUMN role character varying(16) NOT NULL DEFAULT 'user';
+ALTER TABLE "user" ADD COLUMN disabled boolean NOT NULL DEFAULT false;
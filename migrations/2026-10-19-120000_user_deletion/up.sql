@@ -0,0 +1 @@
+ALTER TABLE "user" ADD COLUMN deletion_date timestamp with time zone;
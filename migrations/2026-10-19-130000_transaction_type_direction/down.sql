@@ -0,0 +1 @@
+ALTER TABLE transaction_type DROP COLUMN direction;
@@ -0,0 +1,13 @@
+-- 1: the transactions of this type increase the balance of the account, -1: they decrease it
+ALTER TABLE transaction_type ADD COLUMN direction smallint NOT NULL DEFAULT -1;
+ALTER TABLE transaction_type ADD CONSTRAINT transaction_type_direction_check CHECK (direction IN (-1, 1));
+
+-- the direction of the new transactions of the type: the sign of most of its transactions so far,
+-- the types never used stay at -1
+UPDATE transaction_type SET direction = 1
+WHERE id IN (SELECT id_transaction_type FROM transaction GROUP BY id_transaction_type HAVING sum(sign(amount)) > 0);
+
+-- the seeded types are means of payment, these two are for who only cares about the direction
+INSERT INTO transaction_type (type, direction)
+SELECT t.type, t.direction FROM (VALUES ('Income', 1), ('Expense', -1)) AS t(type, direction)
+WHERE NOT EXISTS (SELECT 1 FROM transaction_type tt WHERE tt.type = t.type);
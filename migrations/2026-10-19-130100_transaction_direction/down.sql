@@ -0,0 +1,3 @@
+-- without the direction the amount carries the sign again
+UPDATE transaction SET amount = direction * abs(amount) WHERE amount <> direction * abs(amount);
+ALTER TABLE transaction DROP COLUMN direction;
@@ -0,0 +1,7 @@
+-- 1: the transaction increases the balance of the account, -1: it decreases it.
+-- The amounts recorded so far carry their own sign and are left as they are: each transaction
+-- takes the direction of its sign, the balance moves by direction * abs(amount).
+ALTER TABLE transaction ADD COLUMN direction smallint;
+UPDATE transaction SET direction = CASE WHEN amount < 0 THEN -1 ELSE 1 END;
+ALTER TABLE transaction ALTER COLUMN direction SET NOT NULL;
+ALTER TABLE transaction ADD CONSTRAINT transaction_direction_check CHECK (direction IN (-1, 1));
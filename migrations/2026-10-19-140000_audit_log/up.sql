@@ -0,0 +1,27 @@
+CREATE TABLE audit_log (
+    id bigserial NOT NULL,
+    id_user bigint NOT NULL,
+    entity character varying(32) NOT NULL,
+    entity_id bigint NOT NULL,
+    id_account bigint NOT NULL,
+    action character varying(16) NOT NULL,
+    before jsonb,
+    after jsonb,
+    creation_date timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT audit_log_pkey PRIMARY KEY (id),
+    CONSTRAINT audit_log_action_check CHECK (action IN ('create', 'update', 'delete'))
+);
+
+-- no foreign keys: the log outlives the users and the entities it describes
+CREATE INDEX audit_log_entity_idx ON audit_log (entity, entity_id);
+CREATE INDEX audit_log_account_idx ON audit_log (id_account);
+
+-- append-only: the rows can be removed (erasure of a user) but never changed
+CREATE FUNCTION audit_log_immutable() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+    FOR EACH ROW EXECUTE PROCEDURE audit_log_immutable();
@@ -0,0 +1,10 @@
+-- the rows in the trash come back as live rows
+DELETE FROM audit_log WHERE action = 'restore';
+ALTER TABLE audit_log DROP CONSTRAINT audit_log_action_check;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_action_check CHECK (action IN ('create', 'update', 'delete'));
+
+ALTER TABLE causal DROP COLUMN deleted_at;
+ALTER TABLE place DROP COLUMN deleted_at;
+ALTER TABLE giro DROP COLUMN deleted_at;
+ALTER TABLE transaction DROP COLUMN deleted_at;
+ALTER TABLE account DROP COLUMN deleted_at;
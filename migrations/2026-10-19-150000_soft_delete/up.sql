@@ -0,0 +1,15 @@
+ALTER TABLE account ADD COLUMN deleted_at timestamp with time zone;
+ALTER TABLE transaction ADD COLUMN deleted_at timestamp with time zone;
+ALTER TABLE giro ADD COLUMN deleted_at timestamp with time zone;
+ALTER TABLE place ADD COLUMN deleted_at timestamp with time zone;
+ALTER TABLE causal ADD COLUMN deleted_at timestamp with time zone;
+
+-- the trash is small, the purge looks only there
+CREATE INDEX account_deleted_at_idx ON account (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX transaction_deleted_at_idx ON transaction (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX giro_deleted_at_idx ON giro (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX place_deleted_at_idx ON place (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX causal_deleted_at_idx ON causal (deleted_at) WHERE deleted_at IS NOT NULL;
+
+ALTER TABLE audit_log DROP CONSTRAINT audit_log_action_check;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_action_check CHECK (action IN ('create', 'update', 'delete', 'restore'));
@@ -0,0 +1,6 @@
+ALTER TABLE detail DROP COLUMN version;
+ALTER TABLE causal DROP COLUMN version;
+ALTER TABLE place DROP COLUMN version;
+ALTER TABLE giro DROP COLUMN version;
+ALTER TABLE transaction DROP COLUMN version;
+ALTER TABLE account DROP COLUMN version;
@@ -0,0 +1,6 @@
+ALTER TABLE account ADD COLUMN version integer NOT NULL DEFAULT 1;
+ALTER TABLE transaction ADD COLUMN version integer NOT NULL DEFAULT 1;
+ALTER TABLE giro ADD COLUMN version integer NOT NULL DEFAULT 1;
+ALTER TABLE place ADD COLUMN version integer NOT NULL DEFAULT 1;
+ALTER TABLE causal ADD COLUMN version integer NOT NULL DEFAULT 1;
+ALTER TABLE detail ADD COLUMN version integer NOT NULL DEFAULT 1;
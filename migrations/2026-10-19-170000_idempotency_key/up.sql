@@ -0,0 +1,13 @@
+CREATE TABLE idempotency_key (
+    id_user bigint NOT NULL,
+    key character varying(255) NOT NULL,
+    request_hash character(64) NOT NULL,
+    status smallint NOT NULL,
+    response jsonb,
+    creation_date timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT idempotency_key_pkey PRIMARY KEY (id_user, key),
+    CONSTRAINT idempotency_key_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+-- the expired keys are purged by the housekeeping
+CREATE INDEX idempotency_key_creation_date_idx ON idempotency_key (creation_date);
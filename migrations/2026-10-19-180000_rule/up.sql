@@ -0,0 +1,22 @@
+CREATE TABLE rule (
+    id bigserial NOT NULL,
+    id_user bigint NOT NULL,
+    name character varying(64) NOT NULL,
+    priority integer NOT NULL DEFAULT 0,
+    enabled boolean NOT NULL DEFAULT true,
+    note_regex character varying(255),
+    min_amount double precision,
+    max_amount double precision,
+    id_account bigint,
+    id_beneficiary bigint,
+    set_causal bigint,
+    set_place bigint,
+    set_transaction_type integer,
+    add_details bigint[] NOT NULL DEFAULT '{}',
+    CONSTRAINT rule_pkey PRIMARY KEY (id),
+    CONSTRAINT rule_user_fk FOREIGN KEY (id_user) REFERENCES "user"(id)
+);
+
+-- no foreign keys on the conditions and the actions: the trash purges accounts, causals and places,
+-- a rule that references one of them no longer matches or skips that action
+CREATE INDEX rule_user_idx ON rule (id_user);
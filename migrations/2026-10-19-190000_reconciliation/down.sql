@@ -0,0 +1,5 @@
+ALTER TABLE giro DROP COLUMN id_reconciliation;
+ALTER TABLE giro DROP COLUMN cleared;
+ALTER TABLE transaction DROP COLUMN id_reconciliation;
+ALTER TABLE transaction DROP COLUMN cleared;
+DROP TABLE reconciliation;
@@ -0,0 +1,24 @@
+CREATE TABLE reconciliation (
+    id bigserial NOT NULL,
+    id_account bigint NOT NULL,
+    statement_date timestamp with time zone NOT NULL,
+    statement_balance double precision NOT NULL,
+    cleared_balance double precision NOT NULL,
+    difference double precision NOT NULL,
+    locked integer NOT NULL DEFAULT 0,
+    creation_date timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT reconciliation_pkey PRIMARY KEY (id),
+    CONSTRAINT reconciliation_account_fk FOREIGN KEY (id_account) REFERENCES account(id)
+);
+
+CREATE INDEX reconciliation_account_idx ON reconciliation (id_account, statement_date);
+
+-- a movement with a reconciliation is locked: it matched a statement and can not change anymore
+ALTER TABLE transaction ADD COLUMN cleared boolean NOT NULL DEFAULT false;
+ALTER TABLE transaction ADD COLUMN id_reconciliation bigint;
+ALTER TABLE transaction ADD CONSTRAINT transaction_reconciliation_fk
+    FOREIGN KEY (id_reconciliation) REFERENCES reconciliation(id);
+ALTER TABLE giro ADD COLUMN cleared boolean NOT NULL DEFAULT false;
+ALTER TABLE giro ADD COLUMN id_reconciliation bigint;
+ALTER TABLE giro ADD CONSTRAINT giro_reconciliation_fk
+    FOREIGN KEY (id_reconciliation) REFERENCES reconciliation(id);
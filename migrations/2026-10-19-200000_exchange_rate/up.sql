@@ -0,0 +1,11 @@
+-- one unit of the currency is worth rate units of the base currency, from that day on
+CREATE TABLE exchange_rate (
+    id_currency smallint NOT NULL,
+    id_base_currency smallint NOT NULL,
+    date date NOT NULL,
+    rate double precision NOT NULL,
+    CONSTRAINT exchange_rate_pkey PRIMARY KEY (id_currency, id_base_currency, date),
+    CONSTRAINT exchange_rate_currency_fk FOREIGN KEY (id_currency) REFERENCES currency(id),
+    CONSTRAINT exchange_rate_base_currency_fk FOREIGN KEY (id_base_currency) REFERENCES currency(id),
+    CONSTRAINT exchange_rate_positive CHECK (rate > 0)
+);
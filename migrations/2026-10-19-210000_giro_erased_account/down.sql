@@ -0,0 +1,4 @@
+DELETE FROM giro WHERE id_source_account IS NULL OR id_destination_account IS NULL;
+ALTER TABLE giro DROP CONSTRAINT giro_account_check;
+ALTER TABLE giro ALTER COLUMN id_destination_account SET NOT NULL;
+ALTER TABLE giro ALTER COLUMN id_source_account SET NOT NULL;
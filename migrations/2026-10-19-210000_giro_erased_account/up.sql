@@ -0,0 +1,5 @@
+-- the account of a user that is erased leaves its side of the giros empty, the other account keeps them
+ALTER TABLE giro ALTER COLUMN id_source_account DROP NOT NULL;
+ALTER TABLE giro ALTER COLUMN id_destination_account DROP NOT NULL;
+ALTER TABLE giro ADD CONSTRAINT giro_account_check
+    CHECK (id_source_account IS NOT NULL OR id_destination_account IS NOT NULL);
@@ -0,0 +1,9 @@
+UPDATE giro SET id_reconciliation_source = id_reconciliation_destination
+    WHERE id_reconciliation_source IS NULL;
+ALTER TABLE giro DROP COLUMN id_reconciliation_destination;
+ALTER TABLE giro RENAME CONSTRAINT giro_reconciliation_source_fk TO giro_reconciliation_fk;
+ALTER TABLE giro RENAME COLUMN id_reconciliation_source TO id_reconciliation;
+
+UPDATE giro SET cleared_source = cleared_source OR cleared_destination;
+ALTER TABLE giro DROP COLUMN cleared_destination;
+ALTER TABLE giro RENAME COLUMN cleared_source TO cleared;
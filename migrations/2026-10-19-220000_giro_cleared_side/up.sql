@@ -0,0 +1,14 @@
+-- each account of a giro has its own statement: the cleared flag and the lock are per side
+ALTER TABLE giro RENAME COLUMN cleared TO cleared_source;
+ALTER TABLE giro ADD COLUMN cleared_destination boolean NOT NULL DEFAULT false;
+UPDATE giro SET cleared_destination = cleared_source;
+
+ALTER TABLE giro RENAME COLUMN id_reconciliation TO id_reconciliation_source;
+ALTER TABLE giro RENAME CONSTRAINT giro_reconciliation_fk TO giro_reconciliation_source_fk;
+ALTER TABLE giro ADD COLUMN id_reconciliation_destination bigint;
+ALTER TABLE giro ADD CONSTRAINT giro_reconciliation_destination_fk
+    FOREIGN KEY (id_reconciliation_destination) REFERENCES reconciliation(id);
+-- the lock goes to the side of the account that was reconciled
+UPDATE giro SET id_reconciliation_destination = id_reconciliation_source, id_reconciliation_source = NULL
+    FROM reconciliation r
+    WHERE r.id = giro.id_reconciliation_source AND r.id_account = giro.id_destination_account;
@@ -18,56 +18,56 @@
 use diesel::result::Error;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
 use crate::base_model::BaseModel;
 use crate::account::model::{AccountType, AccountTypeForm};
 use crate::user::model::User;
+use crate::auth::auth::Admin;
 
-/*  DISABLED FOR SECURITY REASON */
-#[allow(dead_code)]
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<AccountTypeForm>, _user: User) -> Result<Json<AccountType>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<AccountTypeForm>, admin: Admin) -> Result<Json<AccountType>, ApiError> {
     debug!("CREATE_ACCOUNT_TYPE_REQUEST");
-    AccountType::create(&json.into_inner(), &conn)
+    let form = json.into_inner();
+    form.validate(&admin.0, &conn)?;
+    AccountType::create(&form, &conn)
         .map(|at| {
             info!("account_type create successfully {}", at.id);
             Json(at)
         })
         .map_err(|e| {
             error!("Can not create account_type caused by {}", e);
-            Status::InternalServerError
+            ApiError::internal()
         })
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i32, _user: User) -> Result<Json<AccountType>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i32, _user: User) -> Result<Json<AccountType>, ApiError> {
     debug!("READ_ONE_ACCOUNT_TYPE_REQUEST");
     get_by_id(id, &conn).map(Json)
 }
 
 #[get("/")]
-fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<AccountType>>, Custom<String>> {
+fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<AccountType>>, ApiError> {
     debug!("READ_ALL_ACCOUNT_TYPE_REQUEST");
     let result = AccountType::read(&conn);
     AccountType::unpack(result)
 }
 
-/*  DISABLED FOR SECURITY REASON */
-#[allow(dead_code)]
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i32, json: Json<AccountTypeForm>, _user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i32, json: Json<AccountTypeForm>, admin: Admin) -> Result<Status, ApiError> {
     debug!("UPDATE_ACCOUNT_TYPE_REQUEST");
     let at = get_by_id(id, &conn)?;
-    let result = AccountType::update(&at, &json.into_inner(), &conn);
+    let form = json.into_inner();
+    form.validate(&admin.0, &conn)?;
+    let result = AccountType::update(&at, &form, &conn);
     finalize_update_delete(result)
 }
 
-/*  DISABLED FOR SECURITY REASON */
-#[allow(dead_code)]
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i32, _user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i32, _admin: Admin) -> Result<Status, ApiError> {
     debug!("DELETE_ACCOUNT_TYPE_REQUEST");
     let at = get_by_id(id, &conn)?;
     let result = AccountType::delete(&at, &conn);
@@ -80,30 +80,29 @@ pub fn get_mount() -> Vec<rocket::Route> {
     routes![read, read_one]
 }
 
+///
+/// Reference data can be changed only by an admin.
+pub fn get_admin_mount() -> Vec<rocket::Route> {
+    routes![create, update, delete]
+}
+
 // #################################################################################################
 
-fn get_by_id(id: i32, conn: &MoneyManagerDB) -> Result<AccountType, Status> {
+fn get_by_id(id: i32, conn: &MoneyManagerDB) -> Result<AccountType, ApiError> {
     AccountType::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read account_type: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("account_type", e))
 }
 
-fn finalize_update_delete(result: Result<usize, Error>) -> Result<Status, Status> {
+fn finalize_update_delete(result: Result<usize, Error>) -> Result<Status, ApiError> {
     match result {
         Ok(n) if n > 0 => Ok(Status::NoContent),
         Ok(_) => {
             warn!("account_type not found!");
-            Err(Status::NotFound)
+            Err(ApiError::not_found("account_type"))
         },
         Err(e) => {
             error!("An error occurred on account_type: {}", e);
-            Err(Status::InternalServerError)
+            Err(ApiError::internal())
         }
     }
 }
\ No newline at end of file
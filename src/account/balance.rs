@@ -0,0 +1,216 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::prelude::*;
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use diesel::result::Error;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::schema::{transaction, giro};
+use crate::account::model::Account;
+use crate::transaction::model::Transaction;
+
+// the amounts recorded before the direction carry their own sign
+sql_function!(fn abs(x: diesel::sql_types::Double) -> diesel::sql_types::Double);
+
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct AccountBalance {
+    pub id_account: i64,
+    pub at: DateTime<Utc>,
+    pub balance: f64
+}
+
+///
+/// The balance of the account from its initial balance and all its movements out of the trash:
+/// a transaction moves the amount in its direction and always pays its expense,
+/// a giro moves the amount from the source to the destination and the source pays the expense.
+pub fn compute(account: &Account, conn: &PgConnection) -> QueryResult<f64> {
+    sum(account, Bound::All, false, conn)
+}
+
+///
+/// The balance of the account with the movements up to the date (included).
+pub fn at(account: &Account, until: DateTime<Utc>, conn: &PgConnection) -> QueryResult<f64> {
+    sum(account, Bound::Until(until), false, conn)
+}
+
+///
+/// A page of the transactions of the account in the order of the date, each with the balance after it;
+/// the giros move the balance between them, a giro at the same time of a transaction comes first.
+/// The database sums the movements before the page, only the page is loaded.
+pub fn running(account: &Account, offset: i64, limit: i64, conn: &PgConnection) -> QueryResult<Vec<(Transaction, f64)>> {
+    let transactions = transaction::table
+        .filter(transaction::id_account.eq(account.id))
+        .filter(transaction::deleted_at.is_null())
+        .order((transaction::data, transaction::id))
+        .offset(offset)
+        .limit(limit)
+        .load::<Transaction>(conn)?;
+    let (first, until) = match (transactions.first(), transactions.last()) {
+        (Some(first), Some(last)) => ((first.data, first.id), last.data),
+        _ => return Ok(Vec::new())
+    };
+    let mut balance = sum(account, Bound::Before(first.0, first.1), false, conn)?;
+    let giros = giro::table
+        .filter(giro::id_source_account.eq(account.id).or(giro::id_destination_account.eq(account.id)))
+        .filter(giro::deleted_at.is_null())
+        .filter(giro::data.gt(first.0))
+        .filter(giro::data.le(until))
+        .order((giro::data, giro::id))
+        .select((giro::data, giro::id_source_account, giro::amount, giro::expense))
+        .load::<(DateTime<Utc>, Option<i64>, f64, Option<f64>)>(conn)?;
+    let mut giros = giros.into_iter().peekable();
+    let mut ledger = Vec::with_capacity(transactions.len());
+    for t in transactions {
+        while let Some(&(data, id_source, amount, expense)) = giros.peek() {
+            if data > t.data {
+                break;
+            }
+            giros.next();
+            if id_source == Some(account.id) {
+                balance -= amount + expense.unwrap_or(0.0);
+            } else {
+                balance += amount;
+            }
+        }
+        balance += f64::from(t.direction) * t.amount.abs() - t.expense.unwrap_or(0.0);
+        ledger.push((t, balance));
+    }
+    Ok(ledger)
+}
+
+///
+/// How each movement out of the trash changes the balance of the account, in the order of the date.
+pub fn movements(account: &Account, conn: &PgConnection) -> QueryResult<Vec<(DateTime<Utc>, f64)>> {
+    let transactions = transaction::table
+        .filter(transaction::id_account.eq(account.id))
+        .filter(transaction::deleted_at.is_null())
+        .select((transaction::data, transaction::amount, transaction::expense, transaction::direction))
+        .load::<(DateTime<Utc>, f64, Option<f64>, i16)>(conn)?;
+    let giros = giro::table
+        .filter(giro::id_source_account.eq(account.id).or(giro::id_destination_account.eq(account.id)))
+        .filter(giro::deleted_at.is_null())
+        .select((giro::data, giro::id_source_account, giro::amount, giro::expense))
+        .load::<(DateTime<Utc>, Option<i64>, f64, Option<f64>)>(conn)?;
+    let mut movements: Vec<(DateTime<Utc>, f64)> = transactions.into_iter()
+        .map(|(data, amount, expense, direction)| (data, f64::from(direction) * amount.abs() - expense.unwrap_or(0.0)))
+        .chain(giros.into_iter().map(|(data, id_source, amount, expense)| {
+            if id_source == Some(account.id) {
+                (data, -(amount + expense.unwrap_or(0.0)))
+            } else {
+                (data, amount)
+            }
+        }))
+        .collect();
+    movements.sort_by_key(|m| m.0);
+    Ok(movements)
+}
+
+///
+/// The balance that the statement of the bank shows at the date (included): only the cleared movements.
+pub fn cleared(account: &Account, until: DateTime<Utc>, conn: &PgConnection) -> QueryResult<f64> {
+    sum(account, Bound::Until(until), true, conn)
+}
+
+///
+/// Store the computed balance as the current balance of the account.
+pub fn recompute(account: &Account, conn: &PgConnection) -> QueryResult<f64> {
+    let balance = compute(account, conn)?;
+    if (balance - account.current_balance).abs() > f64::EPSILON {
+        info!("account {}: current balance {} corrected to {}", account.id, account.current_balance, balance);
+    }
+    Account::update_current_balance(account.id, balance, conn)?;
+    Ok(balance)
+}
+
+///
+/// Recompute every account, the number of accounts is returned.
+pub fn recompute_all(conn: &PgConnection) -> QueryResult<usize> {
+    let accounts = Account::read(conn)?;
+    conn.transaction::<usize, Error, _>(|| {
+        for account in &accounts {
+            recompute(account, conn)?;
+        }
+        Ok(accounts.len())
+    })
+}
+
+// #################################################################################################
+
+// the movements that a balance counts
+#[derive(Clone,Copy)]
+enum Bound {
+    All,
+    // up to the date (included)
+    Until(DateTime<Utc>),
+    // before the transaction (data, id), the giros up to its time
+    Before(DateTime<Utc>, i64)
+}
+
+// the database sums the movements by direction, no row is loaded
+fn sum(account: &Account, bound: Bound, cleared_only: bool, conn: &PgConnection) -> QueryResult<f64> {
+    let transactions = || {
+        let mut query = transaction::table
+            .filter(transaction::id_account.eq(account.id))
+            .filter(transaction::deleted_at.is_null())
+            .into_boxed::<Pg>();
+        match bound {
+            Bound::All => {},
+            Bound::Until(until) => query = query.filter(transaction::data.le(until)),
+            Bound::Before(data, id) => query = query.filter(transaction::data.lt(data)
+                .or(transaction::data.eq(data).and(transaction::id.lt(id))))
+        }
+        if cleared_only {
+            query = query.filter(transaction::cleared.eq(true));
+        }
+        query
+    };
+    let giros = |outgoing: bool| {
+        let mut query = giro::table
+            .filter(giro::deleted_at.is_null())
+            .into_boxed::<Pg>();
+        query = match (outgoing, cleared_only) {
+            (true, false) => query.filter(giro::id_source_account.eq(account.id)),
+            (true, true) => query.filter(giro::id_source_account.eq(account.id)).filter(giro::cleared_source.eq(true)),
+            (false, false) => query.filter(giro::id_destination_account.eq(account.id)),
+            (false, true) => query.filter(giro::id_destination_account.eq(account.id))
+                .filter(giro::cleared_destination.eq(true))
+        };
+        match bound {
+            Bound::All => {},
+            Bound::Until(until) | Bound::Before(until, _) => query = query.filter(giro::data.le(until))
+        }
+        query
+    };
+    let total = |sum: Option<f64>| sum.unwrap_or(0.0);
+    let income = transactions().filter(transaction::direction.eq(1))
+        .select(dsl::sum(abs(transaction::amount))).first(conn).map(total)?;
+    let spending = transactions().filter(transaction::direction.eq(-1))
+        .select(dsl::sum(abs(transaction::amount))).first(conn).map(total)?;
+    let expense = transactions()
+        .select(dsl::sum(transaction::expense)).first(conn).map(total)?;
+    let outgoing = giros(true)
+        .select(dsl::sum(giro::amount)).first(conn).map(total)?;
+    let giro_expense = giros(true)
+        .select(dsl::sum(giro::expense)).first(conn).map(total)?;
+    let incoming = giros(false)
+        .select(dsl::sum(giro::amount)).first(conn).map(total)?;
+    Ok(account.initial_balance + income - spending - expense - outgoing - giro_expense + incoming)
+}
@@ -19,75 +19,152 @@ use diesel::result::Error;
 use diesel::Connection;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
+use serde_json::Value;
+use chrono::Utc;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::{Validate, Validator};
+use crate::patch;
 use crate::base_model::BaseModel;
-use crate::base_controller::BaseController;
+use crate::base_controller::{BaseController, IfMatch, Tagged};
 use crate::account::model::{Account, AccountForm, AccountUser};
+use crate::account::balance::AccountBalance;
+use crate::audit::model::AuditLog;
+use crate::idempotency::{IdempotencyKey, Replayable};
 use crate::user::model::User;
 
 pub mod model;
+pub mod balance;
 
 mod account_type;
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<AccountForm>, user: User) -> Result<Json<Account>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<AccountForm>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_ACCOUNT_REQUEST");
-    conn.transaction::<Json<Account>, Error, _>(|| {
-        let account = Account::create(&json.into_inner(), &conn)
-            .map_err(|e| { error!("Can not create account: {}", e); e})?;
-        let au = AccountUser {
-            id_user: user.id,
-            id_account: account.id,
-        };
-        AccountUser::create(&au, &conn)
-            .map_err(|e| { error!("Can not create account_user: {}", e); e})?;
-        info!("account create successfully: {}", account.id);
-        Ok(Json(account))
-    }).map_err(|_| Status::InternalServerError)
+    let form = json.into_inner();
+    key.once(&user, "account", &form, &conn, || {
+        form.validate(&user, &conn)?;
+        let account = conn.transaction::<Account, Error, _>(|| {
+            let account = Account::create(&form, &conn)
+                .map_err(|e| { error!("Can not create account: {}", e); e})?;
+            let au = AccountUser {
+                id_user: user.id,
+                id_account: account.id,
+            };
+            AccountUser::create(&au, &conn)
+                .map_err(|e| { error!("Can not create account_user: {}", e); e})?;
+            AuditLog::created(&user, &account, &conn)?;
+            AuditLog::created(&user, &au, &conn)?;
+            info!("account create successfully: {}", account.id);
+            Ok(account)
+        }).map_err(|_| ApiError::internal())?;
+        Replayable::json(Status::Ok, &account)
+    })
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Account>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Tagged<Json<Account>>, ApiError> {
     debug!("READ_ONE_ACCOUNT_REQUEST");
     let account = get_by_id(id, &conn)?;
     // a user can access his own account
     check_property(&conn, &account, &user)?;
-    Ok(Json(account))
+    let version = account.version;
+    Ok(Tagged(Json(account), version))
 }
 
 #[get("/user")]
-pub fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Account>>, Custom<String>> {
+pub fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Account>>, ApiError> {
     debug!("READ_BY_USER_ACCOUNT_REQUEST");
     let result = Account::read_by_user(&user, &conn);
     Account::unpack(result)
 }
 
+///
+/// The balance computed from the movements up to the date, now by default; the rank lets
+/// "/account/type/<id>" go first.
+#[get("/<id>/balance?<at>", rank = 2)]
+fn read_balance(conn: MoneyManagerDB, id: i64, at: Option<String>, user: User) -> Result<Json<AccountBalance>, ApiError> {
+    debug!("READ_BALANCE_ACCOUNT_REQUEST");
+    let account = get_by_id(id, &conn)?;
+    check_property(&conn, &account, &user)?;
+    let mut v = Validator::new();
+    let at = v.date("at", at.as_ref().map(|s| s.as_str()), true);
+    v.finish()?;
+    let at = at.unwrap_or_else(Utc::now);
+    balance::at(&account, at, &conn)
+        .map(|balance| Json(AccountBalance { id_account: account.id, at, balance }))
+        .map_err(|e| {
+            error!("Can not compute the balance of account {} caused by {}", account.id, e);
+            ApiError::internal()
+        })
+}
+
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i64, json: Json<AccountForm>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i64, json: Json<AccountForm>, user: User,
+          if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("UPDATE_ACCOUNT_REQUEST");
     let account = get_by_id(id, &conn)?;
     // check if account can be updated
     check_property(&conn, &account, &user)?;
-    let result = Account::update(&account, &json.into_inner(), &conn);
-    Account::finalize_update_delete(result)
+    if_match.check(account.version)?;
+    let form = json.into_inner();
+    form.validate(&user, &conn)?;
+    save(&account, &form, &user, &conn)
+}
+
+#[patch("/<id>", data = "<json>", format = "application/json")]
+fn patch(conn: MoneyManagerDB, id: i64, json: Json<Value>, user: User,
+         if_match: IfMatch) -> Result<Status, ApiError> {
+    debug!("PATCH_ACCOUNT_REQUEST");
+    let account = get_by_id(id, &conn)?;
+    check_property(&conn, &account, &user)?;
+    if_match.check(account.version)?;
+    // only the fields of the patch change, the others are the current ones
+    let merged = patch::apply(&account, &json)?;
+    let form = patch::form::<AccountForm>(&merged)?;
+    form.validate(&user, &conn)?;
+    save(&account, &form, &user, &conn)
 }
 
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i64, user: User, if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("DELETE_ACCOUNT_REQUEST");
     let account = get_by_id(id, &conn)?;
     // check if causal can be deleted
     check_property(&conn, &account, &user)?;
-    let result = Account::delete(&account, &conn);
+    if_match.check(account.version)?;
+    // the movements moved to the trash with the account are in its entry only
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Account::delete(&account, &conn)?;
+        if n > 0 {
+            AuditLog::deleted(&user, &account, &conn)?;
+        }
+        Ok(n)
+    });
+    Account::finalize_versioned(result)
+}
+
+#[post("/<id>/restore")]
+fn restore(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("RESTORE_ACCOUNT_REQUEST");
+    let account = Account::read_deleted_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("account", e))?;
+    check_property(&conn, &account, &user)?;
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Account::restore(&account, &conn)?;
+        let after = Account::read_by_id(account.id, &conn)?;
+        AuditLog::restored(&user, &after, &conn)?;
+        Ok(n)
+    });
     Account::finalize_update_delete(result)
 }
 
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/account", routes![read_one, read_by_user, create, update, delete])
+    rocket.mount("/account", routes![read_one, read_by_user, read_balance, create, update, patch, delete, restore])
 }
 
 ///
@@ -98,7 +175,13 @@ pub fn mount_account_type(rocket: rocket::Rocket) -> rocket::Rocket {
 
 ///
 ///
-pub fn get_and_check(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Result<Account, Status> {
+pub fn mount_admin_account_type(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/admin/account/type", account_type::get_admin_mount())
+}
+
+///
+///
+pub fn get_and_check(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Result<Account, ApiError> {
     let account = get_by_id(id_account, conn)?;
     check_property(conn, &account, user)?;
     Ok(account)
@@ -106,38 +189,44 @@ pub fn get_and_check(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Res
 
 ///
 ///
-pub fn check(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Result<(), Status> {
+pub fn check(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Result<(), ApiError> {
     check_property_by_id(conn, id_account, user)
 }
 
 // #################################################################################################
 
-fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Account, Status> {
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Account, ApiError> {
     Account::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read account: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("account", e))
 }
 
-fn check_property_by_id(conn: &MoneyManagerDB, id_account: i64, user: &User) -> Result<(), Status> {
+fn check_property_by_id(conn: &MoneyManagerDB, id_account: i64, user: &User) -> Result<(), ApiError> {
     AccountUser::read_for_check(conn, user, id_account)
         .map(|_| Ok(()))
         .map_err(|e| {
             if e.eq(&Error::NotFound) {
                 warn!("The user attempts to access account that does not belong to it! {}", e);
-                Status::Forbidden
+                ApiError::forbidden("account")
             } else {
                 error!("{}", e);
-                Status::InternalServerError
+                ApiError::internal()
             }
         })?
 }
 
-fn check_property(conn: &MoneyManagerDB, account: &Account, user: &User) -> Result<(), Status> {
+fn check_property(conn: &MoneyManagerDB, account: &Account, user: &User) -> Result<(), ApiError> {
     check_property_by_id(conn, account.id, user)
 }
+
+fn save(account: &Account, form: &AccountForm, user: &User, conn: &MoneyManagerDB) -> Result<Status, ApiError> {
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Account::update(account, form, conn)?;
+        if n == 0 {
+            return Ok(0);
+        }
+        let after = Account::read_by_id(account.id, conn)?;
+        AuditLog::updated(user, account, &after, conn)?;
+        Ok(n)
+    });
+    Account::finalize_versioned(result)
+}
@@ -17,18 +17,24 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
 use diesel::pg::expression::dsl::any;
 use chrono::{DateTime, Utc};
 use diesel::result::Error;
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
-use crate::schema::{account, account_user, account_type};
+use crate::schema::{account, account_user, account_type, transaction, transaction_detail, giro};
 use crate::user::model::User;
-use crate::database::MoneyManagerDB;
+use crate::currency::model::Currency;
+use crate::validation::{Validate, Validator};
+use crate::audit::model::Audited;
+use crate::reconciliation::model::Reconciliation;
+use crate::error::ApiError;
 
 #[table_name = "account"]
 #[belongs_to(AccountType, foreign_key = "id_account_type")]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,JsonSchema)]
 pub struct Account {
     pub id: i64,
     pub name: String,
@@ -38,12 +44,15 @@ pub struct Account {
     pub initial_balance: f64,
     pub creation_date: DateTime<Utc>,
     pub id_account_type: i32,
-    pub id_currency: i16
+    pub id_currency: i16,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32
 }
 
-// only for insert and update
+// only for insert and update, the update replaces the whole row: a missing field becomes null
 #[table_name = "account"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+#[derive(Debug,Serialize,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct AccountForm<'a> {
     pub name: &'a str,
     pub status: bool,
@@ -66,7 +75,7 @@ pub struct AccountUser {
 }
 
 #[table_name="account_type"]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
 pub struct AccountType {
     pub id: i32,
     #[serde(rename="type")]
@@ -75,126 +84,279 @@ pub struct AccountType {
 
 // only for insert and update
 #[table_name="account_type"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[derive(Debug,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct AccountTypeForm<'a> {
     #[serde(rename="type")]
     pub type_: &'a str
 }
 
 impl Account {
-    pub fn create(form: &AccountForm, conn: &MoneyManagerDB) -> QueryResult<Account> {
+    pub fn create(form: &AccountForm, conn: &PgConnection) -> QueryResult<Account> {
         diesel::insert_into(account::table)
             .values(form)
-            .get_result::<Account>(&*(*conn))
+            .get_result::<Account>(conn)
+            .map_err(|e| { error!("{}", e); e })
+    }
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Account>> {
+        account::table.filter(account::deleted_at.is_null())
+            .load::<Account>(conn)
             .map_err(|e| { error!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Account>> {
-        account::table.load::<Account>(&**conn)
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Account> {
+        account::table.find(id)
+            .filter(account::deleted_at.is_null())
+            .first::<Account>(conn)
             .map_err(|e| { error!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Account> {
-        account::table.find(id).first::<Account>(&*(*conn))
+    pub fn read_deleted_by_id(id: i64, conn: &PgConnection) -> QueryResult<Account> {
+        account::table.find(id)
+            .filter(account::deleted_at.is_not_null())
+            .first::<Account>(conn)
             .map_err(|e| { error!("{}", e); e })
     }
-    pub fn read_by_user(user: &User, conn: &MoneyManagerDB) -> QueryResult<Vec<Account>> {
+    pub fn read_any_by_id(id: i64, conn: &PgConnection) -> QueryResult<Account> {
+        account::table.find(id).first::<Account>(conn)
+            .map_err(|e| { error!("{}", e); e })
+    }
+    pub fn read_by_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<Account>> {
         let ids = AccountUser::belonging_to(user)
             .select(account_user::id_account)
-            .load::<i64>(&*(*conn))
+            .load::<i64>(conn)
             .map_err(|e| { error!("{}", e); e })?;
         account::table
             .filter(account::id.eq(any(ids)))
-            .load::<Account>(&*(*conn))
+            .filter(account::deleted_at.is_null())
+            .load::<Account>(conn)
             .map_err(|e| { error!("{}", e); e })
     }
-    pub fn update(account: &Account, form: &AccountForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::update(account)
-            .set(form)
-            .execute(&*(*conn))
+    ///
+    /// Only the version that was read is updated, no row means that it has changed in the meantime.
+    pub fn update(account: &Account, form: &AccountForm, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(account::table.find(account.id).filter(account::version.eq(account.version)))
+            .set((form, account::version.eq(account.version + 1)))
+            .execute(conn)
+            .map_err(|e| { error!("{}", e); e })
+    }
+    pub fn update_current_balance(id: i64, balance: f64, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(account::table.find(id))
+            .set(account::current_balance.eq(balance))
+            .execute(conn)
             .map_err(|e| { error!("{}", e); e })
     }
-    pub fn delete(account: &Account, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    ///
+    /// Move the account to the trash with its transactions and giros, all with the same date
+    /// so that they come back together; a giro stays with the other account while it is not in the trash.
+    pub fn delete(account: &Account, conn: &PgConnection) -> QueryResult<usize> {
+        let now = Utc::now();
+        conn.transaction::<usize, Error, _>(|| {
+            let n = diesel::update(account::table.find(account.id).filter(account::version.eq(account.version)))
+                .set((account::deleted_at.eq(now), account::version.eq(account.version + 1)))
+                .execute(conn)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            diesel::update(transaction::table
+                .filter(transaction::id_account.eq(account.id))
+                .filter(transaction::deleted_at.is_null()))
+                .set(transaction::deleted_at.eq(now))
+                .execute(conn)?;
+            let live = || account::table
+                .filter(account::deleted_at.is_null())
+                .select(account::id.nullable());
+            diesel::update(giro::table
+                .filter(giro::id_source_account.eq(account.id)
+                    .and(giro::id_destination_account.is_null().or(giro::id_destination_account.ne_all(live())))
+                    .or(giro::id_destination_account.eq(account.id)
+                        .and(giro::id_source_account.is_null().or(giro::id_source_account.ne_all(live())))))
+                .filter(giro::deleted_at.is_null()))
+                .set(giro::deleted_at.eq(now))
+                .execute(conn)?;
+            Ok(n)
+        }).map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Take the account out of the trash with the transactions and giros deleted with it.
+    pub fn restore(account: &Account, conn: &PgConnection) -> QueryResult<usize> {
+        conn.transaction::<usize, Error, _>(|| {
+            if let Some(deleted_at) = account.deleted_at {
+                diesel::update(transaction::table
+                    .filter(transaction::id_account.eq(account.id))
+                    .filter(transaction::deleted_at.eq(deleted_at)))
+                    .set(transaction::deleted_at.eq(None::<DateTime<Utc>>))
+                    .execute(conn)?;
+                diesel::update(giro::table
+                    .filter(giro::id_source_account.eq(account.id).or(giro::id_destination_account.eq(account.id)))
+                    .filter(giro::deleted_at.eq(deleted_at)))
+                    .set(giro::deleted_at.eq(None::<DateTime<Utc>>))
+                    .execute(conn)?;
+            }
+            diesel::update(account)
+                .set((account::deleted_at.eq(None::<DateTime<Utc>>), account::version.eq(account.version + 1)))
+                .execute(conn)
+        }).map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Delete for good the account with all its transactions and the giros in the trash;
+    /// the other giros lose this side and stay with the other account.
+    pub fn purge(account: &Account, conn: &PgConnection) -> QueryResult<usize> {
         conn.transaction::<usize, Error, _>(|| {
-            AccountUser::delete_by_account(account, conn)?; // TODO mettere fuori
-            // TODO delete all the other related data
+            let transactions = transaction::table
+                .filter(transaction::id_account.eq(account.id))
+                .select(transaction::id);
+            diesel::delete(transaction_detail::table
+                .filter(transaction_detail::id_transaction.eq_any(transactions)))
+                .execute(conn)?;
+            diesel::delete(transaction::table.filter(transaction::id_account.eq(account.id)))
+                .execute(conn)?;
+            // the transactions of the other accounts only lose the beneficiary
+            diesel::update(transaction::table.filter(transaction::id_beneficiary.eq(account.id)))
+                .set(transaction::id_beneficiary.eq(None::<i64>))
+                .execute(conn)?;
+            // the giros in the trash went away with the account, the others stay with the other account
+            diesel::delete(giro::table
+                .filter(giro::id_source_account.eq(account.id).or(giro::id_destination_account.eq(account.id)))
+                .filter(giro::deleted_at.is_not_null()
+                    .or(giro::id_source_account.is_null())
+                    .or(giro::id_destination_account.is_null())))
+                .execute(conn)?;
+            diesel::update(giro::table.filter(giro::id_source_account.eq(account.id)))
+                .set(giro::id_source_account.eq(None::<i64>))
+                .execute(conn)?;
+            diesel::update(giro::table.filter(giro::id_destination_account.eq(account.id)))
+                .set(giro::id_destination_account.eq(None::<i64>))
+                .execute(conn)?;
+            // nothing locked by them is left
+            Reconciliation::delete_by_account(account, conn)?;
+            AccountUser::delete_by_account(account, conn)?;
             diesel::delete(account)
-                .execute(&*(*conn))
-                .map_err(|e| { warn!("{}", e); e })
-        })
+                .execute(conn)
+        }).map_err(|e| { warn!("{}", e); e })
     }
 }
 
 impl AccountUser {
-    pub fn create(form: &AccountUser, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn create(form: &AccountUser, conn: &PgConnection) -> QueryResult<usize> {
         diesel::insert_into(account_user::table)
             .values(form)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_user(conn: &MoneyManagerDB, user: &User) -> QueryResult<Vec<AccountUser>> {
+    pub fn read_by_user(conn: &PgConnection, user: &User) -> QueryResult<Vec<AccountUser>> {
         AccountUser::belonging_to(user)
-            .load::<AccountUser>(&*(*conn))
+            .load::<AccountUser>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_account(conn: &MoneyManagerDB, account: &Account) -> QueryResult<Vec<AccountUser>> {
+    pub fn read_by_account(conn: &PgConnection, account: &Account) -> QueryResult<Vec<AccountUser>> {
         AccountUser::belonging_to(account)
-            .load::<AccountUser>(&**conn)
+            .load::<AccountUser>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_au(conn: &MoneyManagerDB, user: &User, account: &Account) -> QueryResult<AccountUser> {
+    pub fn read_by_au(conn: &PgConnection, user: &User, account: &Account) -> QueryResult<AccountUser> {
         AccountUser::read_for_check(conn, user, account.id)
     }
-    pub fn read_for_check(conn: &MoneyManagerDB, user: &User, id_account: i64) -> QueryResult<AccountUser> {
+    pub fn read_for_check(conn: &PgConnection, user: &User, id_account: i64) -> QueryResult<AccountUser> {
         account_user::table
             .filter(account_user::id_user.eq(user.id))
             .filter(account_user::id_account.eq(id_account))
-            .first::<AccountUser>(&*(*conn))
+            .first::<AccountUser>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(conn: &MoneyManagerDB, user: &User, account: &Account) -> QueryResult<usize> {
+    pub fn delete(conn: &PgConnection, user: &User, account: &Account) -> QueryResult<usize> {
         diesel::delete(account_user::table
             .filter(account_user::id_account.eq(account.id))
             .filter(account_user::id_user.eq(user.id)))
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete_by_account(account: &Account, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn delete_by_account(account: &Account, conn: &PgConnection) -> QueryResult<usize> {
         diesel::delete(account_user::table
             .filter(account_user::id_account.eq(account.id)))
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete_by_user(user: &User, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    ///
+    /// Number of users that share the account.
+    pub fn count_by_account(id_account: i64, conn: &PgConnection) -> QueryResult<i64> {
+        account_user::table
+            .filter(account_user::id_account.eq(id_account))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete_by_user(user: &User, conn: &PgConnection) -> QueryResult<usize> {
         diesel::delete(account_user::table
             .filter(account_user::id_user.eq(user.id)))
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
 
+impl Audited for Account {
+    const ENTITY: &'static str = "account";
+    fn entity_id(&self) -> i64 {
+        self.id
+    }
+    fn id_account(&self, _conn: &PgConnection) -> QueryResult<i64> {
+        Ok(self.id)
+    }
+}
+
+// logged with the account as id, the user is in the body
+impl Audited for AccountUser {
+    const ENTITY: &'static str = "account_user";
+    fn entity_id(&self) -> i64 {
+        self.id_account
+    }
+    fn id_account(&self, _conn: &PgConnection) -> QueryResult<i64> {
+        Ok(self.id_account)
+    }
+}
+
 impl AccountType {
-    pub fn create(form: &AccountTypeForm, conn: &MoneyManagerDB) -> QueryResult<AccountType> {
+    pub fn create(form: &AccountTypeForm, conn: &PgConnection) -> QueryResult<AccountType> {
         diesel::insert_into(account_type::table)
             .values(form)
-            .get_result::<AccountType>(&*(*conn))
+            .get_result::<AccountType>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<AccountType>> {
-        account_type::table.load::<AccountType>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<AccountType>> {
+        account_type::table.load::<AccountType>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i32, conn: &MoneyManagerDB) -> QueryResult<AccountType> {
-        account_type::table.find(id).first::<AccountType>(&*(*conn))
+    pub fn read_by_id(id: i32, conn: &PgConnection) -> QueryResult<AccountType> {
+        account_type::table.find(id).first::<AccountType>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(at: &AccountType, form: &AccountTypeForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn update(at: &AccountType, form: &AccountTypeForm, conn: &PgConnection) -> QueryResult<usize> {
         diesel::update(at)
             .set(form)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(at: &AccountType, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn delete(at: &AccountType, conn: &PgConnection) -> QueryResult<usize> {
         diesel::delete(at)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
+
+impl<'a> Validate for AccountForm<'a> {
+    fn validate(&self, _user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("name", self.name, 64);
+        v.max_length("note", self.note, 255);
+        v.check(self.initial_balance.is_finite(), "initial_balance", "must be a number");
+        v.check(self.current_balance.is_finite(), "current_balance", "must be a number");
+        v.reference("id_account_type", AccountType::read_by_id(self.id_account_type, conn))?;
+        v.reference("id_currency", Currency::read_by_id(self.id_currency, conn))?;
+        v.finish()
+    }
+}
+
+impl<'a> Validate for AccountTypeForm<'a> {
+    fn validate(&self, _user: &User, _conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("type", self.type_, 32);
+        v.finish()
+    }
+}
@@ -0,0 +1,66 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::base_model::BaseModel;
+use crate::base_controller::BaseController;
+use crate::auth::auth::Admin;
+use crate::user::model::User;
+use crate::account;
+use crate::currency;
+use crate::transaction;
+
+#[get("/user")]
+fn read_users(conn: MoneyManagerDB, admin: Admin) -> Result<Json<Vec<User>>, ApiError> {
+    debug!("ADMIN_READ_USER_REQUEST");
+    info!("The admin {} has accessed the user list", admin.0.id);
+    let result = User::read(&conn);
+    User::unpack(result)
+}
+
+#[put("/user/<id>/disable")]
+fn disable_user(conn: MoneyManagerDB, id: i64, admin: Admin) -> Result<Status, ApiError> {
+    debug!("ADMIN_DISABLE_USER_REQUEST");
+    if id == admin.0.id {
+        warn!("The admin {} attempts to disable himself!", id);
+        return Err(ApiError::bad_request("An admin can not disable himself"));
+    }
+    let result = User::update_disabled(id, true, &conn);
+    info!("The admin {} has disabled the user {}", admin.0.id, id);
+    User::finalize_update_delete(result)
+}
+
+#[put("/user/<id>/enable")]
+fn enable_user(conn: MoneyManagerDB, id: i64, admin: Admin) -> Result<Status, ApiError> {
+    debug!("ADMIN_ENABLE_USER_REQUEST");
+    let result = User::update_disabled(id, false, &conn);
+    info!("The admin {} has enabled the user {}", admin.0.id, id);
+    User::finalize_update_delete(result)
+}
+
+///
+/// Administration and reference data (currency, account type, transaction type).
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    let rocket = rocket.mount("/admin", routes![read_users, disable_user, enable_user]);
+    let rocket = currency::mount_admin(rocket);
+    let rocket = account::mount_admin_account_type(rocket);
+    transaction::mount_admin_transaction_type(rocket)
+}
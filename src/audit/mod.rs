@@ -0,0 +1,57 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use rocket_contrib::json::Json;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::base_model::BaseModel;
+use crate::validation::Validator;
+use crate::audit::model::{AuditLog, ENTITIES};
+use crate::user::model::User;
+
+pub mod model;
+
+#[get("/?<entity>&<id>")]
+fn read_by_entity(conn: MoneyManagerDB, entity: String, id: i64, user: User) -> Result<Json<Vec<AuditLog>>, ApiError> {
+    debug!("READ_BY_ENTITY_AUDIT_REQUEST");
+    let mut v = Validator::new();
+    v.check(ENTITIES.contains(&entity.as_str()), "entity", &format!("must be one of {}", ENTITIES.join(", ")));
+    v.finish()?;
+    let result = AuditLog::read_by_entity(&entity, id, &user, &conn);
+    if let Ok(ref entries) = result {
+        // entries exist, but in accounts of other users
+        if entries.is_empty() && count_by_entity(&entity, id, &conn)? > 0 {
+            warn!("The user attempts to read the audit log of {} {} that does not belong to it!", entity, id);
+            return Err(ApiError::forbidden(&entity));
+        }
+    }
+    AuditLog::unpack(result)
+}
+
+///
+///
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/audit", routes![read_by_entity])
+}
+
+// #################################################################################################
+
+fn count_by_entity(entity: &str, id: i64, conn: &MoneyManagerDB) -> Result<i64, ApiError> {
+    AuditLog::count_by_entity(entity, id, conn)
+        .map_err(|e| ApiError::from_db("audit log", e))
+}
@@ -0,0 +1,147 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use schemars::JsonSchema;
+
+use crate::schema::{audit_log, account_user, giro};
+use crate::user::model::User;
+
+pub const ENTITIES: [&str; 6] = ["transaction", "giro", "account", "transaction_detail", "account_user",
+                                  "reconciliation"];
+
+#[table_name = "audit_log"]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
+pub struct AuditLog {
+    pub id: i64,
+    pub id_user: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    pub id_account: i64,
+    pub action: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub creation_date: DateTime<Utc>
+}
+
+// only for insert, the log is never updated
+#[table_name = "audit_log"]
+#[derive(Debug,Insertable)]
+struct AuditLogForm<'a> {
+    id_user: i64,
+    entity: &'a str,
+    entity_id: i64,
+    id_account: i64,
+    action: &'a str,
+    before: Option<Value>,
+    after: Option<Value>
+}
+
+///
+/// An entity whose changes are logged: the id is the one used to query the log,
+/// the account decides who can read its entries.
+pub trait Audited: Serialize {
+    const ENTITY: &'static str;
+    fn entity_id(&self) -> i64;
+    fn id_account(&self, conn: &PgConnection) -> QueryResult<i64>;
+}
+
+impl AuditLog {
+    ///
+    /// Log a change, in the same transaction of the change so one is never saved without the other.
+    pub fn created<T: Audited>(user: &User, after: &T, conn: &PgConnection) -> QueryResult<()> {
+        AuditLog::create(user, "create", after, None, Some(after), conn)
+    }
+    pub fn updated<T: Audited>(user: &User, before: &T, after: &T, conn: &PgConnection) -> QueryResult<()> {
+        AuditLog::create(user, "update", after, Some(before), Some(after), conn)
+    }
+    pub fn deleted<T: Audited>(user: &User, before: &T, conn: &PgConnection) -> QueryResult<()> {
+        AuditLog::create(user, "delete", before, Some(before), None, conn)
+    }
+    pub fn restored<T: Audited>(user: &User, after: &T, conn: &PgConnection) -> QueryResult<()> {
+        AuditLog::create(user, "restore", after, None, Some(after), conn)
+    }
+    ///
+    /// The entries of the entity that belong to the accounts of the user, oldest first.
+    /// The entries of a giro are on one account, the user of the other one reads them too.
+    pub fn read_by_entity(entity: &str, entity_id: i64, user: &User, conn: &PgConnection) -> QueryResult<Vec<AuditLog>> {
+        let accounts = || account_user::table.filter(account_user::id_user.eq(user.id));
+        let mut query = audit_log::table
+            .filter(audit_log::entity.eq(entity))
+            .filter(audit_log::entity_id.eq(entity_id))
+            .into_boxed();
+        if entity == "giro" {
+            let giros = giro::table
+                .filter(giro::id_source_account.eq_any(accounts().select(account_user::id_account.nullable()))
+                    .or(giro::id_destination_account.eq_any(accounts().select(account_user::id_account.nullable()))))
+                .select(giro::id);
+            query = query.filter(audit_log::id_account.eq_any(accounts().select(account_user::id_account))
+                .or(audit_log::entity_id.eq_any(giros)));
+        } else {
+            query = query.filter(audit_log::id_account.eq_any(accounts().select(account_user::id_account)));
+        }
+        query
+            .order((audit_log::creation_date, audit_log::id))
+            .load::<AuditLog>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn count_by_entity(entity: &str, entity_id: i64, conn: &PgConnection) -> QueryResult<i64> {
+        audit_log::table
+            .filter(audit_log::entity.eq(entity))
+            .filter(audit_log::entity_id.eq(entity_id))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Only for the erasure of the accounts of a user.
+    pub fn delete_by_account(id_account: i64, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(audit_log::table.filter(audit_log::id_account.eq(id_account)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+
+    fn create<T: Audited>(user: &User, action: &str, subject: &T, before: Option<&T>, after: Option<&T>,
+                          conn: &PgConnection) -> QueryResult<()> {
+        let form = AuditLogForm {
+            id_user: user.id,
+            entity: T::ENTITY,
+            entity_id: subject.entity_id(),
+            id_account: subject.id_account(conn)?,
+            action,
+            before: before.map(to_json).transpose()?,
+            after: after.map(to_json).transpose()?
+        };
+        diesel::insert_into(audit_log::table)
+            .values(&form)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|e| { error!("Can not log the {} of {} {}: {}", action, form.entity, form.entity_id, e); e })
+    }
+}
+
+// #################################################################################################
+
+fn to_json<T: Serialize>(value: &T) -> QueryResult<Value> {
+    serde_json::to_value(value).map_err(|e| Error::SerializationError(Box::new(e)))
+}
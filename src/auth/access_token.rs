@@ -0,0 +1,116 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde::Deserialize;
+use schemars::JsonSchema;
+use chrono::{DateTime, Utc};
+use rocket_contrib::json::{Json, JsonValue};
+use rocket::http::Status;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::base_model::BaseModel;
+use crate::base_controller::BaseController;
+use crate::auth::auth;
+use crate::auth::model::{AccessToken, AccessTokenForm};
+use crate::user::model::User;
+use crate::validation::Validator;
+
+#[derive(Debug,Deserialize,JsonSchema)]
+pub(crate) struct AccessTokenJSON<'a> {
+    name: &'a str,
+    scopes: Vec<&'a str>,
+    expiration_date: Option<DateTime<Utc>>
+}
+
+#[post("/", data = "<json>", format = "application/json")]
+fn create(conn: MoneyManagerDB, json: Json<AccessTokenJSON>, user: User) -> Result<Json<JsonValue>, ApiError> {
+    debug!("CREATE_ACCESS_TOKEN_REQUEST");
+    if json.scopes.is_empty() || json.scopes.iter().any(|s| !auth::SCOPES.contains(s)) {
+        warn!("The user {} requested an access token with invalid scopes {:?}", user.id, json.scopes);
+        return Err(ApiError::bad_request("The scopes are not valid"));
+    }
+    let mut v = Validator::new();
+    v.not_blank("name", json.name, 64);
+    v.check(json.expiration_date.map_or(true, |d| d > Utc::now()), "expiration_date", "must be in the future");
+    v.finish()?;
+    let (secret, token_hash) = auth::create_random_token();
+    let scopes = json.scopes.join(",");
+    let form = AccessTokenForm {
+        id_user: user.id,
+        name: json.name,
+        token_hash: &token_hash,
+        scopes: &scopes,
+        expiration_date: json.expiration_date
+    };
+    AccessToken::create(&form, &conn)
+        .map(|at| {
+            info!("access token create successfully {} for user {}", at.id, user.id);
+            // the token is shown only now, it can not be read again
+            Json(json!({
+                "id": at.id,
+                "name": at.name,
+                "scopes": at.scope_list(),
+                "creation_date": at.creation_date,
+                "expiration_date": at.expiration_date,
+                "token": format!("{}{}", auth::ACCESS_TOKEN_PREFIX, secret)
+            }))
+        })
+        .map_err(|e| {
+            error!("Can not create access token caused by {}", e);
+            ApiError::internal()
+        })
+}
+
+#[get("/")]
+fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<AccessToken>>, ApiError> {
+    debug!("READ_BY_USER_ACCESS_TOKEN_REQUEST");
+    let result = AccessToken::read_by_user(&user, &conn);
+    AccessToken::unpack(result)
+}
+
+#[delete("/<id>")]
+fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("DELETE_ACCESS_TOKEN_REQUEST");
+    let token = get_by_id(id, &conn)?;
+    check_property(&token, &user)?;
+    let result = AccessToken::delete(&token, &conn);
+    info!("The user {} has revoked the access token {}", user.id, token.id);
+    AccessToken::finalize_update_delete(result)
+}
+
+///
+///
+pub fn get_mount() -> Vec<rocket::Route> {
+    routes![create, read_by_user, delete]
+}
+
+// #################################################################################################
+
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<AccessToken, ApiError> {
+    AccessToken::read_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("access token", e))
+}
+
+fn check_property(token: &AccessToken, user: &User) -> Result<(), ApiError> {
+    if token.id_user != user.id {
+        warn!("The user attempts to access access token that does not belong to it!");
+        Err(ApiError::forbidden("access token"))
+    } else {
+        Ok(())
+    }
+}
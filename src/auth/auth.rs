@@ -15,38 +15,56 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-extern crate crypto;
-extern crate jwt;
-
 use rocket::{Outcome, State};
-use rocket::http::Status;
+use rocket::http::{Method, Status};
 use rocket::request::{self, Request, FromRequest};
 use ring::{rand, pbkdf2, digest};
 use ring::rand::SecureRandom;
 use data_encoding::HEXUPPER;
-use crypto::sha2::Sha256;
 use std::time::{SystemTime, UNIX_EPOCH};
-use jwt::{Header, Registered, Token};
 
-use crate::controller::Extras;
+use crate::settings::Settings;
+use crate::auth::token::{Claims, KeyStore};
 use crate::user::model::User;
-use crate::auth::model::Auth;
+use crate::auth::model::{Auth, AccessToken};
 use crate::database::MoneyManagerDB;
+use crate::metrics;
+
+///
+/// A user with the admin role.
+pub struct Admin(pub User);
 
 pub struct ApiKey {
     pub sub: i64,
-    pub exp: u64
+    pub exp: u64,
+    // milliseconds
+    pub iat: i64,
+    // only for personal access token, a JWT can do everything
+    pub scopes: Option<Vec<String>>
 }
 
 const DEFAULT_ITERATION: i16 = 1000;
 const CREDENTIAL_LEN: usize = digest::SHA256_OUTPUT_LEN;
 
+pub const ACCESS_TOKEN_PREFIX: &str = "mmpat_";
+pub const SCOPE_READ: &str = "read";
+pub const SCOPE_WRITE: &str = "write";
+pub const SCOPES: [&str; 9] = [SCOPE_READ, SCOPE_WRITE, "accounts:write", "transactions:write",
+    "giros:write", "causals:write", "places:write", "details:write", "rules:write"];
+// the scope needed to write on a resource, a resource missing here can not be written with an access token
+const WRITE_SCOPES: [(&str, &str); 7] = [("account", "accounts:write"), ("transaction", "transactions:write"),
+    ("giro", "giros:write"), ("causal", "causals:write"), ("place", "places:write"), ("detail", "details:write"),
+    ("rule", "rules:write")];
+
 #[derive(Debug)]
 pub enum ApiKeyError {
     BadCount,
     Missing,
     Invalid,
-    Broken
+    Broken,
+    Scope,
+    Disabled,
+    Role
 }
 
 ///
@@ -75,26 +93,24 @@ pub fn create_auth(email: &str,
         iteration: it as i16,
         salt: salt_str,
         stored_key: HEXUPPER.encode(&salted_pwd),
-        last_login: None
+        last_login: None,
+        tokens_valid_after: None
     })
 }
 
 ///
 /// Create the token for the current user session.
-pub fn create_token(user: &User, extra: &State<Extras>) -> Result<String, Status> {
-    trace!("extras: {:?}", extra);
-    let header: Header = Default::default();
-    let exp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + extra.jwt_exp;
-    let claims = Registered {
-        sub: Some(user.id.to_string()),
-        exp: Some(exp),
-        ..Default::default()
+pub fn create_token(user: &User, settings: &State<Settings>, keys: &State<KeyStore>) -> Result<String, Status> {
+    trace!("jwt_exp: {}", settings.jwt_exp);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let claims = Claims {
+        sub: user.id.to_string(),
+        exp: now.as_secs() + settings.jwt_exp,
+        iat: now.as_millis() as f64 / 1000.0
     };
-    let token = Token::new(header, claims);
-    token.signed(extra.jwt_key.as_bytes(), Sha256::new())
-        .map(|token| token)
+    keys.sign(&claims)
         .map_err(|e| {
-            error!("Can not generate token caused by {:?}", e);
+            error!("Can not generate token caused by {}", e);
             Status::InternalServerError
         })
 }
@@ -119,61 +135,175 @@ pub fn login(auth: &Auth, pwd: &str, conn: &MoneyManagerDB) -> bool {
     result.is_ok()
 }
 
+///
+/// Create a random token (password reset, access token).
+/// Return the token to send to the user and its hash to store.
+pub fn create_random_token() -> (String, String) {
+    let rng = rand::SystemRandom::new();
+    let mut token = [0u8; CREDENTIAL_LEN];
+    rng.fill(&mut token).unwrap();
+    let token = HEXUPPER.encode(&token);
+    let token_hash = hash_token(&token);
+    (token, token_hash)
+}
+
+///
+/// Only the hash of a token is stored, so a leaked database can not be used to access the accounts.
+pub fn hash_token(token: &str) -> String {
+    HEXUPPER.encode(digest::digest(&digest::SHA256, token.as_bytes()).as_ref())
+}
+
 // #################################################################################################
 
-fn read_token(key: &str, secret: &String) -> Result<ApiKey, String> {
-    let token = Token::<Header, Registered>::parse(key)
+fn read_token(key: &str, keys: &KeyStore) -> Result<ApiKey, String> {
+    let claims = keys.verify(key)
         .map_err(|e| {
-            error!("can not parse key {:?}", e);
-            "Unable to parse key".to_string()
+            error!("token invalid: {}", e);
+            "Token not valid".to_string()
         })?;
-    // verify token
-    if token.verify(secret.as_bytes(), Sha256::new()) {
-        Ok(ApiKey {
-            sub: token.claims.sub.ok_or("sub not valid".to_string())?.parse::<i64>().unwrap(),
-            exp: token.claims.exp.ok_or("exp not valid".to_string())?
-        })
-    } else {
-        error!("token invalid {:?}", token);
-        Err("Token not valid".to_string())
+    Ok(ApiKey {
+        sub: claims.sub.parse::<i64>().map_err(|_| "sub not valid".to_string())?,
+        exp: claims.exp,
+        // tokens issued before the claim existed are considered old
+        iat: (claims.iat * 1000.0).round() as i64,
+        scopes: None
+    })
+}
+
+fn read_access_token(key: &str, conn: &MoneyManagerDB) -> Result<ApiKey, String> {
+    let token = AccessToken::read_by_hash(&hash_token(key), conn)
+        .map_err(|e| format!("Access token not valid: {}", e))?;
+    if !AccessToken::update_last_used(&token, conn).is_ok() {
+        error!("Can not update last use of access token {}", token.id);
+    }
+    Ok(ApiKey {
+        sub: token.id_user,
+        exp: token.expiration_date.map(|d| d.timestamp() as u64).unwrap_or(u64::max_value()),
+        iat: token.creation_date.timestamp_millis(),
+        scopes: Some(token.scope_list())
+    })
+}
+
+///
+/// The scope needed by a request: read for the safe methods, write on the resource otherwise.
+/// Authentication data, administration and the user itself can be handled only with a password login.
+fn required_scope(request: &Request) -> Option<&'static str> {
+    let resource = request.uri().path().trim_start_matches('/').split('/').next().unwrap_or("");
+    match (request.method(), resource) {
+        (_, "auth") | (_, "admin") | (_, "user") => None,
+        (Method::Get, _) | (Method::Head, _) => Some(SCOPE_READ),
+        (_, resource) => WRITE_SCOPES.iter().find(|(r, _)| *r == resource).map(|(_, scope)| *scope)
     }
 }
 
+fn is_scope_allowed(key: &ApiKey, request: &Request) -> bool {
+    match key.scopes {
+        None => true,
+        Some(ref scopes) => match required_scope(request) {
+            Some(SCOPE_READ) => scopes.iter().any(|s| s == SCOPE_READ),
+            Some(scope) => scopes.iter().any(|s| s == scope || s == SCOPE_WRITE),
+            None => false
+        }
+    }
+}
+
+///
+/// Refuse the request, the failures are counted by reason in the metrics.
+fn deny<T>(status: Status, error: ApiKeyError) -> request::Outcome<T, ApiKeyError> {
+    metrics::auth_failure(match error {
+        ApiKeyError::BadCount => "bad_count",
+        ApiKeyError::Missing => "missing",
+        ApiKeyError::Invalid => "invalid",
+        ApiKeyError::Broken => "broken",
+        ApiKeyError::Scope => "scope",
+        ApiKeyError::Disabled => "disabled",
+        ApiKeyError::Role => "role"
+    });
+    Outcome::Failure((status, error))
+}
+
 fn is_token_valid(key: &ApiKey) -> bool {
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
     key.exp > now
 }
 
+///
+/// A token issued before the last invalidation of the sessions (e.g. password reset) is revoked,
+/// the personal access tokens too: their iat is the creation date.
+fn is_session_valid(key: &ApiKey, conn: &MoneyManagerDB) -> bool {
+    match Auth::read_by_id(key.sub, conn) {
+        Ok(auth) => auth.tokens_valid_after
+            .map(|t| key.iat > t.timestamp_millis())
+            .unwrap_or(true),
+        // a user without auth can not have a token
+        Err(_) => false
+    }
+}
+
+fn read_key(key: &str, request: &Request) -> Result<ApiKey, String> {
+    if key.starts_with(ACCESS_TOKEN_PREFIX) {
+        let conn = request.guard::<MoneyManagerDB>().unwrap();
+        // the prefix is not part of the secret
+        read_access_token(&key[ACCESS_TOKEN_PREFIX.len()..], &conn)
+    } else {
+        let keys = request.guard::<State<KeyStore>>().unwrap();
+        read_token(key, &keys)
+    }
+}
+
+///
+/// The standard header is "Authorization: Bearer <token>",
+/// the old "Authentication: <token>" is still accepted.
+fn read_header_keys<'a>(request: &'a Request) -> Vec<&'a str> {
+    let bearer: Vec<&str> = request.headers().get("Authorization")
+        .filter_map(|h| {
+            let mut parts = h.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("Bearer") => Some(token.trim()),
+                _ => None
+            }
+        })
+        .collect();
+    if bearer.is_empty() {
+        request.headers().get("Authentication").collect()
+    } else {
+        bearer
+    }
+}
+
 // #################################################################################################
 
 impl<'a, 'r> FromRequest<'a, 'r> for ApiKey {
     type Error = ApiKeyError;
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<ApiKey, Self::Error> {
-        let keys: Vec<_> = request.headers().get("Authentication").collect();
-        let extra = request.guard::<State<Extras>>().unwrap();
+        let keys = read_header_keys(request);
         match keys.len() {
             0 => {
                 warn!("Access denied! Missing API KEY.");
-                Outcome::Failure((Status::Unauthorized, ApiKeyError::Missing))
+                deny(Status::Unauthorized, ApiKeyError::Missing)
             },
-            1 => match read_token(keys[0], &extra.jwt_key) {
+            1 => match read_key(keys[0], request) {
+                Ok(api_key) if is_token_valid(&api_key) && !is_scope_allowed(&api_key, request) => {
+                    warn!("Access denied! The API KEY has not the scope for {}", request.uri());
+                    deny(Status::Forbidden, ApiKeyError::Scope)
+                },
                 Ok(api_key) if is_token_valid(&api_key) => {
                     debug!("ApiKey is valid!");
                     Outcome::Success(api_key)
                 },
                 Ok(_) => {
                     warn!("Access denied! Expired API KEY.");
-                    Outcome::Failure((Status::Unauthorized, ApiKeyError::Invalid))
+                    deny(Status::Unauthorized, ApiKeyError::Invalid)
                 }
                 Err(_) => {
                     warn!("Access denied! Invalid API KEY.");
-                    Outcome::Failure((Status::Unauthorized, ApiKeyError::Invalid))
+                    deny(Status::Unauthorized, ApiKeyError::Invalid)
                 }
             },
             _ => {
                 warn!("Access denied! Too much API KEY.");
-                Outcome::Failure((Status::Unauthorized, ApiKeyError::BadCount))
+                deny(Status::Unauthorized, ApiKeyError::BadCount)
             }
         }
     }
@@ -183,23 +313,47 @@ impl<'a, 'r> FromRequest<'a, 'r> for User {
     type Error = ApiKeyError;
 
     fn from_request(request: &'a Request<'r>) -> request::Outcome<User, Self::Error> {
-        let conn = request.guard::<MoneyManagerDB>().unwrap();
         let key_outcome = request.guard::<ApiKey>();
         if key_outcome.is_failure() {
             // forward failure from ApiKey handler
             return Outcome::Failure(key_outcome.failed().unwrap());
         }
         let key = key_outcome.unwrap();
+        // only now, an access token holds a connection while it is read
+        let conn = request.guard::<MoneyManagerDB>().unwrap();
+        if !is_session_valid(&key, &conn) {
+            warn!("Access denied to user {} caused by revoked session", key.sub);
+            return deny(Status::Unauthorized, ApiKeyError::Invalid);
+        }
         let user = User::read_by_id(key.sub, &conn);
         match user {
+            Ok(ref user) if user.disabled => {
+                warn!("Access denied to user {} caused by disabled account", user.id);
+                deny(Status::Forbidden, ApiKeyError::Disabled)
+            },
             Ok(user) => {
                 debug!("Access granted to user {}", user.id);
                 Outcome::Success(user)
             },
             Err(e) => {
                 warn!("Access denied to user {} caused by {}", key.sub, e);
-                Outcome::Failure((Status::Unauthorized, ApiKeyError::Broken))
+                deny(Status::Unauthorized, ApiKeyError::Broken)
             }
         }
     }
 }
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ApiKeyError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Admin, Self::Error> {
+        let user = request.guard::<User>()?;
+        if user.is_admin() {
+            debug!("Admin access granted to user {}", user.id);
+            Outcome::Success(Admin(user))
+        } else {
+            warn!("Access denied! The user {} is not an admin.", user.id);
+            deny(Status::Forbidden, ApiKeyError::Role)
+        }
+    }
+}
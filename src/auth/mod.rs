@@ -16,30 +16,41 @@
 */
 
 use serde::Deserialize;
-use rocket_contrib::json::Json;
+use schemars::JsonSchema;
+use rocket_contrib::json::{Json, JsonValue};
 use rocket::http::Status;
 use rocket::State;
-use rocket::response::status::Custom;
 use diesel::result::Error;
+use diesel::Connection;
+use chrono::{Duration, Utc};
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validator;
 use crate::base_model::BaseModel;
-use crate::auth::model::Auth;
+use crate::auth::model::{Auth, PasswordReset, PasswordResetForm};
 use crate::user::model::User;
-use crate::controller::Extras;
+use crate::settings::Settings;
+use crate::mailer::Mailer;
+use crate::auth::token::KeyStore;
+use crate::metrics;
 
 pub mod model;
 pub mod auth;
+pub mod token;
 
-#[derive(Debug,Deserialize)]
-struct AuthJSON<'a> {
+pub(crate) mod access_token;
+
+#[derive(Debug,Deserialize,JsonSchema)]
+pub(crate) struct AuthJSON<'a> {
     email: &'a str,
     password: &'a str
 }
 
 #[post("/<id>", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, id: i64, json: Json<AuthJSON>) -> Result<Status, Status> {
+fn create(conn: MoneyManagerDB, id: i64, json: Json<AuthJSON>) -> Result<Status, ApiError> {
     debug!("CREATE_AUTH_REQUEST");
+    check_password(json.password)?;
     let auth = auth::create_auth(json.email, json.password, None, id).unwrap();
     match Auth::create(&auth, &conn) {
         Ok(_) => {
@@ -48,45 +59,124 @@ fn create(conn: MoneyManagerDB, id: i64, json: Json<AuthJSON>) -> Result<Status,
         },
         Err(e) => {
             error!("Can not create auth for user {} caused by {}", id, e);
-            Err(Status::InternalServerError)
+            Err(ApiError::internal())
         }
     }
 }
 
 #[post("/login", data = "<json>", format = "application/json")]
-fn login(conn: MoneyManagerDB, json: Json<AuthJSON>, extra: State<Extras>) -> Result<String, Status> {
+fn login(conn: MoneyManagerDB, json: Json<AuthJSON>, settings: State<Settings>,
+         keys: State<KeyStore>) -> Result<String, ApiError> {
     debug!("LOGIN_REQUEST");
     let auth = Auth::read_by_email(json.email, &conn);
     match auth {
         Ok(auth) if auth::login(&auth, json.password, &conn) => {
-            finalize_login(&auth, &json, &conn, &extra)
+            finalize_login(&auth, &json, &conn, &settings, &keys)
         },
         Ok(_) => {
             warn!("Wrong credential! Can not login the user: {}", json.email);
-            Err(Status::Unauthorized)
+            metrics::auth_failure("login");
+            Err(ApiError::unauthorized("Wrong email or password"))
+        },
+        Err(e) => Err(ApiError::from_db("auth", e))
+    }
+}
+
+#[derive(Debug,Deserialize,JsonSchema)]
+pub(crate) struct ForgotPasswordJSON<'a> {
+    email: &'a str
+}
+
+#[derive(Debug,Deserialize,JsonSchema)]
+pub(crate) struct ResetPasswordJSON<'a> {
+    token: &'a str,
+    password: &'a str
+}
+
+#[post("/password/forgot", data = "<json>", format = "application/json")]
+fn forgot_password(conn: MoneyManagerDB, json: Json<ForgotPasswordJSON>, settings: State<Settings>,
+                   mailer: State<Box<dyn Mailer>>) -> Result<Status, ApiError> {
+    debug!("FORGOT_PASSWORD_REQUEST");
+    match Auth::read_by_email(json.email, &conn) {
+        Ok(auth) => {
+            if let Err(e) = send_reset_token(&auth, &conn, &settings, &**mailer) {
+                error!("Can not send the reset token to user {}: {}", auth.id, e);
+                return Err(ApiError::internal());
+            }
+            info!("Password reset requested for user {}", auth.id);
         },
         Err(e) if e.eq(&Error::NotFound) => {
-            error!("Can not login the user {}: {}", json.email, e);
-            Err(Status::NotFound)
+            warn!("Password reset requested for unknown email {}", json.email);
         },
         Err(e) => {
-            error!("Can not login the user {}: {}", json.email, e);
-            Err(Status::InternalServerError)
+            error!("Can not read auth for {}: {}", json.email, e);
+            return Err(ApiError::internal());
         }
     }
+    // the answer is always the same, so the registered emails can not be discovered
+    Ok(Status::NoContent)
+}
+
+#[post("/password/reset", data = "<json>", format = "application/json")]
+fn reset_password(conn: MoneyManagerDB, json: Json<ResetPasswordJSON>) -> Result<Status, ApiError> {
+    debug!("RESET_PASSWORD_REQUEST");
+    check_password(json.password)?;
+    let reset = match PasswordReset::read_valid_by_hash(&auth::hash_token(json.token), &conn) {
+        Ok(reset) => reset,
+        Err(e) if e.eq(&Error::NotFound) => {
+            warn!("Password reset attempted with an invalid or expired token!");
+            return Err(ApiError::bad_request("The token is not valid or expired"));
+        },
+        Err(e) => {
+            error!("Can not read password reset: {}", e);
+            return Err(ApiError::internal());
+        }
+    };
+    let result = conn.transaction::<(), Error, _>(|| {
+        // the token can be consumed only once, also with concurrent requests
+        if PasswordReset::mark_used(&reset, &conn)? == 0 {
+            return Err(Error::NotFound);
+        }
+        let old = Auth::read_by_id(reset.id_user, &conn)?;
+        let update = auth::create_auth(&old.email, json.password, None, old.id)
+            .map_err(|()| Error::RollbackTransaction)?;
+        Auth::update(old.id, &update, &conn)?;
+        Auth::invalidate_sessions(old.id, &conn)?;
+        Ok(())
+    });
+    match result {
+        Ok(_) => {
+            info!("The user {} has reset his password!", reset.id_user);
+            Ok(Status::NoContent)
+        },
+        Err(e) if e.eq(&Error::NotFound) => {
+            warn!("Password reset token {} already used!", reset.id);
+            Err(ApiError::bad_request("The token is not valid or expired"))
+        },
+        Err(e) => {
+            error!("Can not reset password for user {}: {}", reset.id_user, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+#[get("/jwks.json")]
+fn jwks(keys: State<KeyStore>) -> JsonValue {
+    debug!("JWKS_REQUEST");
+    keys.jwks()
 }
 
 /* DISABLED FOR SECURITY REASON */
 #[allow(dead_code)]
 #[get("/")]
-fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<Auth>>, Custom<String>> {
+fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<Auth>>, ApiError> {
     warn!("READ_AUTH - DISABLED FOR SECURITY REASON");
     let result = Auth::read(&conn);
     Auth::unpack(result)
 }
 
 #[get("/")]
-fn read_one(conn: MoneyManagerDB, user: User) -> Result<Json<Auth>, Status> {
+fn read_one(conn: MoneyManagerDB, user: User) -> Result<Json<Auth>, ApiError> {
     debug!("READ_AUTH_BY_USER_REQUEST");
     let result = Auth::read_by_user(&user, &conn);
     match result {
@@ -96,55 +186,49 @@ fn read_one(conn: MoneyManagerDB, user: User) -> Result<Json<Auth>, Status> {
             Auth::mask(&mut result);
             Ok(Json(result))
         },
-        Err(e) if e.eq(&Error::NotFound) => {
-            warn!("auth not found for user {}: {}", user.id, e);
-            Err(Status::NotFound)
-        },
-        Err(e) => {
-            error!("Can not get auth for user {}: {}", user.id, e);
-            Err(Status::InternalServerError)
-        }
+        Err(e) => Err(ApiError::from_db("auth", e))
     }
 }
 
 #[put("/", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, user: User, json: Json<AuthJSON>) -> Status {
+fn update(conn: MoneyManagerDB, user: User, json: Json<AuthJSON>) -> Result<Status, ApiError> {
     debug!("UPDATE_AUTH_REQUEST");
+    check_password(json.password)?;
     let update = auth::create_auth(json.email, json.password, None, user.id)
         .map_err(|()| error!("Can not create auth!") ).unwrap();
     let result = Auth::update(user.id, &update, &conn);
     match result {
         Ok(n) if n > 0 => {
             info!("The user {} has updated his authentication data!", user.id);
-            Status::NoContent
+            Ok(Status::NoContent)
         },
         Ok(_) => {
             warn!("Auth data not found!");
-            Status::NotFound
+            Err(ApiError::not_found("auth"))
         },
         Err(e) => {
             error!("Can not update auth for user {}: {}", user.id, e);
-            Status::InternalServerError
+            Err(ApiError::internal())
         }
     }
 }
 
 #[delete("/")]
-fn delete(conn: MoneyManagerDB, user: User) -> Status {
+fn delete(conn: MoneyManagerDB, user: User) -> Result<Status, ApiError> {
     debug!("DELETE_AUTH_REQUEST");
     let result = Auth::delete(user.id, &conn);
     match result {
         Ok(n) if n > 0 => {
             info!("The user {} has deleted his authentication data!", user.id);
-            Status::NoContent
+            Ok(Status::NoContent)
         },
         Ok(_) => {
             warn!("Auth data not found!");
-            Status::NotFound
+            Err(ApiError::not_found("auth"))
         },
         Err(e) => {
             error!("Can not delete auth for user {}: {}", user.id, e);
-            Status::InternalServerError
+            Err(ApiError::internal())
         }
     }
 }
@@ -152,17 +236,26 @@ fn delete(conn: MoneyManagerDB, user: User) -> Status {
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/auth", routes![create, login, read_one, update, delete])
+    rocket.mount("/auth", routes![create, login, read_one, update, delete, forgot_password, reset_password])
+        .mount("/auth/tokens", access_token::get_mount())
+        .mount("/.well-known", routes![jwks])
 }
 
 // #################################################################################################
 
-fn finalize_login(auth: &Auth, json: &Json<AuthJSON>, conn: &MoneyManagerDB, extra: &State<Extras>) -> Result<String, Status> {
+fn check_password(password: &str) -> Result<(), ApiError> {
+    let mut v = Validator::new();
+    v.password("password", password);
+    v.finish()
+}
+
+fn finalize_login(auth: &Auth, json: &Json<AuthJSON>, conn: &MoneyManagerDB, settings: &State<Settings>,
+                  keys: &State<KeyStore>) -> Result<String, ApiError> {
     let user = User::read_by_id(auth.id, &conn).map_err(|e| {
         error!("Can not find the user {} caused by {}", json.email, e.to_string());
-        Status::NotFound
+        ApiError::not_found("user")
     })?;
-    let token = auth::create_token(&user, &extra);
+    let token = auth::create_token(&user, &settings, &keys);
     match token {
         Ok(t) => {
             info!("The user {} has just logged in!", user.id);
@@ -170,8 +263,27 @@ fn finalize_login(auth: &Auth, json: &Json<AuthJSON>, conn: &MoneyManagerDB, ext
         },
         Err(s) => {
             error!("Can not login the user: {}", json.email);
-            Err(s)
+            Err(ApiError::from(s))
         }
     }
 }
 
+fn send_reset_token(auth: &Auth, conn: &MoneyManagerDB, settings: &State<Settings>, mailer: &dyn Mailer) -> Result<(), String> {
+    let (token, token_hash) = auth::create_random_token();
+    let form = PasswordResetForm {
+        id_user: auth.id,
+        token_hash: &token_hash,
+        expiration_date: Utc::now() + Duration::seconds(settings.reset_exp as i64)
+    };
+    conn.transaction::<PasswordReset, Error, _>(|| {
+        PasswordReset::delete_pending_by_user(auth.id, conn)?;
+        PasswordReset::create(&form, conn)
+    }).map_err(|e| e.to_string())?;
+    let body = match settings.reset_url {
+        Some(ref url) => format!("Open the following link to choose a new password:\n{}\n\n\
+            The link expires in {} minutes.", url.replace("{token}", &token), settings.reset_exp / 60),
+        None => format!("Use the following code to choose a new password:\n{}\n\n\
+            The code expires in {} minutes.", token, settings.reset_exp / 60)
+    };
+    mailer.send(&auth.email, "Money Manager - password reset", &body)
+}
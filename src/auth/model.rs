@@ -17,65 +17,122 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
-use crate::schema::auth;
+use crate::schema::{auth, password_reset, access_token};
 use crate::user::model::User;
-use crate::database::MoneyManagerDB;
 
 #[table_name = "auth"]
 #[belongs_to(User, foreign_key = "id")]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,Insertable,AsChangeset)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,Insertable,AsChangeset,JsonSchema)]
 pub struct Auth {
     pub id: i64,
     pub email: String,
     pub iteration: i16,
     pub salt: String,
     pub stored_key: String,
-    pub last_login: Option<DateTime<Utc>>
+    pub last_login: Option<DateTime<Utc>>,
+    pub tokens_valid_after: Option<DateTime<Utc>>
+}
+
+#[table_name = "password_reset"]
+#[belongs_to(User, foreign_key = "id_user")]
+#[derive(Debug,Queryable,Identifiable,Associations)]
+pub struct PasswordReset {
+    pub id: i64,
+    pub id_user: i64,
+    pub token_hash: String,
+    pub expiration_date: DateTime<Utc>,
+    pub used_date: Option<DateTime<Utc>>,
+    pub creation_date: DateTime<Utc>
+}
+
+// only for insert
+#[table_name = "password_reset"]
+#[derive(Debug,Insertable)]
+pub struct PasswordResetForm<'a> {
+    pub id_user: i64,
+    pub token_hash: &'a str,
+    pub expiration_date: DateTime<Utc>
+}
+
+#[table_name = "access_token"]
+#[belongs_to(User, foreign_key = "id_user")]
+#[derive(Debug,Serialize,Queryable,Identifiable,Associations,JsonSchema)]
+pub struct AccessToken {
+    pub id: i64,
+    pub id_user: i64,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub scopes: String,
+    pub creation_date: DateTime<Utc>,
+    pub expiration_date: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>
+}
+
+// only for insert
+#[table_name = "access_token"]
+#[derive(Debug,Insertable)]
+pub struct AccessTokenForm<'a> {
+    pub id_user: i64,
+    pub name: &'a str,
+    pub token_hash: &'a str,
+    pub scopes: &'a str,
+    pub expiration_date: Option<DateTime<Utc>>
 }
 
 impl Auth {
-    pub fn create(form: &Auth, conn: &MoneyManagerDB) -> QueryResult<Auth> {
+    pub fn create(form: &Auth, conn: &PgConnection) -> QueryResult<Auth> {
         diesel::insert_into(auth::table)
             .values(form)
-            .get_result::<Auth>(&*(*conn))
+            .get_result::<Auth>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Auth>> {
-        auth::table.load::<Auth>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Auth>> {
+        auth::table.load::<Auth>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Auth> {
-        auth::table.find(id).first::<Auth>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Auth> {
+        auth::table.find(id).first::<Auth>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_user(user: &User, conn: &MoneyManagerDB) -> QueryResult<Auth> {
-        Auth::belonging_to(user).first::<Auth>(&*(*conn))
+    pub fn read_by_user(user: &User, conn: &PgConnection) -> QueryResult<Auth> {
+        Auth::belonging_to(user).first::<Auth>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_email(email: &str, conn: &MoneyManagerDB) -> QueryResult<Auth> {
+    pub fn read_by_email(email: &str, conn: &PgConnection) -> QueryResult<Auth> {
         auth::table
             .filter(auth::email.eq(email))
-            .first(&*(*conn))
+            .first(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(id: i64, form: &Auth, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn update(id: i64, form: &Auth, conn: &PgConnection) -> QueryResult<usize> {
         diesel::update(auth::table.find(id))
             .set(form)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update_last_login(id: i64, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn update_last_login(id: i64, conn: &PgConnection) -> QueryResult<usize> {
         diesel::update(auth::table.find(id))
             .set(auth::last_login.eq(Utc::now()))
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(id: i64, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    ///
+    /// All the tokens issued before now are no longer accepted.
+    pub fn invalidate_sessions(id: i64, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(auth::table.find(id))
+            .set(auth::tokens_valid_after.eq(Utc::now()))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete(id: i64, conn: &PgConnection) -> QueryResult<usize> {
         diesel::delete(auth::table.find(id))
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
     ///
@@ -86,3 +143,94 @@ impl Auth {
         auth.iteration = 0;
     }
 }
+
+impl PasswordReset {
+    pub fn create(form: &PasswordResetForm, conn: &PgConnection) -> QueryResult<PasswordReset> {
+        diesel::insert_into(password_reset::table)
+            .values(form)
+            .get_result::<PasswordReset>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Only a token not used and not expired can be read.
+    pub fn read_valid_by_hash(token_hash: &str, conn: &PgConnection) -> QueryResult<PasswordReset> {
+        password_reset::table
+            .filter(password_reset::token_hash.eq(token_hash))
+            .filter(password_reset::used_date.is_null())
+            .filter(password_reset::expiration_date.gt(Utc::now()))
+            .first::<PasswordReset>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Return 0 if the token has already been used.
+    pub fn mark_used(reset: &PasswordReset, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(password_reset::table
+            .filter(password_reset::id.eq(reset.id))
+            .filter(password_reset::used_date.is_null()))
+            .set(password_reset::used_date.eq(Utc::now()))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Remove the tokens not yet used, only the last one requested must be valid.
+    pub fn delete_pending_by_user(id_user: i64, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(password_reset::table
+            .filter(password_reset::id_user.eq(id_user))
+            .filter(password_reset::used_date.is_null()))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Remove the tokens that can not be used anymore.
+    pub fn delete_expired(conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(password_reset::table
+            .filter(password_reset::expiration_date.le(Utc::now()))
+            .or_filter(password_reset::used_date.is_not_null()))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+impl AccessToken {
+    pub fn create(form: &AccessTokenForm, conn: &PgConnection) -> QueryResult<AccessToken> {
+        diesel::insert_into(access_token::table)
+            .values(form)
+            .get_result::<AccessToken>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<AccessToken> {
+        access_token::table.find(id).first::<AccessToken>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<AccessToken>> {
+        AccessToken::belonging_to(user)
+            .order(access_token::creation_date.desc())
+            .load::<AccessToken>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_hash(token_hash: &str, conn: &PgConnection) -> QueryResult<AccessToken> {
+        access_token::table
+            .filter(access_token::token_hash.eq(token_hash))
+            .first::<AccessToken>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn update_last_used(token: &AccessToken, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(token)
+            .set(access_token::last_used.eq(Utc::now()))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete(token: &AccessToken, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(token)
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete_expired(conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(access_token::table.filter(access_token::expiration_date.le(Utc::now())))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn scope_list(&self) -> Vec<String> {
+        self.scopes.split(',').map(|s| s.to_string()).collect()
+    }
+}
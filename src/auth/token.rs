@@ -0,0 +1,174 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fs;
+use ring::{digest, hmac, signature};
+use data_encoding::{BASE64, BASE64URL_NOPAD, HEXLOWER};
+use serde::{Serialize, Deserialize};
+use rocket_contrib::json::JsonValue;
+
+const ALG_EDDSA: &str = "EdDSA";
+const ALG_HS256: &str = "HS256";
+
+#[derive(Debug,Serialize,Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: u64,
+    /// seconds with the milliseconds, a reset in the same second must revoke the tokens issued before it
+    #[serde(default)]
+    pub iat: f64
+}
+
+#[derive(Debug,Serialize,Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>
+}
+
+struct SigningKey {
+    kid: String,
+    pair: signature::Ed25519KeyPair
+}
+
+///
+/// Keys used to sign and verify the JWT.
+/// The first Ed25519 key signs the new tokens, all of them verify (key rotation).
+/// Without Ed25519 keys the tokens are signed and verified with the shared secret (HS256).
+pub struct KeyStore {
+    secret: Option<hmac::SigningKey>,
+    signing: Option<SigningKey>,
+    verifying: Vec<(String, Vec<u8>)>
+}
+
+impl KeyStore {
+    pub fn new(secret: &str, key_files: &[String]) -> Result<KeyStore, String> {
+        let mut signing = None;
+        let mut verifying = Vec::new();
+        for path in key_files {
+            let pair = read_key_pair(path)?;
+            let public = pair.public_key_bytes().to_vec();
+            let kid = HEXLOWER.encode(&digest::digest(&digest::SHA256, &public).as_ref()[..8]);
+            info!("JWT key {} loaded from {}", kid, path);
+            verifying.push((kid.clone(), public));
+            if signing.is_none() {
+                signing = Some(SigningKey { kid, pair });
+            }
+        }
+        // once the Ed25519 keys are there a leaked shared secret must not mint tokens anymore
+        let secret = if secret.is_empty() {
+            None
+        } else if signing.is_some() {
+            warn!("jwt_key is ignored, the HS256 tokens are refused when jwt_private_keys are configured");
+            None
+        } else {
+            Some(hmac::SigningKey::new(&digest::SHA256, secret.as_bytes()))
+        };
+        if secret.is_none() && signing.is_none() {
+            return Err("neither jwt_key nor jwt_private_keys are configured".to_string());
+        }
+        Ok(KeyStore { secret, signing, verifying })
+    }
+
+    pub fn sign(&self, claims: &Claims) -> Result<String, String> {
+        let header = match self.signing {
+            Some(ref key) => Header { alg: ALG_EDDSA.to_string(), typ: "JWT".to_string(), kid: Some(key.kid.clone()) },
+            None => Header { alg: ALG_HS256.to_string(), typ: "JWT".to_string(), kid: None }
+        };
+        let header = serde_json::to_vec(&header).map_err(|e| e.to_string())?;
+        let claims = serde_json::to_vec(claims).map_err(|e| e.to_string())?;
+        let message = format!("{}.{}", BASE64URL_NOPAD.encode(&header), BASE64URL_NOPAD.encode(&claims));
+        let sign = match (&self.signing, &self.secret) {
+            (Some(key), _) => BASE64URL_NOPAD.encode(key.pair.sign(message.as_bytes()).as_ref()),
+            (None, Some(secret)) => BASE64URL_NOPAD.encode(hmac::sign(secret, message.as_bytes()).as_ref()),
+            (None, None) => return Err("no key available".to_string())
+        };
+        Ok(format!("{}.{}", message, sign))
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Claims, String> {
+        let parts: Vec<&str> = token.split('.').collect();
+        if parts.len() != 3 {
+            return Err("malformed token".to_string());
+        }
+        let header: Header = decode_json(parts[0])?;
+        let message = &token[..parts[0].len() + 1 + parts[1].len()];
+        let sign = BASE64URL_NOPAD.decode(parts[2].as_bytes()).map_err(|e| e.to_string())?;
+        let valid = match header.alg.as_str() {
+            ALG_EDDSA => {
+                let kid = header.kid.ok_or("kid missing".to_string())?;
+                let public = self.verifying.iter()
+                    .find(|(k, _)| *k == kid)
+                    .map(|(_, p)| p)
+                    .ok_or(format!("unknown kid {}", kid))?;
+                signature::verify(&signature::ED25519,
+                                  untrusted::Input::from(public),
+                                  untrusted::Input::from(message.as_bytes()),
+                                  untrusted::Input::from(&sign)).is_ok()
+            },
+            ALG_HS256 => match self.secret {
+                Some(ref secret) => hmac::verify_with_own_key(secret, message.as_bytes(), &sign).is_ok(),
+                None => return Err("HS256 tokens are not accepted".to_string())
+            },
+            alg => return Err(format!("algorithm {} not supported", alg))
+        };
+        if valid {
+            decode_json(parts[1])
+        } else {
+            Err("invalid signature".to_string())
+        }
+    }
+
+    ///
+    /// The public keys in JWKS format, other services can verify our tokens.
+    pub fn jwks(&self) -> JsonValue {
+        let keys: Vec<JsonValue> = self.verifying.iter()
+            .map(|(kid, public)| json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": ALG_EDDSA,
+                "kid": kid,
+                "x": BASE64URL_NOPAD.encode(public)
+            }))
+            .collect();
+        json!({ "keys": keys })
+    }
+}
+
+// #################################################################################################
+
+fn decode_json<T: serde::de::DeserializeOwned>(part: &str) -> Result<T, String> {
+    let bytes = BASE64URL_NOPAD.decode(part.as_bytes()).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+///
+/// Read a PKCS#8 Ed25519 key, DER or PEM.
+fn read_key_pair(path: &str) -> Result<signature::Ed25519KeyPair, String> {
+    let content = fs::read(path).map_err(|e| format!("can not read {}: {}", path, e))?;
+    let der = if content.starts_with(b"-----BEGIN") {
+        let pem = String::from_utf8(content).map_err(|e| format!("invalid PEM {}: {}", path, e))?;
+        let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        BASE64.decode(body.trim().as_bytes()).map_err(|e| format!("invalid PEM {}: {}", path, e))?
+    } else {
+        content
+    };
+    signature::Ed25519KeyPair::from_pkcs8_maybe_unchecked(untrusted::Input::from(&der))
+        .map_err(|_| format!("{} is not a PKCS#8 Ed25519 key", path))
+}
@@ -15,44 +15,113 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use rocket::Request;
 use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder, Response};
 use diesel::QueryResult;
 
-use crate::auth::model::Auth;
+use crate::error::ApiError;
+use crate::auth::model::{Auth, AccessToken};
 use crate::user::model::User;
 use crate::causal::model::Causal;
 use crate::account::model::{Account, AccountType};
-use crate::currency::model::Currency;
+use crate::currency::model::{Currency, ExchangeRate};
 use crate::transaction::model::{Transaction, TransactionType, TransactionDetail};
 use crate::place::model::Place;
 use crate::detail::model::Detail;
 use crate::giro::model::Giro;
+use crate::rule::model::Rule;
 
 pub trait BaseController<T> {
-    fn finalize_update_delete(result: QueryResult<usize>) -> Result<Status, Status> {
+    fn finalize_update_delete(result: QueryResult<usize>) -> Result<Status, ApiError> {
         match result {
             Ok(n) if n > 0 => Ok(Status::NoContent),
             Ok(_) => {
                 warn!("object not found!");
-                Err(Status::NotFound)
+                Err(ApiError::not_found("object"))
             },
             Err(e) => {
                 error!("error on update/delete object: {}", e);
-                Err(Status::InternalServerError)
+                Err(ApiError::internal())
+            }
+        }
+    }
+    ///
+    /// For the updates filtered by version: no row means that someone else changed it in the meantime.
+    fn finalize_versioned(result: QueryResult<usize>) -> Result<Status, ApiError> {
+        match result {
+            Ok(n) if n > 0 => Ok(Status::NoContent),
+            Ok(_) => {
+                warn!("object changed by another request!");
+                Err(ApiError::precondition_failed())
+            },
+            Err(e) => {
+                error!("error on update/delete object: {}", e);
+                Err(ApiError::internal())
             }
         }
     }
 }
 
 impl BaseController<Auth> for Auth { }
+impl BaseController<AccessToken> for AccessToken { }
 impl BaseController<User> for User { }
 impl BaseController<Causal> for Causal { }
 impl BaseController<Account> for Account { }
 impl BaseController<AccountType> for AccountType { }
 impl BaseController<Currency> for Currency { }
+impl BaseController<ExchangeRate> for ExchangeRate { }
 impl BaseController<Transaction> for Transaction { }
 impl BaseController<TransactionType> for TransactionType { }
 impl BaseController<TransactionDetail> for TransactionDetail { }
 impl BaseController<Place> for Place { }
 impl BaseController<Detail> for Detail { }
 impl BaseController<Giro> for Giro { }
+impl BaseController<Rule> for Rule { }
+
+///
+/// The If-Match header of PUT and DELETE. It is optional: without it the version
+/// read at the beginning of the request is still checked when the row is written.
+pub struct IfMatch(Option<String>);
+
+impl IfMatch {
+    pub fn check(&self, version: i32) -> Result<(), ApiError> {
+        let tags = match self.0 {
+            Some(ref tags) => tags,
+            None => return Ok(())
+        };
+        let current = etag(version);
+        // weak tags compare as strong ones, the version is all there is
+        if tags.trim() == "*" || tags.split(',').any(|t| t.trim().trim_start_matches("W/") == current) {
+            Ok(())
+        } else {
+            warn!("If-Match {} does not match {}", tags, current);
+            Err(ApiError::precondition_failed())
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfMatch {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> Outcome<IfMatch, ()> {
+        Outcome::Success(IfMatch(request.headers().get_one("If-Match").map(|s| s.to_string())))
+    }
+}
+
+///
+/// A response with the ETag of the version of the entity.
+pub struct Tagged<R>(pub R, pub i32);
+
+impl<'r, R: Responder<'r>> Responder<'r> for Tagged<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        Response::build_from(self.0.respond_to(request)?)
+            .raw_header("ETag", etag(self.1))
+            .ok()
+    }
+}
+
+fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
@@ -15,72 +15,81 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use serde::Deserialize;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
 use crate::base_model::BaseModel;
-use crate::base_controller::BaseController;
+use crate::base_controller::{BaseController, IfMatch, Tagged};
 use crate::causal::model::{Causal, CausalForm};
+use crate::idempotency::{IdempotencyKey, Replayable};
 use crate::user::model::User;
 
 pub mod model;
 
-#[derive(Debug,Deserialize)]
-struct CausalJSON<'a> {
+#[derive(Debug,Serialize,Deserialize,JsonSchema)]
+pub(crate) struct CausalJSON<'a> {
     pub description: &'a str
 }
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<CausalJSON>, user: User) -> Result<Json<Causal>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<CausalJSON>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_CAUSAL_REQUEST");
-    let insert = CausalForm {
-        description: json.description,
-        id_user: Some(user.id),
-    };
-    Causal::create(&insert, &conn)
-        .map(|result| {
-            info!("causal create successfully: {}", result.id);
-            Json(result)
-        })
-        .map_err(|e| {
-            error!("Can not create causal: {}", e);
-            Status::InternalServerError
-        })
+    key.once(&user, "causal", &*json, &conn, || {
+        let insert = CausalForm {
+            description: json.description,
+            id_user: Some(user.id),
+        };
+        insert.validate(&user, &conn)?;
+        let causal = Causal::create(&insert, &conn)
+            .map(|result| {
+                info!("causal create successfully: {}", result.id);
+                result
+            })
+            .map_err(|e| {
+                error!("Can not create causal: {}", e);
+                ApiError::internal()
+            })?;
+        Replayable::json(Status::Ok, &causal)
+    })
 }
 
 /* DISABLED FOR SECURITY REASON */
 #[allow(dead_code)]
 #[get("/")]
-fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<Causal>>, Custom<String>> {
+fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<Causal>>, ApiError> {
     debug!("READ_CAUSAL_REQUEST");
     let result = Causal::read(&conn);
     Causal::unpack(result)
 }
 
 #[get("/user")]
-fn read_for_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Causal>>, Custom<String>> {
+fn read_for_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Causal>>, ApiError> {
     debug!("READ_FOR_USER_CAUSAL_REQUEST");
     let result = Causal::read_for_user(&user, &conn);
     Causal::unpack(result)
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Causal>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Tagged<Json<Causal>>, ApiError> {
     debug!("READ_ONE_CAUSAL_REQUEST");
     let causal = get_by_id(id, &conn)?;
     // a user can access his own causals or the default ones
     if causal.id_user.is_some() {
         check_property(&causal, &user)?;
     }
-    Ok(Json(causal))
+    let version = causal.version;
+    Ok(Tagged(Json(causal), version))
 }
 
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i64, json: Json<CausalJSON>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i64, json: Json<CausalJSON>, user: User,
+          if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("UPDATE_CAUSAL_REQUEST");
     let form = CausalForm {
         description: json.description,
@@ -89,44 +98,50 @@ fn update(conn: MoneyManagerDB, id: i64, json: Json<CausalJSON>, user: User) ->
     let causal = get_by_id(id, &conn)?;
     // check if causal can be updated
     check_property(&causal, &user)?;
+    if_match.check(causal.version)?;
+    form.validate(&user, &conn)?;
     let update = Causal::update(&causal, &form, &conn);
-    Causal::finalize_update_delete(update)
+    Causal::finalize_versioned(update)
 }
 
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i64, user: User, if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("DELETE_CAUSAL_REQUEST");
     let causal = get_by_id(id, &conn)?;
     // check if causal can be deleted
     check_property(&causal, &user)?;
+    if_match.check(causal.version)?;
     let delete = Causal::delete(&causal, &conn);
-    Causal::finalize_update_delete(delete)
+    Causal::finalize_versioned(delete)
+}
+
+#[post("/<id>/restore")]
+fn restore(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("RESTORE_CAUSAL_REQUEST");
+    let causal = Causal::read_deleted_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("causal", e))?;
+    check_property(&causal, &user)?;
+    let restore = Causal::restore(&causal, &conn);
+    Causal::finalize_update_delete(restore)
 }
 
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/causal", routes![read_one, read_for_user, create, update, delete])
+    rocket.mount("/causal", routes![read_one, read_for_user, create, update, delete, restore])
 }
 
 // #################################################################################################
 
-fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Causal, Status> {
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Causal, ApiError> {
     Causal::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read causal: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("causal", e))
 }
 
-fn check_property(causal: &Causal, user: &User) -> Result<(), Status> {
+fn check_property(causal: &Causal, user: &User) -> Result<(), ApiError> {
     if causal.id_user.is_none() || causal.id_user.unwrap() != user.id {
         warn!("The user attempts to access causal that does not belong to it!");
-        Err(Status::Forbidden)
+        Err(ApiError::forbidden("causal"))
     } else {
         Ok(())
     }
@@ -17,18 +17,24 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 use crate::schema::causal;
 use crate::user::model::User;
-use crate::database::MoneyManagerDB;
+use crate::validation::{Validate, Validator};
+use crate::error::ApiError;
 
 #[table_name = "causal"]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
 pub struct Causal {
     pub id: i64,
     pub description: String,
-    pub id_user: Option<i64>
+    pub id_user: Option<i64>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32
 }
 
 // only for insert and update
@@ -40,35 +46,69 @@ pub struct CausalForm<'a> {
 }
 
 impl Causal {
-    pub fn create(form: &CausalForm, conn: &MoneyManagerDB) -> QueryResult<Causal> {
+    pub fn create(form: &CausalForm, conn: &PgConnection) -> QueryResult<Causal> {
         diesel::insert_into(causal::table)
             .values(form)
-            .get_result::<Causal>(&*(*conn))
+            .get_result::<Causal>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Causal>> {
-        causal::table.load::<Causal>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Causal>> {
+        causal::table.filter(causal::deleted_at.is_null())
+            .load::<Causal>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_for_user(user: &User, conn: &MoneyManagerDB) -> QueryResult<Vec<Causal>> {
-        causal::table.filter(causal::id_user.eq(user.id))
-            .or_filter(causal::id_user.is_null())
-            .load::<Causal>(&*(*conn))
+    pub fn read_for_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<Causal>> {
+        causal::table.filter(causal::id_user.eq(user.id).or(causal::id_user.is_null()))
+            .filter(causal::deleted_at.is_null())
+            .load::<Causal>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Causal> {
-        causal::table.find(id).first::<Causal>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Causal> {
+        causal::table.find(id)
+            .filter(causal::deleted_at.is_null())
+            .first::<Causal>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(causal: &Causal, form: &CausalForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::update(causal)
-            .set(form)
-            .execute(&*(*conn))
+    pub fn read_deleted_by_id(id: i64, conn: &PgConnection) -> QueryResult<Causal> {
+        causal::table.find(id)
+            .filter(causal::deleted_at.is_not_null())
+            .first::<Causal>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Also in the trash: the transactions keep referring to it.
+    pub fn read_any_by_id(id: i64, conn: &PgConnection) -> QueryResult<Causal> {
+        causal::table.find(id).first::<Causal>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(causal: &Causal, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::delete(causal)
-            .execute(&*(*conn))
+    ///
+    /// Only the version that was read is updated, no row means that it has changed in the meantime.
+    pub fn update(causal: &Causal, form: &CausalForm, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(causal::table.find(causal.id).filter(causal::version.eq(causal.version)))
+            .set((form, causal::version.eq(causal.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-}
\ No newline at end of file
+    ///
+    /// Move the causal to the trash.
+    pub fn delete(causal: &Causal, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(causal::table.find(causal.id).filter(causal::version.eq(causal.version)))
+            .set((causal::deleted_at.eq(Utc::now()), causal::version.eq(causal.version + 1)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn restore(causal: &Causal, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(causal)
+            .set((causal::deleted_at.eq(None::<DateTime<Utc>>), causal::version.eq(causal.version + 1)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+impl<'a> Validate for CausalForm<'a> {
+    fn validate(&self, _user: &User, _conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("description", self.description, 255);
+        v.finish()
+    }
+}
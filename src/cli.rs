@@ -0,0 +1,188 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use rocket_contrib::databases::database_config;
+
+use crate::migration;
+use crate::settings;
+use crate::auth::auth;
+use crate::auth::model::{Auth, AccessToken, PasswordReset};
+use crate::user::model::{User, UserForm, ROLE_ADMIN};
+use crate::user::archive::{self, Archive};
+use crate::account::balance;
+use crate::account::model::Account;
+
+const PASSWORD_ENV: &str = "MM_PASSWORD";
+
+const COMMANDS: [&str; 7] = ["migrate", "create-admin", "reset-password", "recompute-balances",
+                             "export-user", "import-user", "purge-sessions"];
+
+pub const USAGE: &str = "usage: money-manager-server [COMMAND]
+
+Without a command the server is started. Commands:
+    migrate [--check]                   apply the pending migrations, or only check them
+    create-admin EMAIL NAME SURNAME     create an administrator
+    reset-password EMAIL                set a new password and revoke the sessions of the user
+    recompute-balances [ACCOUNT_ID]     recompute the current balance of the accounts
+    export-user EMAIL [FILE]            write the archive of the user (JSON) in FILE or on stdout
+    import-user FILE                    create a new user from an archive
+    purge-sessions                      remove the expired access tokens and password resets
+
+The passwords are read from the MM_PASSWORD variable or from the standard input.
+The database is the one configured in Rocket.toml for the current ROCKET_ENV, or MM_DATABASE_URL.";
+
+///
+/// Run an operational command against the database, without starting the server.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let command = args.first().map(|a| a.as_str()).unwrap_or("");
+    let params: Vec<&str> = args.iter().skip(1).map(|a| a.as_str()).collect();
+    if !COMMANDS.contains(&command) {
+        return Err(USAGE.to_string());
+    }
+    let conn = connect()?;
+    match (command, params.as_slice()) {
+        ("migrate", []) => migration::run(&conn),
+        ("migrate", ["--check"]) => migration::verify(&conn),
+        ("create-admin", [email, name, surname]) => create_admin(email, name, surname, &conn),
+        ("reset-password", [email]) => reset_password(email, &conn),
+        ("recompute-balances", []) => {
+            let n = balance::recompute_all(&conn).map_err(|e| e.to_string())?;
+            println!("{} accounts recomputed", n);
+            Ok(())
+        },
+        ("recompute-balances", [id]) => {
+            let id = id.parse::<i64>().map_err(|e| format!("invalid account id {}: {}", id, e))?;
+            let account = Account::read_by_id(id, &conn).map_err(|e| e.to_string())?;
+            let balance = balance::recompute(&account, &conn).map_err(|e| e.to_string())?;
+            println!("account {}: {}", id, balance);
+            Ok(())
+        },
+        ("export-user", [email]) => export_user(email, None, &conn),
+        ("export-user", [email, file]) => export_user(email, Some(*file), &conn),
+        ("import-user", [file]) => import_user(file, &conn),
+        ("purge-sessions", []) => purge_sessions(&conn),
+        _ => Err(USAGE.to_string())
+    }
+}
+
+// #################################################################################################
+
+fn connect() -> Result<PgConnection, String> {
+    let config = settings::rocket_config()?;
+    let config = database_config("db", &config).map_err(|e| format!("{:?}", e))?;
+    PgConnection::establish(config.url).map_err(|e| e.to_string())
+}
+
+fn read_password() -> Result<String, String> {
+    if let Ok(password) = env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    eprintln!("Password:");
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())?;
+    let password = line.trim_end_matches(|c| c == '\n' || c == '\r').to_string();
+    if password.is_empty() {
+        Err("the password can not be empty".to_string())
+    } else {
+        Ok(password)
+    }
+}
+
+fn read_user_by_email(email: &str, conn: &PgConnection) -> Result<User, String> {
+    let auth = Auth::read_by_email(email, conn).map_err(|e| match e {
+        Error::NotFound => format!("no user with email {}", email),
+        e => e.to_string()
+    })?;
+    User::read_by_id(auth.id, conn).map_err(|e| e.to_string())
+}
+
+fn create_admin(email: &str, name: &str, surname: &str, conn: &PgConnection) -> Result<(), String> {
+    if Auth::read_by_email(email, conn).is_ok() {
+        return Err(format!("the email {} is already registered", email));
+    }
+    let form = UserForm {
+        name: name.to_string(),
+        surname: surname.to_string(),
+        phone: None,
+        country: None,
+        address: None,
+        birthdate: None,
+        note: None
+    };
+    form.validate().map_err(|e| format!("{}: {:?}", e.message, e.fields))?;
+    let password = read_password()?;
+    let user = conn.transaction::<User, Error, _>(|| {
+        let user = User::create(&form, conn)?;
+        User::update_role(user.id, ROLE_ADMIN, conn)?;
+        let auth = auth::create_auth(email, &password, None, user.id)
+            .map_err(|()| Error::RollbackTransaction)?;
+        Auth::create(&auth, conn)?;
+        Ok(user)
+    }).map_err(|e| e.to_string())?;
+    info!("The administrator {} has been created from the command line", user.id);
+    println!("administrator {} created", user.id);
+    Ok(())
+}
+
+fn reset_password(email: &str, conn: &PgConnection) -> Result<(), String> {
+    let old = Auth::read_by_email(email, conn).map_err(|e| e.to_string())?;
+    let password = read_password()?;
+    let update = auth::create_auth(&old.email, &password, None, old.id)
+        .map_err(|()| "can not create auth".to_string())?;
+    conn.transaction::<(), Error, _>(|| {
+        Auth::update(old.id, &update, conn)?;
+        Auth::invalidate_sessions(old.id, conn)?;
+        Ok(())
+    }).map_err(|e| e.to_string())?;
+    info!("The password of user {} has been reset from the command line", old.id);
+    println!("password of user {} reset", old.id);
+    Ok(())
+}
+
+fn export_user(email: &str, file: Option<&str>, conn: &PgConnection) -> Result<(), String> {
+    let user = read_user_by_email(email, conn)?;
+    let archive = archive::export(&user, conn).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&archive).map_err(|e| e.to_string())?;
+    match file {
+        Some(file) => fs::write(file, json).map_err(|e| format!("can not write {}: {}", file, e))?,
+        None => println!("{}", json)
+    }
+    info!("The data of user {} has been exported from the command line", user.id);
+    Ok(())
+}
+
+fn import_user(file: &str, conn: &PgConnection) -> Result<(), String> {
+    let json = fs::read_to_string(file).map_err(|e| format!("can not read {}: {}", file, e))?;
+    let archive: Archive = serde_json::from_str(&json).map_err(|e| format!("invalid archive: {}", e))?;
+    let user = archive::import(&archive, conn)?;
+    println!("user {} imported as {}, the password must be reset", archive.email, user.id);
+    Ok(())
+}
+
+fn purge_sessions(conn: &PgConnection) -> Result<(), String> {
+    let tokens = AccessToken::delete_expired(conn).map_err(|e| e.to_string())?;
+    let resets = PasswordReset::delete_expired(conn).map_err(|e| e.to_string())?;
+    info!("Purged {} access tokens and {} password resets", tokens, resets);
+    println!("{} access tokens and {} password resets removed", tokens, resets);
+    Ok(())
+}
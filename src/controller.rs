@@ -16,10 +16,14 @@
 */
 
 use rocket::fairing::AdHoc;
+use rocket::Config;
 use rocket::error::LaunchError;
 use rocket_cors::{AllowedOrigins, CorsOptions, Cors};
 
 use crate::database::MoneyManagerDB;
+use crate::error;
+use crate::settings::Settings;
+use crate::migration;
 use crate::causal;
 use crate::user;
 use crate::auth;
@@ -29,12 +33,18 @@ use crate::transaction;
 use crate::place;
 use crate::detail;
 use crate::giro;
-
-#[derive(Debug)]
-pub struct Extras {
-    pub jwt_key: String,
-    pub jwt_exp: u64
-}
+use crate::admin;
+use crate::audit;
+use crate::trash;
+use crate::rule;
+use crate::reconciliation;
+use crate::report;
+use crate::openapi;
+use crate::health;
+use crate::metrics::{self, Metrics};
+use crate::housekeeping::Housekeeping;
+use crate::mailer;
+use crate::auth::token::KeyStore;
 
 /*
 #[get("/")]
@@ -43,14 +53,32 @@ fn index() -> &'static str {
 }
 */
 
-pub fn init() -> LaunchError {
+pub fn init(config: Config, settings: Settings) -> LaunchError {
+    rocket(config, settings).launch()
+}
 
-    let mut rocket = rocket::ignite()
+///
+/// The server ready to be launched, the tests use it with a local client.
+pub fn rocket(config: Config, settings: Settings) -> rocket::Rocket {
+    let rocket = rocket::custom(config)
+        .attach(Metrics::new())
         .attach(MoneyManagerDB::fairing())
-        .attach(enable_cors())
-        .attach(fairing_extra());
+        .attach(fairing_migrations(settings.migrations.clone()))
+        .attach(enable_cors(&settings.cors_origins))
+        .attach(Housekeeping::new(settings.housekeeping_interval, settings.trash_retention_days,
+                                  settings.idempotency_window_hours))
+        .attach(fairing_settings(settings))
+        .attach(error::RequestIdFairing)
+        .register(error::get_catchers());
+
+    mount(rocket)
+}
 
+///
+/// All the routes, without the fairings: the OpenAPI document is built from them.
+pub fn mount(mut rocket: rocket::Rocket) -> rocket::Rocket {
     // rocket = rocket.mount("/", routes![index]);
+    rocket = rocket.mount("/", health::get_mount()).mount("/", metrics::get_mount());
     rocket = causal::mount(rocket);
     rocket = user::mount(rocket);
     rocket = auth::mount(rocket);
@@ -60,29 +88,73 @@ pub fn init() -> LaunchError {
     rocket = transaction::mount(rocket);
     rocket = transaction::mount_transaction_type(rocket);
     rocket = transaction::mount_transaction_detail(rocket);
+    rocket = transaction::mount_transaction_bulk(rocket);
+    rocket = transaction::mount_transaction_suggestion(rocket);
     rocket = place::mount(rocket);
     rocket = detail::mount(rocket);
     rocket = giro::mount(rocket);
+    rocket = admin::mount(rocket);
+    rocket = audit::mount(rocket);
+    rocket = trash::mount(rocket);
+    rocket = rule::mount(rocket);
+    rocket = reconciliation::mount(rocket);
+    rocket = report::mount(rocket);
+    // the last one, it describes the routes mounted so far
+    rocket = openapi::mount(rocket);
 
-    rocket.launch()
+    rocket
 }
 
-fn enable_cors() -> Cors{
+fn enable_cors(origins: &[String]) -> Cors {
+    let allowed_origins = if origins.iter().any(|o| o == "*") {
+        AllowedOrigins::all()
+    } else {
+        if origins.is_empty() {
+            warn!("No CORS origin configured, the browsers of other origins are refused");
+        }
+        AllowedOrigins::some_exact(origins)
+    };
     let options = CorsOptions {
-        allowed_origins: AllowedOrigins::some_exact(&["http://rippetanks.ddns.net", "http://localhost"]),
+        allowed_origins,
+        // the clients send it back in If-Match
+        expose_headers: ["ETag"].iter().map(|h| h.to_string()).collect(),
         ..Default::default()
     };
+    // the origins are checked by the settings, the options are always valid
     CorsOptions::to_cors(&options).unwrap()
 }
 
-fn fairing_extra() -> rocket::fairing::AdHoc {
-    AdHoc::on_attach("Extras Fairing", |rocket| {
-        let config = rocket.config();
-        let jwt_key = config.get_str("jwt_key").unwrap().to_string();
-        let jwt_exp = config.get_int("jwt_exp").unwrap() as u64;
-        Ok(rocket.manage(Extras {
-            jwt_key,
-            jwt_exp
-        }))
+fn fairing_settings(settings: Settings) -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("Settings Fairing", move |rocket| {
+        let keys = match KeyStore::new(&settings.jwt_key, &settings.jwt_private_keys) {
+            Ok(keys) => keys,
+            Err(e) => {
+                error!("Can not load the JWT keys: {}", e);
+                return Err(rocket);
+            }
+        };
+        let mailer = mailer::from_settings(&settings);
+        Ok(rocket.manage(mailer).manage(keys).manage(settings))
+    })
+}
+
+fn fairing_migrations(mode: String) -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("Migrations Fairing", move |rocket| {
+        // run: apply the pending migrations; verify: refuse to start if there are any; off: nothing
+        let result = match MoneyManagerDB::get_one(&rocket) {
+            Some(conn) => match mode.as_str() {
+                "run" => migration::run(&conn),
+                "verify" => migration::verify(&conn),
+                _ => Ok(())
+            },
+            None => Err("no database connection available".to_string())
+        };
+        match result {
+            Ok(_) => Ok(rocket),
+            Err(e) => {
+                error!("Database migrations failed: {}", e);
+                Err(rocket)
+            }
+        }
     })
 }
@@ -17,83 +17,119 @@
 
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
+use chrono::NaiveDate;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
 use crate::base_model::BaseModel;
 use crate::base_controller::BaseController;
 use crate::user::model::User;
-use crate::currency::model::{Currency, CurrencyForm};
+use crate::auth::auth::Admin;
+use crate::currency::model::{Currency, CurrencyForm, ExchangeRate};
 
 pub mod model;
 
-/* DISABLED FOR SECURITY REASON */
-#[allow(dead_code)]
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<CurrencyForm>, _user: User) -> Result<Json<Currency>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<CurrencyForm>, admin: Admin) -> Result<Json<Currency>, ApiError> {
     debug!("CREATE_CURRENCY_REQUEST");
-    Currency::create(&json.into_inner(), &conn)
+    let form = json.into_inner();
+    form.validate(&admin.0, &conn)?;
+    Currency::create(&form, &conn)
         .map(|result| {
             info!("currency create successfully: {}", result.id);
             Json(result)
         })
         .map_err(|e| {
             error!("Can not create currency: {}", e);
-            Status::InternalServerError
+            ApiError::internal()
         })
 }
 
 #[get("/")]
-fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<Currency>>, Custom<String>> {
+fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<Currency>>, ApiError> {
     debug!("READ_CURRENCY_REQUEST");
     let result = Currency::read(&conn);
     Currency::unpack(result)
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i16, _user: User) -> Result<Json<Currency>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i16, _user: User) -> Result<Json<Currency>, ApiError> {
     debug!("READ_ONE_CURRENCY_REQUEST");
     let result = get_by_id(id, &conn)?;
     Ok(Json(result))
 }
 
-/* DISABLED FOR SECURITY REASON */
-#[allow(dead_code)]
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i16, json: Json<CurrencyForm>, _user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i16, json: Json<CurrencyForm>, admin: Admin) -> Result<Status, ApiError> {
     debug!("UPDATE_CURRENCY_REQUEST");
     let currency = get_by_id(id, &conn)?;
-    let update = Currency::update(&currency, &json.into_inner(), &conn);
+    let form = json.into_inner();
+    form.validate(&admin.0, &conn)?;
+    let update = Currency::update(&currency, &form, &conn);
     Currency::finalize_update_delete(update)
 }
 
-/* DISABLED FOR SECURITY REASON */
-#[allow(dead_code)]
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i16, _user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i16, _admin: Admin) -> Result<Status, ApiError> {
     debug!("DELETE_CURRENCY_REQUEST");
     let currency = get_by_id(id, &conn)?;
     let delete = Currency::delete(&currency, &conn);
     Currency::finalize_update_delete(delete)
 }
 
+///
+/// Create the rate of the day or replace it.
+#[post("/rate", data = "<json>", format = "application/json")]
+fn save_rate(conn: MoneyManagerDB, json: Json<ExchangeRate>, admin: Admin) -> Result<Json<ExchangeRate>, ApiError> {
+    debug!("SAVE_EXCHANGE_RATE_REQUEST");
+    let rate = json.into_inner();
+    rate.validate(&admin.0, &conn)?;
+    ExchangeRate::save(&rate, &conn)
+        .map(|result| {
+            info!("exchange rate {} -> {} saved for {}", result.id_currency, result.id_base_currency, result.date);
+            Json(result)
+        })
+        .map_err(|e| {
+            error!("Can not save exchange rate: {}", e);
+            ApiError::internal()
+        })
+}
+
+#[get("/rate/<id_currency>/<id_base_currency>")]
+fn read_rates(conn: MoneyManagerDB, id_currency: i16, id_base_currency: i16,
+              _user: User) -> Result<Json<Vec<ExchangeRate>>, ApiError> {
+    debug!("READ_EXCHANGE_RATE_REQUEST");
+    let result = ExchangeRate::read_pair(id_currency, id_base_currency, &conn);
+    ExchangeRate::unpack(result)
+}
+
+#[delete("/rate/<id_currency>/<id_base_currency>/<date>")]
+fn delete_rate(conn: MoneyManagerDB, id_currency: i16, id_base_currency: i16, date: String,
+               _admin: Admin) -> Result<Status, ApiError> {
+    debug!("DELETE_EXCHANGE_RATE_REQUEST");
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|_| ApiError::bad_request("The date must be YYYY-MM-DD"))?;
+    let rate = ExchangeRate { id_currency, id_base_currency, date, rate: 0.0 };
+    let delete = ExchangeRate::delete(&rate, &conn);
+    ExchangeRate::finalize_update_delete(delete)
+}
+
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/currency", routes![read, read_one])
+    rocket.mount("/currency", routes![read, read_one, read_rates])
+}
+
+///
+/// Reference data can be changed only by an admin.
+pub fn mount_admin(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/admin/currency", routes![create, update, delete, save_rate, delete_rate])
 }
 
 // #################################################################################################
 
-fn get_by_id(id: i16, conn: &MoneyManagerDB) -> Result<Currency, Status> {
+fn get_by_id(id: i16, conn: &MoneyManagerDB) -> Result<Currency, ApiError> {
     Currency::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read currency: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("currency", e))
 }
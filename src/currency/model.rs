@@ -17,13 +17,19 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use chrono::NaiveDate;
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
-use crate::schema::currency;
-use crate::database::MoneyManagerDB;
+use crate::schema::{currency, exchange_rate};
+use crate::user::model::User;
+use crate::validation::{Validate, Validator};
+use crate::error::ApiError;
 
 #[table_name = "currency"]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
 pub struct Currency {
     pub id: i16,
     pub name: String,
@@ -33,37 +39,108 @@ pub struct Currency {
 
 // only for insert and update
 #[table_name = "currency"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[derive(Debug,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct CurrencyForm<'a> {
     pub name: &'a str,
     pub code: &'a str,
     pub number: i16
 }
 
+///
+/// One unit of the currency is worth rate units of the base currency, from the date on.
+#[table_name = "exchange_rate"]
+#[primary_key(id_currency, id_base_currency, date)]
+#[derive(Debug,Clone,Serialize,Deserialize,Queryable,Identifiable,Insertable,AsChangeset,JsonSchema)]
+pub struct ExchangeRate {
+    pub id_currency: i16,
+    pub id_base_currency: i16,
+    pub date: NaiveDate,
+    pub rate: f64
+}
+
 impl Currency {
-    pub fn create(form: &CurrencyForm, conn: &MoneyManagerDB) -> QueryResult<Currency> {
+    pub fn create(form: &CurrencyForm, conn: &PgConnection) -> QueryResult<Currency> {
         diesel::insert_into(currency::table)
             .values(form)
-            .get_result::<Currency>(&*(*conn))
+            .get_result::<Currency>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Currency>> {
-        currency::table.load::<Currency>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Currency>> {
+        currency::table.load::<Currency>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i16, conn: &MoneyManagerDB) -> QueryResult<Currency> {
-        currency::table.find(id).first::<Currency>(&*(*conn))
+    pub fn read_by_id(id: i16, conn: &PgConnection) -> QueryResult<Currency> {
+        currency::table.find(id).first::<Currency>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(currency: &Currency, form: &CurrencyForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn update(currency: &Currency, form: &CurrencyForm, conn: &PgConnection) -> QueryResult<usize> {
         diesel::update(currency)
             .set(form)
-            .execute(&*(*conn))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Its exchange rates are useless without it.
+    pub fn delete(currency: &Currency, conn: &PgConnection) -> QueryResult<usize> {
+        conn.transaction::<usize, Error, _>(|| {
+            diesel::delete(exchange_rate::table
+                .filter(exchange_rate::id_currency.eq(currency.id)
+                    .or(exchange_rate::id_base_currency.eq(currency.id))))
+                .execute(conn)?;
+            diesel::delete(currency)
+                .execute(conn)
+        }).map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+impl ExchangeRate {
+    ///
+    /// A rate for the same day replaces the old one.
+    pub fn save(rate: &ExchangeRate, conn: &PgConnection) -> QueryResult<ExchangeRate> {
+        diesel::insert_into(exchange_rate::table)
+            .values(rate)
+            .on_conflict((exchange_rate::id_currency, exchange_rate::id_base_currency, exchange_rate::date))
+            .do_update()
+            .set(exchange_rate::rate.eq(rate.rate))
+            .get_result::<ExchangeRate>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(currency: &Currency, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::delete(currency)
-            .execute(&*(*conn))
+    ///
+    /// The rates from the currency to the base one, the oldest first.
+    pub fn read_pair(id_currency: i16, id_base_currency: i16, conn: &PgConnection) -> QueryResult<Vec<ExchangeRate>> {
+        exchange_rate::table
+            .filter(exchange_rate::id_currency.eq(id_currency))
+            .filter(exchange_rate::id_base_currency.eq(id_base_currency))
+            .order(exchange_rate::date)
+            .load::<ExchangeRate>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
+    pub fn delete(rate: &ExchangeRate, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(rate)
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+impl<'a> Validate for CurrencyForm<'a> {
+    fn validate(&self, _user: &User, _conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("name", self.name, 64);
+        // ISO 4217
+        v.check(self.code.len() == 3 && self.code.chars().all(|c| c.is_ascii_uppercase()), "code",
+                "must be an ISO 4217 code");
+        v.check(self.number > 0 && self.number < 1000, "number", "must be an ISO 4217 number");
+        v.finish()
+    }
+}
+
+impl Validate for ExchangeRate {
+    fn validate(&self, _user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.positive("rate", self.rate);
+        v.check(self.id_currency != self.id_base_currency, "id_base_currency", "must be different from the currency");
+        v.reference("id_currency", Currency::read_by_id(self.id_currency, conn))?;
+        v.reference("id_base_currency", Currency::read_by_id(self.id_base_currency, conn))?;
+        v.finish()
+    }
 }
@@ -19,3 +19,11 @@ use rocket_contrib::databases::diesel;
 
 #[database("db")]
 pub struct MoneyManagerDB(diesel::PgConnection);
+
+///
+/// Connections of the pool: open, idle and maximum.
+/// The pool type is generated by the database attribute above.
+pub fn pool_usage(pool: &MoneyManagerDBPool) -> (u32, u32, u32) {
+    let state = pool.0.state();
+    (state.connections, state.idle_connections, pool.0.max_size())
+}
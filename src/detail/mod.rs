@@ -15,60 +15,69 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use serde::Deserialize;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
 use crate::base_model::BaseModel;
-use crate::base_controller::BaseController;
+use crate::base_controller::{BaseController, IfMatch, Tagged};
 use crate::detail::model::{Detail, DetailForm};
+use crate::idempotency::{IdempotencyKey, Replayable};
 use crate::user::model::User;
 
 pub mod model;
 
-#[derive(Debug,Deserialize)]
-struct DetailJSON<'a> {
+#[derive(Debug,Serialize,Deserialize,JsonSchema)]
+pub(crate) struct DetailJSON<'a> {
     pub description: &'a str
 }
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<DetailJSON>, user: User) -> Result<Json<Detail>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<DetailJSON>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_DETAIL_REQUEST");
-    let detail = DetailForm {
-        description: json.description,
-        id_user: Some(user.id)
-    };
-    Detail::create(&detail, &conn)
-        .map(|result| {
-            info!("result create successfully {}", result.id);
-            Json(result)
-        })
-        .map_err(|e| {
-            error!("Can not create detail: {}", e);
-            Status::InternalServerError
-        })
+    key.once(&user, "detail", &*json, &conn, || {
+        let detail = DetailForm {
+            description: json.description,
+            id_user: Some(user.id)
+        };
+        detail.validate(&user, &conn)?;
+        let detail = Detail::create(&detail, &conn)
+            .map(|result| {
+                info!("result create successfully {}", result.id);
+                result
+            })
+            .map_err(|e| {
+                error!("Can not create detail: {}", e);
+                ApiError::internal()
+            })?;
+        Replayable::json(Status::Ok, &detail)
+    })
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Detail>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Tagged<Json<Detail>>, ApiError> {
     debug!("READ_ONE_DETAIL_REQUEST");
     let detail = get_by_id(id, &conn)?;
     check_property(&detail, &user)?;
-    Ok(Json(detail))
+    let version = detail.version;
+    Ok(Tagged(Json(detail), version))
 }
 
 #[get("/user")]
-pub fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Detail>>, Custom<String>> {
+pub fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Detail>>, ApiError> {
     debug!("READ_BY_USER_DETAIL_REQUEST");
     let result = Detail::read_by_user(&user, &conn);
     Detail::unpack(result)
 }
 
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i64, json: Json<DetailJSON>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i64, json: Json<DetailJSON>, user: User,
+          if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("UPDATE_DETAIL_REQUEST");
     let form = DetailForm {
         description: json.description,
@@ -76,17 +85,20 @@ fn update(conn: MoneyManagerDB, id: i64, json: Json<DetailJSON>, user: User) ->
     };
     let detail = get_by_id(id, &conn)?;
     check_property(&detail, &user)?;
+    if_match.check(detail.version)?;
+    form.validate(&user, &conn)?;
     let update = Detail::update(&detail, &form, &conn);
-    Detail::finalize_update_delete(update)
+    Detail::finalize_versioned(update)
 }
 
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i64, user: User, if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("DELETE_DETAIL_REQUEST");
     let detail = get_by_id(id, &conn)?;
     check_property(&detail, &user)?;
+    if_match.check(detail.version)?;
     let delete = Detail::delete(&detail, &conn);
-    Detail::finalize_update_delete(delete)
+    Detail::finalize_versioned(delete)
 }
 
 ///
@@ -97,7 +109,7 @@ pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
 
 ///
 ///
-pub fn get_and_check(id_detail: i64, user: &User, conn: &MoneyManagerDB) -> Result<Detail, Status> {
+pub fn get_and_check(id_detail: i64, user: &User, conn: &MoneyManagerDB) -> Result<Detail, ApiError> {
     let detail = get_by_id(id_detail, conn)?;
     check_property(&detail, user)?;
     Ok(detail)
@@ -105,22 +117,15 @@ pub fn get_and_check(id_detail: i64, user: &User, conn: &MoneyManagerDB) -> Resu
 
 // #################################################################################################
 
-fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Detail, Status> {
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Detail, ApiError> {
     Detail::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read detail: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("detail", e))
 }
 
-fn check_property(detail: &Detail, user: &User) -> Result<(), Status> {
+fn check_property(detail: &Detail, user: &User) -> Result<(), ApiError> {
     if detail.id_user.is_some() && detail.id_user.unwrap() != user.id {
         warn!("The user attempts to access detail that does not belong to it!");
-        Err(Status::Forbidden)
+        Err(ApiError::forbidden("detail"))
     } else {
         Ok(())
     }
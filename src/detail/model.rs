@@ -17,18 +17,22 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 use crate::schema::detail;
 use crate::user::model::User;
-use crate::database::MoneyManagerDB;
+use crate::validation::{Validate, Validator};
+use crate::error::ApiError;
 
 #[table_name = "detail"]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
 pub struct Detail {
     pub id: i64,
     pub description: String,
-    pub id_user: Option<i64>
+    pub id_user: Option<i64>,
+    pub version: i32
 }
 
 // only for insert and update
@@ -40,34 +44,44 @@ pub struct DetailForm<'a> {
 }
 
 impl Detail {
-    pub fn create(form: &DetailForm, conn: &MoneyManagerDB) -> QueryResult<Detail> {
+    pub fn create(form: &DetailForm, conn: &PgConnection) -> QueryResult<Detail> {
         diesel::insert_into(detail::table)
             .values(form)
-            .get_result::<Detail>(&*(*conn))
+            .get_result::<Detail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Detail>> {
-        detail::table.load::<Detail>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Detail>> {
+        detail::table.load::<Detail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Detail> {
-        detail::table.find(id).first::<Detail>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Detail> {
+        detail::table.find(id).first::<Detail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_user(user: &User, conn: &MoneyManagerDB) -> QueryResult<Vec<Detail>> {
+    pub fn read_by_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<Detail>> {
         detail::table.filter(detail::id_user.eq(user.id))
-            .load::<Detail>(&*(*conn))
+            .load::<Detail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(detail: &Detail, form: &DetailForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::update(detail)
-            .set(form)
-            .execute(&*(*conn))
+    ///
+    /// Only the version that was read is updated, no row means that it has changed in the meantime.
+    pub fn update(detail: &Detail, form: &DetailForm, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(detail::table.find(detail.id).filter(detail::version.eq(detail.version)))
+            .set((form, detail::version.eq(detail.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(detail: &Detail, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::delete(detail)
-            .execute(&*(*conn))
+    pub fn delete(detail: &Detail, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(detail::table.find(detail.id).filter(detail::version.eq(detail.version)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
+
+impl<'a> Validate for DetailForm<'a> {
+    fn validate(&self, _user: &User, _conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("description", self.description, 32);
+        v.finish()
+    }
+}
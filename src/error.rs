@@ -0,0 +1,198 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde::Serialize;
+use rocket::{Request, Response};
+use rocket::http::{Header, Status};
+use rocket::response::{self, Responder};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket_contrib::json::Json;
+use ring::rand::{SecureRandom, SystemRandom};
+use data_encoding::HEXLOWER;
+use diesel::result::Error;
+
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+#[derive(Debug,Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String
+}
+
+///
+/// The error returned by every route, serialized as JSON:
+/// a stable code the clients can branch on, a message for humans and the errors of the fields.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub code: &'static str,
+    pub message: String,
+    pub fields: Vec<FieldError>
+}
+
+struct RequestId(String);
+
+impl ApiError {
+    pub fn new(status: Status, code: &'static str, message: &str) -> ApiError {
+        ApiError {
+            status,
+            code,
+            message: message.to_string(),
+            fields: Vec::new()
+        }
+    }
+    pub fn bad_request(message: &str) -> ApiError {
+        ApiError::new(Status::BadRequest, "bad_request", message)
+    }
+    pub fn unauthorized(message: &str) -> ApiError {
+        ApiError::new(Status::Unauthorized, "unauthorized", message)
+    }
+    pub fn forbidden(entity: &str) -> ApiError {
+        ApiError::new(Status::Forbidden, "forbidden", &format!("The {} does not belong to the user", entity))
+    }
+    pub fn not_found(entity: &str) -> ApiError {
+        ApiError::new(Status::NotFound, "not_found", &format!("The {} does not exist", entity))
+    }
+    pub fn conflict(message: &str) -> ApiError {
+        ApiError::new(Status::Conflict, "conflict", message)
+    }
+    pub fn precondition_failed() -> ApiError {
+        ApiError::new(Status::PreconditionFailed, "precondition_failed",
+                      "The resource has been changed in the meantime, read it again")
+    }
+    pub fn unprocessable(fields: Vec<FieldError>) -> ApiError {
+        ApiError {
+            fields,
+            ..ApiError::new(Status::UnprocessableEntity, "validation_failed", "The request is not valid")
+        }
+    }
+    pub fn internal() -> ApiError {
+        ApiError::new(Status::InternalServerError, "internal_error", "An internal error occurred")
+    }
+    ///
+    /// The details of a database error are only logged, they must not reach the client.
+    pub fn from_db(entity: &str, e: Error) -> ApiError {
+        if e.eq(&Error::NotFound) {
+            warn!("{} not found", entity);
+            ApiError::not_found(entity)
+        } else {
+            error!("An error occurred on {}: {}", entity, e);
+            ApiError::internal()
+        }
+    }
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> ApiError {
+        let code = match status.code {
+            400 => "bad_request",
+            401 => "unauthorized",
+            403 => "forbidden",
+            404 => "not_found",
+            409 => "conflict",
+            412 => "precondition_failed",
+            422 => "validation_failed",
+            _ if status.code >= 500 => "internal_error",
+            _ => "error"
+        };
+        ApiError::new(status, code, status.reason)
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let body = json!({
+            "code": self.code,
+            "message": self.message,
+            "fields": self.fields,
+            "request_id": request_id(request)
+        });
+        Response::build_from(Json(body).respond_to(request)?)
+            .status(self.status)
+            .ok()
+    }
+}
+
+///
+/// The id of the request: the one sent by the client (or a proxy) or a new one.
+pub fn request_id(request: &Request) -> String {
+    request.local_cache(|| {
+        let id = request.headers().get_one(REQUEST_ID_HEADER)
+            .map(|id| id.chars().take(64).collect())
+            .unwrap_or_else(|| {
+                let mut bytes = [0u8; 8];
+                SystemRandom::new().fill(&mut bytes).unwrap();
+                HEXLOWER.encode(&bytes)
+            });
+        RequestId(id)
+    }).0.clone()
+}
+
+///
+/// Every response carries the id of the request, so a client error can be found in the log.
+pub struct RequestIdFairing;
+
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Id",
+            kind: Kind::Response
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id(request)));
+    }
+}
+
+// #################################################################################################
+
+#[catch(400)]
+fn bad_request(_: &Request) -> ApiError {
+    ApiError::bad_request("The request can not be understood")
+}
+
+#[catch(401)]
+fn unauthorized(_: &Request) -> ApiError {
+    ApiError::unauthorized("Missing or invalid credentials")
+}
+
+#[catch(403)]
+fn forbidden(_: &Request) -> ApiError {
+    ApiError::new(Status::Forbidden, "forbidden", "Access denied")
+}
+
+#[catch(404)]
+fn not_found(_: &Request) -> ApiError {
+    ApiError::new(Status::NotFound, "not_found", "Resource not found")
+}
+
+#[catch(422)]
+fn unprocessable_entity(_: &Request) -> ApiError {
+    ApiError::new(Status::UnprocessableEntity, "validation_failed", "The request body is not valid")
+}
+
+#[catch(500)]
+fn internal_error(_: &Request) -> ApiError {
+    ApiError::internal()
+}
+
+///
+///
+pub fn get_catchers() -> Vec<rocket::Catcher> {
+    catchers![bad_request, unauthorized, forbidden, not_found, unprocessable_entity, internal_error]
+}
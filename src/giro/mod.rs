@@ -15,124 +15,255 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use diesel::Connection;
+use diesel::result::Error;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
+use serde_json::Value;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
+use crate::patch;
 use crate::base_model::BaseModel;
-use crate::base_controller::BaseController;
+use crate::base_controller::{BaseController, IfMatch, Tagged};
 use crate::giro::model::{Giro, GiroForm};
+use crate::audit::model::AuditLog;
+use crate::idempotency::{IdempotencyKey, Replayable};
+use crate::account::model::Account;
 use crate::account;
+use crate::reconciliation;
 use crate::user::model::User;
 
 pub mod model;
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<GiroForm>, user: User) -> Result<Json<Giro>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<GiroForm>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_GIRO_REQUEST");
     let form = json.into_inner();
-    check_source_id_property(form.id_source_account, &user, &conn)?;
-    Giro::create(&form, &conn)
-        .map(|giro| {
-            info!("giro create successfully {}", giro.id);
-            Json(giro)
-        })
-        .map_err(|e| {
-            error!("Can not create giro caused by {}", e);
-            Status::InternalServerError
-        })
+    key.once(&user, "giro", &form, &conn, || {
+        // the user must own both the accounts
+        form.validate(&user, &conn)?;
+        check_open(&form, &conn)?;
+        let result = conn.transaction::<Giro, Error, _>(|| {
+            let giro = Giro::create(&form, &conn)?;
+            AuditLog::created(&user, &giro, &conn)?;
+            Ok(giro)
+        });
+        let giro = result
+            .map(|giro| {
+                info!("giro create successfully {}", giro.id);
+                giro
+            })
+            .map_err(|e| {
+                error!("Can not create giro caused by {}", e);
+                ApiError::internal()
+            })?;
+        Replayable::json(Status::Ok, &giro)
+    })
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Giro>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Tagged<Json<Giro>>, ApiError> {
     debug!("READ_ONE_GIRO_REQUEST");
     let giro = get_by_id(id, &conn)?;
     // a user can access his own giro
     check_source_property(&giro, &user, &conn)?;
     check_destination_property(&giro, &user, &conn)?;
-    Ok(Json(giro))
+    let version = giro.version;
+    Ok(Tagged(Json(giro), version))
 }
 
 #[get("/account/source/<id>")]
-fn read_by_source(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<Giro>>, Custom<String>> {
+fn read_by_source(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<Giro>>, ApiError> {
     debug!("READ_BY_ACCOUNT_SOURCE_GIRO_REQUEST");
-    let account = account::get_and_check(id, &user, &conn)
-        .map_err(|s| Custom(s, String::new()))?;
+    let account = account::get_and_check(id, &user, &conn)?;
     let result = Giro::read_by_source(&account, &conn);
     Giro::unpack(result)
 }
 
 #[get("/account/destination/<id>")]
-fn read_by_destination(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<Giro>>, Custom<String>> {
+fn read_by_destination(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<Giro>>, ApiError> {
     debug!("READ_BY_ACCOUNT_DESTINATION_GIRO_REQUEST");
-    let account = account::get_and_check(id, &user, &conn)
-        .map_err(|s| Custom(s, String::new()))?;
+    let account = account::get_and_check(id, &user, &conn)?;
     let result = Giro::read_by_destination(&account, &conn);
     Giro::unpack(result)
 }
 
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i64, json: Json<GiroForm>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i64, json: Json<Value>, user: User,
+          if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("UPDATE_GIRO_REQUEST");
     let giro = get_by_id(id, &conn)?;
     // check if account can be updated
     check_source_property(&giro, &user, &conn)?;
-    let result = Giro::update(&giro, &json.into_inner(), &conn);
-    Giro::finalize_update_delete(result)
+    check_complete(&giro)?;
+    if_match.check(giro.version)?;
+    // the body replaces the giro, but a missing cleared flag does not clear it
+    let body = patch::replace(&giro, &json, &["cleared_source", "cleared_destination"])?;
+    let form = patch::form::<GiroForm>(&body)?;
+    form.validate(&user, &conn)?;
+    save(&giro, &form, &user, &conn)
+}
+
+#[patch("/<id>", data = "<json>", format = "application/json")]
+fn patch(conn: MoneyManagerDB, id: i64, json: Json<Value>, user: User,
+         if_match: IfMatch) -> Result<Status, ApiError> {
+    debug!("PATCH_GIRO_REQUEST");
+    let giro = get_by_id(id, &conn)?;
+    check_source_property(&giro, &user, &conn)?;
+    check_complete(&giro)?;
+    if_match.check(giro.version)?;
+    // only the fields of the patch change, the others are the current ones
+    let merged = patch::apply(&giro, &json)?;
+    let form = patch::form::<GiroForm>(&merged)?;
+    form.validate(&user, &conn)?;
+    save(&giro, &form, &user, &conn)
 }
 
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i64, user: User, if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("DELETE_GIRO_REQUEST");
     let giro = get_by_id(id, &conn)?;
     // check if causal can be deleted
     check_source_property(&giro, &user, &conn)?;
-    let result = Giro::delete(&giro, &conn);
+    if_match.check(giro.version)?;
+    reconciliation::check_unlocked("giro", giro.id_reconciliation_source.or(giro.id_reconciliation_destination))?;
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Giro::delete(&giro, &conn)?;
+        if n > 0 {
+            AuditLog::deleted(&user, &giro, &conn)?;
+        }
+        Ok(n)
+    });
+    Giro::finalize_versioned(result)
+}
+
+#[post("/<id>/restore")]
+fn restore(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("RESTORE_GIRO_REQUEST");
+    let giro = Giro::read_deleted_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("giro", e))?;
+    check_source_property(&giro, &user, &conn)?;
+    let mut accounts = giro.id_source_account.iter().chain(giro.id_destination_account.iter());
+    if accounts.any(|id| Account::read_by_id(*id, &conn).is_err()) {
+        return Err(ApiError::conflict("An account of the giro is in the trash, it must be restored first"));
+    }
+    let sides = [(giro.cleared_source, giro.id_source_account), (giro.cleared_destination, giro.id_destination_account)];
+    for &(cleared, id_account) in sides.iter() {
+        if let (true, Some(id_account)) = (cleared, id_account) {
+            if !reconciliation::is_open(id_account, giro.data, &conn)? {
+                return Err(ApiError::conflict("The giro is cleared up to the last reconciled statement, it can not be restored"));
+            }
+        }
+    }
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Giro::restore(&giro, &conn)?;
+        let after = Giro::read_by_id(giro.id, &conn)?;
+        AuditLog::restored(&user, &after, &conn)?;
+        Ok(n)
+    });
     Giro::finalize_update_delete(result)
 }
 
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/giro", routes![read_one, read_by_source, read_by_destination, create, update, delete])
+    rocket.mount("/giro", routes![read_one, read_by_source, read_by_destination, create, update, patch, delete, restore])
 }
 
 // #################################################################################################
 
-fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Giro, Status> {
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Giro, ApiError> {
     Giro::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read giro: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("giro", e))
+}
+
+fn check_source_property(giro: &Giro, user: &User, conn: &MoneyManagerDB) -> Result<(), ApiError> {
+    match giro.id_source_account {
+        Some(id_account) => check_source_id_property(id_account, user, conn),
+        // the source account has been erased, the giro belongs to the destination one
+        None => check_destination_property(giro, user, conn)
+    }
 }
 
-fn check_source_property(giro: &Giro, user: &User, conn: &MoneyManagerDB) -> Result<(), Status> {
-    check_source_id_property(giro.id_source_account, user, conn)
+// the form needs both the accounts, a giro that lost one can only be deleted
+fn check_complete(giro: &Giro) -> Result<(), ApiError> {
+    if giro.id_source_account.is_none() || giro.id_destination_account.is_none() {
+        return Err(ApiError::conflict("An account of the giro has been erased, it can only be deleted"));
+    }
+    Ok(())
 }
 
-fn check_source_id_property(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Result<(), Status> {
+fn check_source_id_property(id_account: i64, user: &User, conn: &MoneyManagerDB) -> Result<(), ApiError> {
     let c = account::check(id_account, user, conn);
     if c.is_err() {
         warn!("The user attempts to access giro (source account) that does not belong to it!");
-        Err(Status::Forbidden)
+        Err(ApiError::forbidden("giro"))
     } else {
         Ok(())
     }
 }
 
-fn check_destination_property(giro: &Giro, user: &User, conn: &MoneyManagerDB) -> Result<(), Status> {
-    let c = account::check(giro.id_destination_account, user, conn);
+fn check_destination_property(giro: &Giro, user: &User, conn: &MoneyManagerDB) -> Result<(), ApiError> {
+    let id_account = match giro.id_destination_account {
+        Some(id_account) => id_account,
+        None => return Ok(())
+    };
+    let c = account::check(id_account, user, conn);
     if c.is_err() {
         warn!("The user attempts to access giro (destination account) that does not belong to it!");
-        Err(Status::Forbidden)
+        Err(ApiError::forbidden("giro"))
     } else {
         Ok(())
     }
 }
+
+// each account reconciles its own side: a locked side keeps the giro as it is,
+// only the cleared flag of the other side can still change
+fn save(giro: &Giro, form: &GiroForm, user: &User, conn: &MoneyManagerDB) -> Result<Status, ApiError> {
+    if !same_movement(giro, form) {
+        reconciliation::check_unlocked("giro", giro.id_reconciliation_source.or(giro.id_reconciliation_destination))?;
+    }
+    if form.cleared_source != giro.cleared_source {
+        reconciliation::check_unlocked("giro", giro.id_reconciliation_source)?;
+    }
+    if form.cleared_destination != giro.cleared_destination {
+        reconciliation::check_unlocked("giro", giro.id_reconciliation_destination)?;
+    }
+    if giro.id_reconciliation_source.is_none() {
+        reconciliation::check_open("cleared_source", form.cleared_source, form.id_source_account, form.data, conn)?;
+    }
+    if giro.id_reconciliation_destination.is_none() {
+        reconciliation::check_open("cleared_destination", form.cleared_destination, form.id_destination_account,
+                                   form.data, conn)?;
+    }
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Giro::update(giro, form, conn)?;
+        if n == 0 {
+            return Ok(0);
+        }
+        let after = Giro::read_by_id(giro.id, conn)?;
+        AuditLog::updated(user, giro, &after, conn)?;
+        Ok(n)
+    });
+    Giro::finalize_versioned(result)
+}
+
+fn check_open(form: &GiroForm, conn: &MoneyManagerDB) -> Result<(), ApiError> {
+    reconciliation::check_open("cleared_source", form.cleared_source, form.id_source_account, form.data, conn)?;
+    reconciliation::check_open("cleared_destination", form.cleared_destination, form.id_destination_account,
+                               form.data, conn)
+}
+
+// the giro and the form are the same movement, the cleared flags apart
+fn same_movement(giro: &Giro, form: &GiroForm) -> bool {
+    giro.id_source_account == Some(form.id_source_account)
+        && giro.id_destination_account == Some(form.id_destination_account)
+        && giro.data == form.data
+        && giro.note.as_ref().map(|s| s.as_str()) == form.note
+        && giro.amount == form.amount
+        && giro.expense == form.expense
+        && giro.id_currency == form.id_currency
+}
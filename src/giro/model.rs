@@ -17,33 +17,51 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 use crate::schema::giro;
 use crate::currency::model::Currency;
 use crate::account::model::Account;
-use crate::database::MoneyManagerDB;
+use crate::user::model::User;
+use crate::validation::{Validate, Validator};
+use crate::audit::model::Audited;
+use crate::error::ApiError;
 
 #[table_name = "giro"]
 //#[belongs_to(Account, foreign_key = "id_source_account")]
 //#[belongs_to(Account, foreign_key = "id_destination_account")]
 #[belongs_to(Currency, foreign_key = "id_currency")]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,JsonSchema)]
 pub struct Giro {
     pub id: i64,
-    pub id_source_account: i64,
-    pub id_destination_account: i64,
+    /// null when the account has been erased with its user
+    pub id_source_account: Option<i64>,
+    /// null when the account has been erased with its user
+    pub id_destination_account: Option<i64>,
     pub data: DateTime<Utc>,
     pub note: Option<String>,
     pub amount: f64,
     pub expense: Option<f64>,
-    pub id_currency: i16
+    pub id_currency: i16,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32,
+    /// it appears on the statement of the source account
+    pub cleared_source: bool,
+    /// the reconciliation of the source account that locked it
+    pub id_reconciliation_source: Option<i64>,
+    /// it appears on the statement of the destination account
+    pub cleared_destination: bool,
+    /// the reconciliation of the destination account that locked it
+    pub id_reconciliation_destination: Option<i64>
 }
 
-// only for insert and update
+// only for insert and update, the update replaces the whole row: a missing field becomes null
 #[table_name = "giro"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+#[derive(Debug,Serialize,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct GiroForm<'a> {
     pub id_source_account: i64,
     pub id_destination_account: i64,
@@ -51,43 +69,111 @@ pub struct GiroForm<'a> {
     pub note: Option<&'a str>,
     pub amount: f64,
     pub expense: Option<f64>,
-    pub id_currency: i16
+    pub id_currency: i16,
+    /// missing it is false on create and unchanged on update
+    #[serde(default)]
+    pub cleared_source: bool,
+    /// missing it is false on create and unchanged on update
+    #[serde(default)]
+    pub cleared_destination: bool
 }
 
 impl Giro {
-    pub fn create(form: &GiroForm, conn: &MoneyManagerDB) -> QueryResult<Giro> {
+    pub fn create(form: &GiroForm, conn: &PgConnection) -> QueryResult<Giro> {
         diesel::insert_into(giro::table)
             .values(form)
-            .get_result::<Giro>(&*(*conn))
+            .get_result::<Giro>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Giro>> {
+        giro::table.filter(giro::deleted_at.is_null())
+            .load::<Giro>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Giro>> {
-        giro::table.load::<Giro>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Giro> {
+        giro::table.find(id)
+            .filter(giro::deleted_at.is_null())
+            .first::<Giro>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Giro> {
-        giro::table.find(id).first::<Giro>(&*(*conn))
+    pub fn read_deleted_by_id(id: i64, conn: &PgConnection) -> QueryResult<Giro> {
+        giro::table.find(id)
+            .filter(giro::deleted_at.is_not_null())
+            .first::<Giro>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_source(account: &Account, conn: &MoneyManagerDB) -> QueryResult<Vec<Giro>> {
+    pub fn read_by_source(account: &Account, conn: &PgConnection) -> QueryResult<Vec<Giro>> {
         giro::table.filter(giro::id_source_account.eq(account.id))
-            .load::<Giro>(&*(*conn))
+            .filter(giro::deleted_at.is_null())
+            .load::<Giro>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_destination(account: &Account, conn: &MoneyManagerDB) -> QueryResult<Vec<Giro>> {
+    pub fn read_by_destination(account: &Account, conn: &PgConnection) -> QueryResult<Vec<Giro>> {
         giro::table.filter(giro::id_destination_account.eq(account.id))
-            .load::<Giro>(&*(*conn))
+            .filter(giro::deleted_at.is_null())
+            .load::<Giro>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(giro: &Giro, form: &GiroForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::update(giro)
-            .set(form)
-            .execute(&*(*conn))
+    ///
+    /// Only the version that was read is updated, no row means that it has changed in the meantime.
+    pub fn update(giro: &Giro, form: &GiroForm, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(giro::table.find(giro.id).filter(giro::version.eq(giro.version)))
+            .set((form, giro::version.eq(giro.version + 1)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Move the giro to the trash.
+    pub fn delete(giro: &Giro, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(giro::table.find(giro.id).filter(giro::version.eq(giro.version)))
+            .set((giro::deleted_at.eq(Utc::now()), giro::version.eq(giro.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(giro: &Giro, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::delete(giro)
-            .execute(&*(*conn))
+    pub fn restore(giro: &Giro, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(giro)
+            .set((giro::deleted_at.eq(None::<DateTime<Utc>>), giro::version.eq(giro.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
+
+// the entries are on the source account while it is there,
+// the users of the destination one read them as well
+impl Audited for Giro {
+    const ENTITY: &'static str = "giro";
+    fn entity_id(&self) -> i64 {
+        self.id
+    }
+    fn id_account(&self, _conn: &PgConnection) -> QueryResult<i64> {
+        self.id_source_account.or(self.id_destination_account)
+            .ok_or(diesel::result::Error::NotFound)
+    }
+}
+
+impl<'a> Validate for GiroForm<'a> {
+    fn validate(&self, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.positive("amount", self.amount);
+        v.not_negative("expense", self.expense);
+        v.max_length("note", self.note, 255);
+        v.check(self.id_source_account != self.id_destination_account, "id_destination_account",
+                "must be different from the source account");
+        let source = v.reference("id_source_account", Account::read_by_id(self.id_source_account, conn))?;
+        if source.is_some() {
+            v.account(self.id_source_account, user, conn)?;
+        }
+        let destination = v.reference("id_destination_account",
+                                      Account::read_by_id(self.id_destination_account, conn))?;
+        if destination.is_some() {
+            v.account(self.id_destination_account, user, conn)?;
+        }
+        if v.reference("id_currency", Currency::read_by_id(self.id_currency, conn))?.is_some() {
+            if let Some(source) = source {
+                v.check(source.id_currency == self.id_currency, "id_currency",
+                        "must be the currency of the source account");
+            }
+        }
+        v.finish()
+    }
+}
@@ -0,0 +1,69 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::RunQueryDsl;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::json::JsonValue;
+
+use crate::database::MoneyManagerDB;
+use crate::migration;
+
+///
+/// The process is alive, nothing else is checked.
+#[get("/health")]
+fn health() -> JsonValue {
+    json!({ "status": "ok" })
+}
+
+///
+/// The server can serve the requests: a connection of the pool answers and the schema is up to date.
+#[get("/ready")]
+fn ready(conn: Option<MoneyManagerDB>) -> Custom<JsonValue> {
+    debug!("READY_REQUEST");
+    let conn = match conn {
+        Some(conn) => conn,
+        None => return unavailable("no database connection available")
+    };
+    if let Err(e) = diesel::sql_query("SELECT 1").execute(&*conn) {
+        error!("Readiness: the database does not answer: {}", e);
+        return unavailable("the database does not answer");
+    }
+    match migration::pending(&conn) {
+        Ok(ref pending) if pending.is_empty() => Custom(Status::Ok, json!({ "status": "ready" })),
+        Ok(pending) => {
+            warn!("Readiness: pending migrations {}", pending.join(", "));
+            unavailable("pending migrations")
+        },
+        Err(e) => {
+            error!("Readiness: can not check the migrations: {}", e);
+            unavailable("can not check the migrations")
+        }
+    }
+}
+
+///
+///
+pub fn get_mount() -> Vec<rocket::Route> {
+    routes![health, ready]
+}
+
+// #################################################################################################
+
+fn unavailable(reason: &str) -> Custom<JsonValue> {
+    Custom(Status::ServiceUnavailable, json!({ "status": "unavailable", "reason": reason }))
+}
@@ -0,0 +1,111 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::thread;
+use std::time::Duration as StdDuration;
+use chrono::{Duration, Utc};
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use rocket::Rocket;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket_contrib::databases::database_config;
+
+use crate::user::erasure;
+use crate::trash::model as trash;
+use crate::idempotency::model as idempotency;
+
+///
+/// Periodic jobs: erasure of the users at the end of the grace period, purge of the trash
+/// and of the expired idempotency keys.
+/// They run on a thread of their own, at the start and then every interval, with a connection
+/// that is not taken from the pool of the requests. An interval of 0 turns them off.
+pub struct Housekeeping {
+    interval: u64,
+    trash_retention_days: i64,
+    idempotency_window_hours: i64
+}
+
+impl Housekeeping {
+    pub fn new(interval: u64, trash_retention_days: i64, idempotency_window_hours: i64) -> Housekeeping {
+        Housekeeping {
+            interval,
+            trash_retention_days,
+            idempotency_window_hours
+        }
+    }
+
+    fn run(&self, conn: &PgConnection) {
+        match erasure::purge_scheduled(conn) {
+            Ok(n) if n > 0 => info!("Housekeeping: {} users erased", n),
+            Ok(_) => debug!("Housekeeping: no user to erase"),
+            Err(e) => error!("Housekeeping: can not erase the users: {}", e)
+        }
+        let before = Utc::now() - Duration::days(self.trash_retention_days);
+        match trash::purge(before, conn) {
+            Ok(n) if n > 0 => info!("Housekeeping: {} rows purged from the trash", n),
+            Ok(_) => debug!("Housekeeping: nothing to purge from the trash"),
+            Err(e) => error!("Housekeeping: can not purge the trash: {}", e)
+        }
+        let before = Utc::now() - Duration::hours(self.idempotency_window_hours);
+        match idempotency::purge(before, conn) {
+            Ok(n) if n > 0 => info!("Housekeeping: {} expired idempotency keys purged", n),
+            Ok(_) => debug!("Housekeeping: no expired idempotency key"),
+            Err(e) => error!("Housekeeping: can not purge the idempotency keys: {}", e)
+        }
+    }
+}
+
+impl Fairing for Housekeeping {
+    fn info(&self) -> Info {
+        Info {
+            name: "Housekeeping",
+            kind: Kind::Attach
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        if self.interval == 0 {
+            info!("Housekeeping: disabled");
+            return Ok(rocket);
+        }
+        let url = match database_config("db", rocket.config()) {
+            Ok(config) => config.url.to_string(),
+            Err(e) => {
+                error!("Housekeeping: no database configured: {:?}", e);
+                return Err(rocket);
+            }
+        };
+        let housekeeping = Housekeeping::new(self.interval, self.trash_retention_days,
+                                             self.idempotency_window_hours);
+        let spawned = thread::Builder::new()
+            .name("housekeeping".to_string())
+            .spawn(move || loop {
+                match PgConnection::establish(&url) {
+                    Ok(conn) => housekeeping.run(&conn),
+                    Err(e) => warn!("Housekeeping: database not available: {}", e)
+                }
+                thread::sleep(StdDuration::from_secs(housekeeping.interval));
+            });
+        match spawned {
+            Ok(_) => Ok(rocket),
+            Err(e) => {
+                error!("Housekeeping: can not start the thread: {}", e);
+                Err(rocket)
+            }
+        }
+    }
+}
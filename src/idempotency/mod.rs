@@ -0,0 +1,186 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::Cursor;
+use chrono::{DateTime, Duration, Utc};
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::{DatabaseErrorKind, Error};
+use ring::digest;
+use data_encoding::HEXLOWER;
+use rocket::{Request, State};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder, Response};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::ApiError;
+use crate::validation::Validator;
+use crate::settings::Settings;
+use crate::user::model::User;
+use crate::idempotency::model::StoredResponse;
+
+pub mod model;
+
+const HEADER: &str = "Idempotency-Key";
+const MAX_KEY_LEN: usize = 255;
+
+///
+/// The Idempotency-Key header of the create routes. It is optional: without it
+/// every request creates a new entity, as before.
+pub struct IdempotencyKey {
+    key: Option<String>,
+    window: Duration
+}
+
+impl IdempotencyKey {
+    ///
+    /// Run the creation once per key: a retry with the same request receives the stored response,
+    /// a request with another body (or for another route) is refused with 422.
+    /// The errors are not stored, the client can fix the request and retry with the same key.
+    pub fn once<F, T>(&self, user: &User, route: &str, request: &T, conn: &PgConnection,
+                      create: F) -> Result<Replayable, ApiError>
+        where F: FnOnce() -> Result<Replayable, ApiError>, T: Serialize {
+        let key = match self.key {
+            Some(ref key) => key,
+            None => return create()
+        };
+        let hash = hash(route, request)?;
+        let since = Utc::now() - self.window;
+        if let Some(replayed) = replay(user, key, &hash, since, conn)? {
+            return Ok(replayed);
+        }
+        let mut failure = None;
+        let result = conn.transaction::<Replayable, Error, _>(|| {
+            StoredResponse::delete_expired(user, key, since, conn)?;
+            let response = create().map_err(|e| {
+                failure = Some(e);
+                Error::RollbackTransaction
+            })?;
+            StoredResponse::create(&StoredResponse {
+                id_user: user.id,
+                key: key.to_string(),
+                request_hash: hash.clone(),
+                status: response.status.code as i16,
+                response: response.body.clone(),
+                creation_date: Utc::now()
+            }, conn)?;
+            Ok(response)
+        });
+        match (result, failure) {
+            (Ok(response), _) => Ok(response),
+            (Err(_), Some(e)) => Err(e),
+            (Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)), None) => {
+                // a request with the same key completed in the meantime, this one is undone
+                warn!("Concurrent requests with the idempotency key {}", key);
+                replay(user, key, &hash, since, conn)?.ok_or_else(|| ApiError::conflict("The key is in use by another request"))
+            },
+            (Err(e), None) => {
+                error!("Can not store the idempotency key caused by {}", e);
+                Err(ApiError::internal())
+            }
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IdempotencyKey {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> Outcome<IdempotencyKey, ()> {
+        let key = request.headers().get_one(HEADER).map(|s| s.to_string());
+        if key.as_ref().map_or(false, |k| k.trim().is_empty() || k.len() > MAX_KEY_LEN) {
+            warn!("The {} header is empty or too long", HEADER);
+            return Outcome::Failure((Status::BadRequest, ()));
+        }
+        match request.guard::<State<Settings>>().succeeded() {
+            Some(settings) => Outcome::Success(IdempotencyKey {
+                key,
+                window: Duration::hours(settings.idempotency_window_hours)
+            }),
+            None => Outcome::Failure((Status::InternalServerError, ()))
+        }
+    }
+}
+
+///
+/// The response of a create route, as it is stored for the retries.
+/// A replayed response has the Idempotent-Replayed header.
+pub struct Replayable {
+    status: Status,
+    body: Option<Value>,
+    replayed: bool
+}
+
+impl Replayable {
+    pub fn json<T: Serialize>(status: Status, body: &T) -> Result<Replayable, ApiError> {
+        let body = serde_json::to_value(body).map_err(|e| {
+            error!("Can not serialize the response caused by {}", e);
+            ApiError::internal()
+        })?;
+        Ok(Replayable { status, body: Some(body), replayed: false })
+    }
+    pub fn empty(status: Status) -> Replayable {
+        Replayable { status, body: None, replayed: false }
+    }
+}
+
+impl<'r> Responder<'r> for Replayable {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        let mut response = Response::build();
+        response.status(self.status);
+        if let Some(body) = self.body {
+            response.header(ContentType::JSON).sized_body(Cursor::new(body.to_string()));
+        }
+        if self.replayed {
+            response.raw_header("Idempotent-Replayed", "true");
+        }
+        response.ok()
+    }
+}
+
+// #################################################################################################
+
+fn hash<T: Serialize>(route: &str, request: &T) -> Result<String, ApiError> {
+    let json = serde_json::to_string(request).map_err(|e| {
+        error!("Can not serialize the request caused by {}", e);
+        ApiError::internal()
+    })?;
+    let data = format!("{}\n{}", route, json);
+    Ok(HEXLOWER.encode(digest::digest(&digest::SHA256, data.as_bytes()).as_ref()))
+}
+
+fn replay(user: &User, key: &str, hash: &str, since: DateTime<Utc>,
+          conn: &PgConnection) -> Result<Option<Replayable>, ApiError> {
+    let stored = match StoredResponse::read(user, key, since, conn) {
+        Ok(stored) => stored,
+        Err(Error::NotFound) => return Ok(None),
+        Err(e) => return Err(ApiError::from_db("idempotency key", e))
+    };
+    if stored.request_hash != hash {
+        warn!("The idempotency key {} is used for another request", key);
+        let mut v = Validator::new();
+        v.error(HEADER, "has been used for another request");
+        v.finish()?;
+    }
+    debug!("Replay the response of the idempotency key {}", key);
+    Ok(Some(Replayable {
+        status: Status::from_code(stored.status as u16).unwrap_or(Status::Ok),
+        body: stored.response,
+        replayed: true
+    }))
+}
@@ -0,0 +1,75 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::schema::idempotency_key;
+use crate::user::model::User;
+
+///
+/// The response given to the first request with a key, with the hash of that request.
+#[table_name = "idempotency_key"]
+#[derive(Debug,Queryable,Insertable)]
+pub struct StoredResponse {
+    pub id_user: i64,
+    pub key: String,
+    pub request_hash: String,
+    pub status: i16,
+    pub response: Option<Value>,
+    pub creation_date: DateTime<Utc>
+}
+
+impl StoredResponse {
+    pub fn create(stored: &StoredResponse, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::insert_into(idempotency_key::table)
+            .values(stored)
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// The response of the key, if it has been stored after since.
+    pub fn read(user: &User, key: &str, since: DateTime<Utc>, conn: &PgConnection) -> QueryResult<StoredResponse> {
+        idempotency_key::table.find((user.id, key))
+            .filter(idempotency_key::creation_date.gt(since))
+            .first::<StoredResponse>(conn)
+    }
+    ///
+    /// An expired key can be used again.
+    pub fn delete_expired(user: &User, key: &str, since: DateTime<Utc>, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(idempotency_key::table.find((user.id, key))
+            .filter(idempotency_key::creation_date.le(since)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete_by_user(user: &User, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(idempotency_key::table.filter(idempotency_key::id_user.eq(user.id)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+///
+/// Delete the keys stored before the given time, the retries are no longer recognized.
+pub fn purge(before: DateTime<Utc>, conn: &PgConnection) -> QueryResult<usize> {
+    diesel::delete(idempotency_key::table.filter(idempotency_key::creation_date.le(before)))
+        .execute(conn)
+        .map_err(|e| { warn!("{}", e); e })
+}
@@ -0,0 +1,82 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::settings::Settings;
+
+///
+/// Something able to deliver an email to a user.
+pub trait Mailer: Send + Sync {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String>;
+}
+
+///
+/// Only writes the email in the log, useful in development.
+pub struct LogMailer;
+
+///
+/// Delivers the email through the local sendmail binary.
+pub struct SendmailMailer {
+    pub from: String,
+    pub path: String
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        info!("MAIL to: {} - subject: {}\n{}", to, subject, body);
+        Ok(())
+    }
+}
+
+impl Mailer for SendmailMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        let mut child = Command::new(&self.path)
+            .arg("-t")
+            .arg("-i")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("can not start {}: {}", self.path, e))?;
+        {
+            let stdin = child.stdin.as_mut().ok_or("can not open sendmail stdin".to_string())?;
+            write!(stdin, "From: {}\nTo: {}\nSubject: {}\n\n{}\n", self.from, to, subject, body)
+                .map_err(|e| format!("can not write the email: {}", e))?;
+        }
+        let status = child.wait().map_err(|e| e.to_string())?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("sendmail exited with {}", status))
+        }
+    }
+}
+
+///
+/// Choose the mailer from the settings.
+pub fn from_settings(settings: &Settings) -> Box<dyn Mailer> {
+    match settings.mailer.as_str() {
+        "sendmail" => {
+            info!("Emails are delivered with {}", settings.sendmail_path);
+            Box::new(SendmailMailer {
+                from: settings.mail_from.clone(),
+                path: settings.sendmail_path.clone()
+            })
+        },
+        _ => Box::new(LogMailer)
+    }
+}
@@ -15,46 +15,37 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-#![feature(proc_macro_hygiene, decl_macro)]
-
-#[macro_use] extern crate rocket;
-#[macro_use] extern crate rocket_contrib;
-#[macro_use] extern crate diesel;
-
 #[macro_use] extern crate log;
 extern crate log4rs;
 
-use std::path::Path;
+use std::env;
+use std::process;
 
-mod controller;
-mod base_model;
-mod base_controller;
-mod database;
-mod schema;
-
-mod auth;
-mod causal;
-mod user;
-mod account;
-mod currency;
-mod transaction;
-mod place;
-mod detail;
-mod giro;
+use money_manager_server::{controller, cli, settings};
 
 fn main() {
-    let path = if cfg!(windows) {
-        "log-config.yml"
-    } else {
-        let path = "/etc/money-manager/log-config.yml";
-        if Path::new(path).exists() {
-            path
-        } else {
-            "log-config.yml"
+    let path = settings::log_config_path();
+    log4rs::init_file(&path, Default::default()).unwrap();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        let (config, settings) = match settings::load() {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                error!("{}", e);
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+        let error = controller::init(config, settings); // return only on error
+        error!("Launch failed! Error: {}", error);
+    } else if let Err(e) = cli::run(&args) {
+        if e == cli::USAGE {
+            eprintln!("{}", e);
+            process::exit(2);
         }
-    };
-    log4rs::init_file(path, Default::default()).unwrap();
-
-    let error = controller::init(); // return only on error
-    error!("Launch failed! Error: {}", error);
+        error!("Command {} failed! Error: {}", args[0], e);
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,172 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use rocket::{Request, Response, Data, Rocket, State};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::response::content::Content;
+use rocket::http::ContentType;
+
+use crate::database::{self, MoneyManagerDBPool};
+
+// upper bounds of the latency histogram, in seconds
+const BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+const AUTH_FAILURE_REASONS: [&str; 8] = ["login", "missing", "invalid", "bad_count", "broken", "scope", "disabled", "role"];
+
+static AUTH_FAILURES: [AtomicU64; 8] = [
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)
+];
+
+///
+/// Count and latency of the requests per route and status, collected by a fairing
+/// and exposed at /metrics in the Prometheus text format.
+pub struct Metrics {
+    requests: Arc<Mutex<BTreeMap<(String, u16), Histogram>>>
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; 10],
+    count: u64,
+    sum: f64
+}
+
+struct Start(Option<Instant>);
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            requests: Arc::new(Mutex::new(BTreeMap::new()))
+        }
+    }
+}
+
+impl Fairing for Metrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Metrics",
+            kind: Kind::Attach | Kind::Request | Kind::Response
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        // the route reads the same registry
+        Ok(rocket.manage(Registry(self.requests.clone())))
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        request.local_cache(|| Start(Some(Instant::now())));
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let elapsed = match request.local_cache(|| Start(None)).0 {
+            Some(start) => start.elapsed(),
+            None => return
+        };
+        let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        let route = request.route()
+            .map(|r| format!("{} {}", r.method, r.uri.path()))
+            .unwrap_or_else(|| "unmatched".to_string());
+        let mut requests = match self.requests.lock() {
+            Ok(requests) => requests,
+            Err(poisoned) => poisoned.into_inner()
+        };
+        let histogram = requests.entry((route, response.status().code)).or_insert_with(Histogram::default);
+        for (i, bound) in BUCKETS.iter().enumerate() {
+            if seconds <= *bound {
+                histogram.buckets[i] += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+}
+
+struct Registry(Arc<Mutex<BTreeMap<(String, u16), Histogram>>>);
+
+///
+/// A request refused by the authentication, the reason is one of AUTH_FAILURE_REASONS.
+pub fn auth_failure(reason: &str) {
+    match AUTH_FAILURE_REASONS.iter().position(|r| *r == reason) {
+        Some(i) => {
+            AUTH_FAILURES[i].fetch_add(1, Ordering::Relaxed);
+        },
+        None => warn!("Unknown authentication failure {}", reason)
+    }
+}
+
+#[get("/metrics")]
+fn metrics(registry: State<Registry>, pool: Option<State<MoneyManagerDBPool>>) -> Content<String> {
+    debug!("METRICS_REQUEST");
+    let mut out = String::new();
+    {
+        let requests = match registry.0.lock() {
+            Ok(requests) => requests,
+            Err(poisoned) => poisoned.into_inner()
+        };
+        out.push_str("# HELP http_requests_total Requests by route and status.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((route, status), h) in requests.iter() {
+            let _ = writeln!(out, "http_requests_total{{route=\"{}\",status=\"{}\"}} {}", escape(route), status, h.count);
+        }
+        out.push_str("# HELP http_request_duration_seconds Latency of the requests by route and status.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((route, status), h) in requests.iter() {
+            let labels = format!("route=\"{}\",status=\"{}\"", escape(route), status);
+            for (i, bound) in BUCKETS.iter().enumerate() {
+                let _ = writeln!(out, "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, h.buckets[i]);
+            }
+            let _ = writeln!(out, "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, h.count);
+            let _ = writeln!(out, "http_request_duration_seconds_sum{{{}}} {}", labels, h.sum);
+            let _ = writeln!(out, "http_request_duration_seconds_count{{{}}} {}", labels, h.count);
+        }
+    }
+    if let Some(pool) = pool {
+        let (open, idle, max) = database::pool_usage(&pool);
+        out.push_str("# HELP db_pool_connections Connections of the database pool by state.\n");
+        out.push_str("# TYPE db_pool_connections gauge\n");
+        let _ = writeln!(out, "db_pool_connections{{state=\"active\"}} {}", open - idle);
+        let _ = writeln!(out, "db_pool_connections{{state=\"idle\"}} {}", idle);
+        out.push_str("# HELP db_pool_max_connections Size of the database pool.\n");
+        out.push_str("# TYPE db_pool_max_connections gauge\n");
+        let _ = writeln!(out, "db_pool_max_connections {}", max);
+    }
+    out.push_str("# HELP auth_failures_total Requests refused by the authentication, by reason.\n");
+    out.push_str("# TYPE auth_failures_total counter\n");
+    for (i, reason) in AUTH_FAILURE_REASONS.iter().enumerate() {
+        let _ = writeln!(out, "auth_failures_total{{reason=\"{}\"}} {}", reason, AUTH_FAILURES[i].load(Ordering::Relaxed));
+    }
+    Content(ContentType::with_params("text", "plain", ("version", "0.0.4")), out)
+}
+
+///
+///
+pub fn get_mount() -> Vec<rocket::Route> {
+    routes![metrics]
+}
+
+// #################################################################################################
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
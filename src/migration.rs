@@ -0,0 +1,77 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+
+// the migrations directory is compiled into the binary
+embed_migrations!();
+
+const RUNNING: &str = "Running migration ";
+
+///
+/// Apply the migrations not yet applied, each one in its own transaction.
+pub fn run(conn: &PgConnection) -> Result<(), String> {
+    let mut output = Vec::new();
+    let result = embedded_migrations::run_with_output(conn, &mut output);
+    for version in parse_output(&output) {
+        info!("Migration {} applied", version);
+    }
+    result.map_err(|e| e.to_string())
+}
+
+///
+/// The migrations not yet applied: they are run in a transaction that is always rolled back.
+pub fn pending(conn: &PgConnection) -> Result<Vec<String>, String> {
+    let mut output = Vec::new();
+    let mut failure = None;
+    let result = conn.transaction::<(), Error, _>(|| {
+        if let Err(e) = embedded_migrations::run_with_output(conn, &mut output) {
+            failure = Some(e.to_string());
+        }
+        Err(Error::RollbackTransaction)
+    });
+    match (result, failure) {
+        (_, Some(e)) => Err(e),
+        (Err(Error::RollbackTransaction), None) => Ok(parse_output(&output)),
+        (Err(e), None) => Err(e.to_string()),
+        (Ok(_), None) => Ok(parse_output(&output))
+    }
+}
+
+///
+/// Fail if the database is behind the binary, nothing is changed.
+pub fn verify(conn: &PgConnection) -> Result<(), String> {
+    let pending = pending(conn)?;
+    if pending.is_empty() {
+        info!("The database schema is up to date");
+        Ok(())
+    } else {
+        Err(format!("pending migrations: {}", pending.join(", ")))
+    }
+}
+
+// #################################################################################################
+
+fn parse_output(output: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter(|l| l.starts_with(RUNNING))
+        .map(|l| l[RUNNING.len()..].trim().to_string())
+        .collect()
+}
@@ -0,0 +1,529 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use rocket::{Route, State};
+use rocket::response::content::Html;
+use rocket_contrib::json::JsonValue;
+use schemars::JsonSchema;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use serde_json::{Map, Value};
+
+use crate::audit::model::AuditLog;
+use crate::account::model::{Account, AccountForm, AccountType, AccountTypeForm};
+use crate::account::balance::AccountBalance;
+use crate::auth::{AuthJSON, ForgotPasswordJSON, ResetPasswordJSON};
+use crate::auth::access_token::AccessTokenJSON;
+use crate::auth::model::{Auth, AccessToken};
+use crate::causal::CausalJSON;
+use crate::causal::model::Causal;
+use crate::currency::model::{Currency, CurrencyForm, ExchangeRate};
+use crate::detail::DetailJSON;
+use crate::detail::model::Detail;
+use crate::giro::model::{Giro, GiroForm};
+use crate::place::model::{Place, PlaceForm};
+use crate::transaction::model::{Transaction, TransactionForm, TransactionType, TransactionTypeForm, TransactionDetail,
+                                BulkRequest, BulkReport, LedgerEntry};
+use crate::transaction::suggestion::Suggestions;
+use crate::trash::model::Trash;
+use crate::reconciliation::model::{Reconciliation, ReconciliationForm};
+use crate::report::net_worth::NetWorth;
+use crate::rule::RuleApplication;
+use crate::rule::model::{Rule, RuleForm};
+use crate::user::UserJSON;
+use crate::user::model::User;
+
+///
+/// The document is built once, from the mounted routes.
+pub struct OpenApi(Value);
+
+struct Operation {
+    summary: &'static str,
+    request: Option<Value>,
+    response: Option<Value>
+}
+
+#[get("/openapi.json")]
+fn openapi(doc: State<OpenApi>) -> JsonValue {
+    debug!("OPENAPI_REQUEST");
+    JsonValue(doc.0.clone())
+}
+
+#[get("/docs")]
+fn docs() -> Html<&'static str> {
+    debug!("DOCS_REQUEST");
+    Html(DOCS)
+}
+
+///
+/// Mount the documentation routes, then describe all the routes of the server.
+/// It must be the last mount.
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    let rocket = rocket.mount("/", routes![openapi, docs]);
+    let doc = document(rocket.routes());
+    rocket.manage(OpenApi(doc))
+}
+
+///
+/// OpenAPI 3 document of the routes: the paths come from the routes, the bodies from the models.
+/// A route without description is published anyway, with a generic summary.
+pub fn document<'a, I: Iterator<Item = &'a Route>>(routes: I) -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let mut paths = Map::new();
+    for route in routes {
+        let path = path(route.uri.path());
+        let method = route.method.as_str().to_lowercase();
+        let operation = describe(&method, &path, &mut gen).unwrap_or_else(|| {
+            warn!("The route {} {} is not described in the OpenAPI document", method, path);
+            Operation { summary: "Not documented", request: None, response: None }
+        });
+        let item = paths.entry(path.clone()).or_insert_with(|| Value::Object(Map::new()));
+        item[&method] = operation_json(&method, &path, route.uri.query(), operation);
+    }
+    let mut schemas = serde_json::to_value(gen.definitions()).unwrap_or_else(|_| json_object());
+    schemas["Error"] = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "code": { "type": "string" },
+            "message": { "type": "string" },
+            "fields": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "field": { "type": "string" },
+                        "message": { "type": "string" }
+                    }
+                }
+            },
+            "request_id": { "type": "string" }
+        }
+    });
+    serde_json::json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "Money Manager",
+            "version": env!("CARGO_PKG_VERSION"),
+            "license": { "name": "GPL-3.0-or-later" }
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT or access token" }
+            },
+            "responses": {
+                "Unauthorized": error_response("The token is missing, invalid or revoked"),
+                "Forbidden": error_response("The entity does not belong to the user"),
+                "NotFound": error_response("The entity does not exist"),
+                "Unprocessable": error_response("The body is not valid, see fields"),
+                "PreconditionFailed": error_response("The entity has been changed in the meantime, read it again")
+            }
+        }
+    })
+}
+
+// #################################################################################################
+
+const DOCS: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Money Manager API</title>
+    <meta charset="utf-8"/>
+  </head>
+  <body>
+    <redoc spec-url="/openapi.json"></redoc>
+    <script src="https://cdn.jsdelivr.net/npm/redoc@2.0.0-rc.18/bundles/redoc.standalone.js"></script>
+  </body>
+</html>
+"#;
+
+// the routes that do not need a token
+const PUBLIC: [(&str, &str); 11] = [
+    ("post", "/user"),
+    ("post", "/auth/{id}"),
+    ("post", "/auth/login"),
+    ("post", "/auth/password/forgot"),
+    ("post", "/auth/password/reset"),
+    ("get", "/.well-known/jwks.json"),
+    ("get", "/openapi.json"),
+    ("get", "/docs"),
+    ("get", "/health"),
+    ("get", "/ready"),
+    ("get", "/metrics")
+];
+
+// the entities with a version, read with an ETag and changed with If-Match
+const VERSIONED: [&str; 6] = [
+    "/account/{id}",
+    "/transaction/{id}",
+    "/giro/{id}",
+    "/place/{id}",
+    "/causal/{id}",
+    "/detail/{id}"
+];
+
+// the create routes that honour the Idempotency-Key header
+const IDEMPOTENT: [&str; 9] = [
+    "/account",
+    "/transaction",
+    "/transaction/detail",
+    "/transaction/bulk",
+    "/giro",
+    "/place",
+    "/causal",
+    "/detail",
+    "/rule"
+];
+
+fn describe(method: &str, path: &str, gen: &mut SchemaGenerator) -> Option<Operation> {
+    let op = |summary, request, response| Some(Operation { summary, request, response });
+    match (method, path) {
+        ("post", "/user") => op("Register a user", Some(schema::<UserJSON>(gen)), Some(schema::<User>(gen))),
+        ("get", "/user/user") => op("Read the current user", None, Some(schema::<User>(gen))),
+        ("put", "/user/user") => op("Update the current user", Some(schema::<UserJSON>(gen)), None),
+        ("delete", "/user/user") => op("Erase the current user, at the end of the grace period", None,
+                                       Some(deletion_date())),
+        ("delete", "/user/user/deletion") => op("Cancel the scheduled erasure", None, None),
+
+        ("post", "/auth/{id}") => op("Create the credentials of a user", Some(schema::<AuthJSON>(gen)), None),
+        ("post", "/auth/login") => op("Login", Some(schema::<AuthJSON>(gen)), Some(token())),
+        ("get", "/auth") => op("Read the credentials of the current user", None, Some(schema::<Auth>(gen))),
+        ("put", "/auth") => op("Change email and password", Some(schema::<AuthJSON>(gen)), None),
+        ("delete", "/auth") => op("Delete the credentials of the current user", None, None),
+        ("post", "/auth/password/forgot") => op("Send a password reset token", Some(schema::<ForgotPasswordJSON>(gen)), None),
+        ("post", "/auth/password/reset") => op("Reset the password", Some(schema::<ResetPasswordJSON>(gen)), None),
+        ("post", "/auth/tokens") => op("Create a personal access token, shown only once",
+                                       Some(schema::<AccessTokenJSON>(gen)), Some(access_token())),
+        ("get", "/auth/tokens") => op("List the access tokens", None, Some(schema::<Vec<AccessToken>>(gen))),
+        ("delete", "/auth/tokens/{id}") => op("Revoke an access token", None, None),
+        ("get", "/.well-known/jwks.json") => op("Public keys of the tokens", None, Some(json_object())),
+
+        ("post", "/account") => op("Create an account", Some(schema::<AccountForm>(gen)), Some(schema::<Account>(gen))),
+        ("get", "/account/user") => op("List the accounts of the user", None, Some(schema::<Vec<Account>>(gen))),
+        ("get", "/account/{id}") => op("Read an account", None, Some(schema::<Account>(gen))),
+        ("get", "/account/{id}/balance") => op("The balance of an account at a date, from its movements", None,
+                                               Some(schema::<AccountBalance>(gen))),
+        ("put", "/account/{id}") => op("Update an account", Some(schema::<AccountForm>(gen)), None),
+        ("patch", "/account/{id}") => op("Change some fields of an account", Some(json_object()), None),
+        ("delete", "/account/{id}") => op("Move an account to the trash with its movements", None, None),
+        ("post", "/account/{id}/restore") => op("Restore an account with the movements deleted with it", None, None),
+        ("post", "/account/{id}/reconcile") => op("Check the cleared movements against a statement, lock them if they match",
+                                                  Some(schema::<ReconciliationForm>(gen)),
+                                                  Some(schema::<Reconciliation>(gen))),
+        ("get", "/report/net-worth") => op("The net worth at the end of every period, from the movements of the accounts",
+                                           None, Some(schema::<NetWorth>(gen))),
+        ("get", "/reconciliation/account/{id}") => op("List the reconciliations of an account", None,
+                                                      Some(schema::<Vec<Reconciliation>>(gen))),
+        ("get", "/account/type") => op("List the account types", None, Some(schema::<Vec<AccountType>>(gen))),
+        ("get", "/account/type/{id}") => op("Read an account type", None, Some(schema::<AccountType>(gen))),
+        ("post", "/admin/account/type") => op("Create an account type", Some(schema::<AccountTypeForm>(gen)),
+                                              Some(schema::<AccountType>(gen))),
+        ("put", "/admin/account/type/{id}") => op("Update an account type", Some(schema::<AccountTypeForm>(gen)), None),
+        ("delete", "/admin/account/type/{id}") => op("Delete an account type", None, None),
+
+        ("get", "/currency") => op("List the currencies", None, Some(schema::<Vec<Currency>>(gen))),
+        ("get", "/currency/{id}") => op("Read a currency", None, Some(schema::<Currency>(gen))),
+        ("post", "/admin/currency") => op("Create a currency", Some(schema::<CurrencyForm>(gen)), Some(schema::<Currency>(gen))),
+        ("put", "/admin/currency/{id}") => op("Update a currency", Some(schema::<CurrencyForm>(gen)), None),
+        ("delete", "/admin/currency/{id}") => op("Delete a currency", None, None),
+        ("get", "/currency/rate/{id_currency}/{id_base_currency}") => op("List the exchange rates of a currency, the oldest first",
+                                                                       None, Some(schema::<Vec<ExchangeRate>>(gen))),
+        ("post", "/admin/currency/rate") => op("Set the exchange rate of a day", Some(schema::<ExchangeRate>(gen)),
+                                               Some(schema::<ExchangeRate>(gen))),
+        ("delete", "/admin/currency/rate/{id_currency}/{id_base_currency}/{date}") =>
+            op("Delete an exchange rate", None, None),
+
+        ("post", "/transaction") => op("Create a transaction", Some(schema::<TransactionForm>(gen)),
+                                       Some(schema::<Transaction>(gen))),
+        ("get", "/transaction/{id}") => op("Read a transaction", None, Some(schema::<Transaction>(gen))),
+        ("get", "/transaction/account/{id}") => op("List the transactions of an account, optionally with the running balance",
+                                                   None, Some(schema::<Vec<LedgerEntry>>(gen))),
+        ("put", "/transaction/{id}") => op("Update a transaction", Some(schema::<TransactionForm>(gen)), None),
+        ("patch", "/transaction/{id}") => op("Change some fields of a transaction", Some(json_object()), None),
+        ("delete", "/transaction/{id}") => op("Move a transaction to the trash", None, None),
+        ("post", "/transaction/{id}/restore") => op("Restore a transaction, 409 when its account is in the trash",
+                                                    None, None),
+        ("get", "/transaction/type") => op("List the transaction types", None, Some(schema::<Vec<TransactionType>>(gen))),
+        ("get", "/transaction/type/{id}") => op("Read a transaction type", None, Some(schema::<TransactionType>(gen))),
+        ("post", "/admin/transaction/type") => op("Create a transaction type", Some(schema::<TransactionTypeForm>(gen)),
+                                                  Some(schema::<TransactionType>(gen))),
+        ("put", "/admin/transaction/type/{id}") => op("Update a transaction type",
+                                                      Some(schema::<TransactionTypeForm>(gen)), None),
+        ("delete", "/admin/transaction/type/{id}") => op("Delete a transaction type", None, None),
+        ("post", "/transaction/detail") => op("Add a detail to a transaction", Some(schema::<TransactionDetail>(gen)), None),
+        ("put", "/transaction/detail") => op("Update the detail of a transaction", Some(schema::<TransactionDetail>(gen)), None),
+        ("get", "/transaction/detail/transaction/{id}") => op("List the details of a transaction", None,
+                                                              Some(schema::<Vec<TransactionDetail>>(gen))),
+        ("get", "/transaction/detail/detail/{id}") => op("List the transactions of a detail", None,
+                                                         Some(schema::<Vec<TransactionDetail>>(gen))),
+        ("delete", "/transaction/detail/transaction/{id_transaction}/detail/{id_detail}") =>
+            op("Remove a detail from a transaction", None, None),
+        ("post", "/transaction/bulk") => op("Create, change, delete or move many transactions at once",
+                                            Some(schema::<BulkRequest>(gen)), Some(schema::<BulkReport>(gen))),
+        ("get", "/transaction/suggest") => op("Suggest causals, places and details from the similar past transactions",
+                                              None, Some(schema::<Suggestions>(gen))),
+
+        ("post", "/giro") => op("Move money between two accounts of the user", Some(schema::<GiroForm>(gen)),
+                                Some(schema::<Giro>(gen))),
+        ("get", "/giro/{id}") => op("Read a giro", None, Some(schema::<Giro>(gen))),
+        ("get", "/giro/account/source/{id}") => op("List the giros from an account", None, Some(schema::<Vec<Giro>>(gen))),
+        ("get", "/giro/account/destination/{id}") => op("List the giros to an account", None, Some(schema::<Vec<Giro>>(gen))),
+        ("put", "/giro/{id}") => op("Update a giro", Some(schema::<GiroForm>(gen)), None),
+        ("patch", "/giro/{id}") => op("Change some fields of a giro", Some(json_object()), None),
+        ("delete", "/giro/{id}") => op("Move a giro to the trash", None, None),
+        ("post", "/giro/{id}/restore") => op("Restore a giro, 409 when one of its accounts is in the trash", None, None),
+
+        ("post", "/causal") => op("Create a causal", Some(schema::<CausalJSON>(gen)), Some(schema::<Causal>(gen))),
+        ("get", "/causal/user") => op("List the causals of the user and the global ones", None,
+                                      Some(schema::<Vec<Causal>>(gen))),
+        ("get", "/causal/{id}") => op("Read a causal", None, Some(schema::<Causal>(gen))),
+        ("put", "/causal/{id}") => op("Update a causal", Some(schema::<CausalJSON>(gen)), None),
+        ("delete", "/causal/{id}") => op("Move a causal to the trash", None, None),
+        ("post", "/causal/{id}/restore") => op("Restore a causal", None, None),
+
+        ("post", "/place") => op("Create a place", Some(schema::<PlaceForm>(gen)), Some(schema::<Place>(gen))),
+        ("get", "/place/user") => op("List the places of the user", None, Some(schema::<Vec<Place>>(gen))),
+        ("get", "/place/{id}") => op("Read a place", None, Some(schema::<Place>(gen))),
+        ("put", "/place/{id}") => op("Update a place", Some(schema::<PlaceForm>(gen)), None),
+        ("patch", "/place/{id}") => op("Change some fields of a place", Some(json_object()), None),
+        ("delete", "/place/{id}") => op("Move a place to the trash", None, None),
+        ("post", "/place/{id}/restore") => op("Restore a place", None, None),
+
+        ("post", "/detail") => op("Create a detail", Some(schema::<DetailJSON>(gen)), Some(schema::<Detail>(gen))),
+        ("get", "/detail/user") => op("List the details of the user", None, Some(schema::<Vec<Detail>>(gen))),
+        ("get", "/detail/{id}") => op("Read a detail", None, Some(schema::<Detail>(gen))),
+        ("put", "/detail/{id}") => op("Update a detail", Some(schema::<DetailJSON>(gen)), None),
+        ("delete", "/detail/{id}") => op("Delete a detail", None, None),
+
+        ("get", "/admin/user") => op("List all the users", None, Some(schema::<Vec<User>>(gen))),
+        ("put", "/admin/user/{id}/disable") => op("Disable a user", None, None),
+        ("put", "/admin/user/{id}/enable") => op("Enable a user", None, None),
+
+        ("get", "/trash") => op("What the user deleted in the retention period", None, Some(schema::<Trash>(gen))),
+
+        ("get", "/audit") => op("History of the changes of an entity in the accounts of the user", None,
+                                Some(schema::<Vec<AuditLog>>(gen))),
+
+        ("post", "/rule") => op("Create a rule that categorizes the new transactions", Some(schema::<RuleForm>(gen)),
+                                Some(schema::<Rule>(gen))),
+        ("get", "/rule/{id}") => op("Read a rule", None, Some(schema::<Rule>(gen))),
+        ("get", "/rule/user") => op("List the rules of the user, in the order they are applied", None,
+                                    Some(schema::<Vec<Rule>>(gen))),
+        ("put", "/rule/{id}") => op("Update a rule", Some(schema::<RuleForm>(gen)), None),
+        ("delete", "/rule/{id}") => op("Delete a rule", None, None),
+        ("post", "/rule/{id}/apply") => op("Apply a rule to the existing transactions, the dry run changes nothing",
+                                           None, Some(schema::<RuleApplication>(gen))),
+
+        ("get", "/health") => op("The process is alive", None, Some(status())),
+        ("get", "/ready") => op("The database answers and its schema is up to date, otherwise 503", None,
+                                Some(status())),
+        ("get", "/metrics") => op("Metrics in the Prometheus text format", None, None),
+
+        ("get", "/openapi.json") => op("This document", None, Some(json_object())),
+        ("get", "/docs") => op("Interactive documentation of this document", None, None),
+        _ => None
+    }
+}
+
+fn operation_json(method: &str, path: &str, query: Option<&str>, operation: Operation) -> Value {
+    let public = PUBLIC.contains(&(method, path));
+    let mut parameters = Vec::new();
+    for name in path.split('/').filter(|s| s.starts_with('{')) {
+        let name = name.trim_matches(|c| c == '{' || c == '}');
+        parameters.push(serde_json::json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": { "type": param_type(name) }
+        }));
+    }
+    for name in query.into_iter().flat_map(|q| q.split('&')) {
+        let name = name.trim_matches(|c| c == '<' || c == '>');
+        parameters.push(serde_json::json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "schema": { "type": param_type(name) }
+        }));
+    }
+    let mut responses = Map::new();
+    match (path, operation.response) {
+        ("/docs", _) => {
+            responses.insert("200".to_string(), serde_json::json!({
+                "description": "HTML page",
+                "content": { "text/html": {} }
+            }));
+        },
+        ("/metrics", _) => {
+            responses.insert("200".to_string(), serde_json::json!({
+                "description": "Prometheus text format",
+                "content": { "text/plain": {} }
+            }));
+        },
+        (_, Some(schema)) => {
+            responses.insert("200".to_string(), serde_json::json!({
+                "description": "OK",
+                "content": { "application/json": { "schema": schema } }
+            }));
+        },
+        (_, None) => {
+            responses.insert("204".to_string(), serde_json::json!({ "description": "Done" }));
+        }
+    }
+    if !public {
+        responses.insert("401".to_string(), response_ref("Unauthorized"));
+        responses.insert("403".to_string(), response_ref("Forbidden"));
+    }
+    if !parameters.is_empty() {
+        responses.insert("404".to_string(), response_ref("NotFound"));
+    }
+    let versioned = VERSIONED.contains(&path);
+    if versioned && (method == "put" || method == "patch" || method == "delete") {
+        parameters.push(serde_json::json!({
+            "name": "If-Match",
+            "in": "header",
+            "required": false,
+            "description": "The ETag of the version that is changed",
+            "schema": { "type": "string" }
+        }));
+        responses.insert("412".to_string(), response_ref("PreconditionFailed"));
+    }
+    if method == "post" && IDEMPOTENT.contains(&path) {
+        parameters.push(serde_json::json!({
+            "name": "Idempotency-Key",
+            "in": "header",
+            "required": false,
+            "description": "A retry with the same key and body receives the first response, with another body 422",
+            "schema": { "type": "string", "maxLength": 255 }
+        }));
+    }
+    if let (true, "get", Some(ok)) = (versioned, method, responses.get_mut("200")) {
+        ok["headers"] = serde_json::json!({
+            "ETag": { "description": "The version of the entity", "schema": { "type": "string" } }
+        });
+    }
+    let mut operation_json = serde_json::json!({
+        "summary": operation.summary,
+        "tags": [tag(path)],
+        "responses": responses
+    });
+    if !parameters.is_empty() {
+        operation_json["parameters"] = Value::Array(parameters);
+    }
+    if let Some(request) = operation.request {
+        operation_json["requestBody"] = serde_json::json!({
+            "required": true,
+            "content": { "application/json": { "schema": request } }
+        });
+        operation_json["responses"]["422"] = response_ref("Unprocessable");
+    }
+    operation_json["security"] = if public {
+        serde_json::json!([])
+    } else {
+        serde_json::json!([{ "bearer": [] }])
+    };
+    if path.starts_with("/admin") {
+        operation_json["description"] = Value::from("Only for the administrators.");
+    }
+    operation_json
+}
+
+///
+/// /account/<id> becomes /account/{id}; the mount point of "/" routes has no trailing slash.
+fn path(uri: &str) -> String {
+    let path = uri.replace('<', "{").replace('>', "}");
+    if path.len() > 1 && path.ends_with('/') {
+        path[..path.len() - 1].to_string()
+    } else {
+        path
+    }
+}
+
+fn tag(path: &str) -> String {
+    match path.split('/').nth(1) {
+        Some("admin") => "admin".to_string(),
+        Some(".well-known") => "auth".to_string(),
+        Some("openapi.json") | Some("docs") => "docs".to_string(),
+        Some("health") | Some("ready") | Some("metrics") => "monitoring".to_string(),
+        Some(segment) => segment.to_string(),
+        None => "default".to_string()
+    }
+}
+
+// the parameters are ids, except the few listed here
+fn param_type(name: &str) -> &'static str {
+    match name {
+        "entity" | "note" | "at" | "date" | "from" | "to" | "interval" | "group_by" => "string",
+        "dry_run" | "running_balance" => "boolean",
+        "amount" => "number",
+        _ => "integer"
+    }
+}
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    serde_json::to_value(gen.subschema_for::<T>()).unwrap_or_else(|_| json_object())
+}
+
+fn json_object() -> Value {
+    serde_json::json!({ "type": "object" })
+}
+
+fn status() -> Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": { "status": { "type": "string" } }
+    })
+}
+
+fn token() -> Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": { "token": { "type": "string" } }
+    })
+}
+
+fn access_token() -> Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "id": { "type": "integer" },
+            "name": { "type": "string" },
+            "scopes": { "type": "array", "items": { "type": "string" } },
+            "creation_date": { "type": "string", "format": "date-time" },
+            "expiration_date": { "type": "string", "format": "date-time", "nullable": true },
+            "token": { "type": "string" }
+        }
+    })
+}
+
+fn deletion_date() -> Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": { "deletion_date": { "type": "string", "format": "date-time" } }
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    serde_json::json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+    })
+}
+
+fn response_ref(name: &str) -> Value {
+    serde_json::json!({ "$ref": format!("#/components/responses/{}", name) })
+}
@@ -0,0 +1,91 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::ApiError;
+
+///
+/// Apply a JSON Merge Patch (RFC 7386) to an entity: the fields of the patch replace the ones
+/// of the entity, a null clears them and the missing ones are left as they are.
+/// The result is the JSON of a full form, read by the caller with `form`.
+pub fn apply<T: Serialize>(entity: &T, patch: &Value) -> Result<String, ApiError> {
+    if !patch.is_object() {
+        return Err(ApiError::bad_request("The patch must be a JSON object"));
+    }
+    let mut target = serde_json::to_value(entity)
+        .map_err(|e| {
+            error!("Can not serialize the entity to patch caused by {}", e);
+            ApiError::internal()
+        })?;
+    merge(&mut target, patch);
+    Ok(target.to_string())
+}
+
+///
+/// The body of a full update (PUT) as the JSON of a form: the `kept` fields that it leaves out
+/// keep the value of the entity instead of taking the default of the form.
+pub fn replace<T: Serialize>(entity: &T, body: &Value, kept: &[&str]) -> Result<String, ApiError> {
+    let fields = match body {
+        Value::Object(fields) => fields,
+        _ => return Err(ApiError::bad_request("The body must be a JSON object"))
+    };
+    let current = serde_json::to_value(entity)
+        .map_err(|e| {
+            error!("Can not serialize the entity to update caused by {}", e);
+            ApiError::internal()
+        })?;
+    let mut fields = fields.clone();
+    for key in kept {
+        if !fields.contains_key(*key) {
+            fields.insert(key.to_string(), current[*key].clone());
+        }
+    }
+    Ok(Value::Object(fields).to_string())
+}
+
+///
+/// Read a form from the JSON of a patch or of a bulk item, a field of the wrong type is 400.
+pub fn form<'a, F: Deserialize<'a>>(json: &'a str) -> Result<F, ApiError> {
+    serde_json::from_str(json)
+        .map_err(|e| {
+            warn!("Can not read the form caused by {}", e);
+            ApiError::bad_request(&format!("The fields are not valid: {}", e))
+        })
+}
+
+// #################################################################################################
+
+fn merge(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(fields) => {
+            if !target.is_object() {
+                *target = Value::Object(serde_json::Map::new());
+            }
+            let map = target.as_object_mut().unwrap();
+            for (key, value) in fields {
+                if value.is_null() {
+                    map.remove(key);
+                } else {
+                    merge(map.entry(key.as_str()).or_insert(Value::Null), value);
+                }
+            }
+        },
+        _ => *target = patch.clone()
+    }
+}
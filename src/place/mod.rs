@@ -17,96 +17,131 @@
 
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
+use serde_json::Value;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
+use crate::patch;
 use crate::base_model::BaseModel;
-use crate::base_controller::BaseController;
+use crate::base_controller::{BaseController, IfMatch, Tagged};
 use crate::place::model::{Place, PlaceForm};
+use crate::idempotency::{IdempotencyKey, Replayable};
 use crate::user::model::User;
 
 pub mod model;
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<PlaceForm>, user: User) -> Result<Json<Place>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<PlaceForm>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_PLACE_REQUEST");
     let mut place = json.into_inner();
     place.id_user = Some(user.id);
-    Place::create(&place, &conn)
-        .map(|p| {
-            info!("place create successfully {}", p.id);
-            Json(p)
-        })
-        .map_err(|e| {
-            error!("Can not create place caused by {}", e);
-            Status::InternalServerError
-        })
+    key.once(&user, "place", &place, &conn, || {
+        place.validate(&user, &conn)?;
+        let place = Place::create(&place, &conn)
+            .map(|p| {
+                info!("place create successfully {}", p.id);
+                p
+            })
+            .map_err(|e| {
+                error!("Can not create place caused by {}", e);
+                ApiError::internal()
+            })?;
+        Replayable::json(Status::Ok, &place)
+    })
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Place>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Tagged<Json<Place>>, ApiError> {
     debug!("READ_ONE_PLACE_REQUEST");
     let place = get_by_id(id, &conn)?;
     // a user can access his own place or the default ones
     if place.id_user.is_some() {
         check_property(&place, &user)?;
     }
-    Ok(Json(place))
+    let version = place.version;
+    Ok(Tagged(Json(place), version))
 }
 
 #[get("/user")]
-pub fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Place>>, Custom<String>> {
+pub fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Place>>, ApiError> {
     debug!("READ_BY_USER_PLACE_REQUEST");
     let result = Place::read_by_user(&user, &conn);
     Place::unpack(result)
 }
 
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i64, json: Json<PlaceForm>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i64, json: Json<PlaceForm>, user: User,
+          if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("UPDATE_PLACE_REQUEST");
-    let mut place = get_by_id(id, &conn)?;
-    place.id_user = Some(user.id);
+    let place = get_by_id(id, &conn)?;
     // check if place can be updated
     check_property(&place, &user)?;
-    let result = Place::update(&place, &json.into_inner(), &conn);
-    Place::finalize_update_delete(result)
+    if_match.check(place.version)?;
+    let mut form = json.into_inner();
+    // the place can not be given to another user
+    form.id_user = Some(user.id);
+    form.validate(&user, &conn)?;
+    let result = Place::update(&place, &form, &conn);
+    Place::finalize_versioned(result)
+}
+
+#[patch("/<id>", data = "<json>", format = "application/json")]
+fn patch(conn: MoneyManagerDB, id: i64, json: Json<Value>, user: User,
+         if_match: IfMatch) -> Result<Status, ApiError> {
+    debug!("PATCH_PLACE_REQUEST");
+    let place = get_by_id(id, &conn)?;
+    check_property(&place, &user)?;
+    if_match.check(place.version)?;
+    // only the fields of the patch change, the others are the current ones
+    let merged = patch::apply(&place, &json)?;
+    let mut form = patch::form::<PlaceForm>(&merged)?;
+    // the place can not be given to another user
+    form.id_user = Some(user.id);
+    form.validate(&user, &conn)?;
+    let result = Place::update(&place, &form, &conn);
+    Place::finalize_versioned(result)
 }
 
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i64, user: User, if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("DELETE_PLACE_REQUEST");
     let place = get_by_id(id, &conn)?;
     // check if place can be deleted
     check_property(&place, &user)?;
+    if_match.check(place.version)?;
     let result = Place::delete(&place, &conn);
+    Place::finalize_versioned(result)
+}
+
+#[post("/<id>/restore")]
+fn restore(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("RESTORE_PLACE_REQUEST");
+    let place = Place::read_deleted_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("place", e))?;
+    check_property(&place, &user)?;
+    let result = Place::restore(&place, &conn);
     Place::finalize_update_delete(result)
 }
 
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/place", routes![read_one, read_by_user, create, update, delete])
+    rocket.mount("/place", routes![read_one, read_by_user, create, update, patch, delete, restore])
 }
 
 // #################################################################################################
 
-fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Place, Status> {
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Place, ApiError> {
     Place::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read place: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("place", e))
 }
 
-fn check_property(place: &Place, user: &User) -> Result<(), Status> {
+fn check_property(place: &Place, user: &User) -> Result<(), ApiError> {
     if place.id_user.is_none() || place.id_user.unwrap() != user.id {
         warn!("The user attempts to access place that does not belong to it!");
-        Err(Status::Forbidden)
+        Err(ApiError::forbidden("place"))
     } else {
         Ok(())
     }
@@ -17,15 +17,19 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 use crate::schema::place;
 use crate::user::model::User;
-use crate::database::MoneyManagerDB;
+use crate::validation::{Validate, Validator};
+use crate::error::ApiError;
 
 #[table_name = "place"]
 #[belongs_to(User, foreign_key = "id_user")]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,JsonSchema)]
 pub struct Place {
     pub id: i64,
     pub name: String,
@@ -35,12 +39,15 @@ pub struct Place {
     pub website: Option<String>,
     pub phone: Option<String>,
     pub note: Option<String>,
-    pub id_user: Option<i64>
+    pub id_user: Option<i64>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32
 }
 
-// only for insert and update
+// only for insert and update, the update replaces the whole row: a missing field becomes null
 #[table_name = "place"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+#[derive(Debug,Serialize,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct PlaceForm<'a> {
     pub name: &'a str,
     pub address: Option<String>,
@@ -53,35 +60,78 @@ pub struct PlaceForm<'a> {
 }
 
 impl Place {
-    pub fn create(form: &PlaceForm, conn: &MoneyManagerDB) -> QueryResult<Place> {
+    pub fn create(form: &PlaceForm, conn: &PgConnection) -> QueryResult<Place> {
         diesel::insert_into(place::table)
             .values(form)
-            .get_result::<Place>(&*(*conn))
+            .get_result::<Place>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Place>> {
+        place::table.filter(place::deleted_at.is_null())
+            .load::<Place>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Place>> {
-        place::table.load::<Place>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Place> {
+        place::table.find(id)
+            .filter(place::deleted_at.is_null())
+            .first::<Place>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Place> {
-        place::table.find(id).first::<Place>(&*(*conn))
+    pub fn read_deleted_by_id(id: i64, conn: &PgConnection) -> QueryResult<Place> {
+        place::table.find(id)
+            .filter(place::deleted_at.is_not_null())
+            .first::<Place>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_user(user: &User, conn: &MoneyManagerDB) -> QueryResult<Vec<Place>> {
+    ///
+    /// Also in the trash: the transactions keep referring to it.
+    pub fn read_any_by_id(id: i64, conn: &PgConnection) -> QueryResult<Place> {
+        place::table.find(id).first::<Place>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<Place>> {
         place::table
             .filter(place::id_user.eq(user.id))
-            .load::<Place>(&*(*conn))
+            .filter(place::deleted_at.is_null())
+            .load::<Place>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(place: &Place, form: &PlaceForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::update(place)
-            .set(form)
-            .execute(&*(*conn))
+    ///
+    /// Only the version that was read is updated, no row means that it has changed in the meantime.
+    pub fn update(place: &Place, form: &PlaceForm, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(place::table.find(place.id).filter(place::version.eq(place.version)))
+            .set((form, place::version.eq(place.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(place: &Place, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::delete(place)
-            .execute(&*(*conn))
+    ///
+    /// Move the place to the trash.
+    pub fn delete(place: &Place, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(place::table.find(place.id).filter(place::version.eq(place.version)))
+            .set((place::deleted_at.eq(Utc::now()), place::version.eq(place.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
+    pub fn restore(place: &Place, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(place)
+            .set((place::deleted_at.eq(None::<DateTime<Utc>>), place::version.eq(place.version + 1)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+impl<'a> Validate for PlaceForm<'a> {
+    fn validate(&self, _user: &User, _conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("name", self.name, 64);
+        v.max_length("address", self.address.as_ref().map(|s| s.as_str()), 128);
+        v.max_length("country", self.country.as_ref().map(|s| s.as_str()), 64);
+        v.max_length("email", self.email.as_ref().map(|s| s.as_str()), 255);
+        v.email("email", self.email.as_ref().map(|s| s.as_str()));
+        v.max_length("website", self.website.as_ref().map(|s| s.as_str()), 128);
+        v.url("website", self.website.as_ref().map(|s| s.as_str()));
+        v.max_length("phone", self.phone.as_ref().map(|s| s.as_str()), 16);
+        v.max_length("note", self.note.as_ref().map(|s| s.as_str()), 255);
+        v.finish()
+    }
 }
@@ -0,0 +1,134 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::Connection;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use chrono::{DateTime, Utc};
+use rocket_contrib::json::Json;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validator;
+use crate::base_model::BaseModel;
+use crate::reconciliation::model::{Reconciliation, ReconciliationForm, NewReconciliation};
+use crate::account::balance;
+use crate::audit::model::AuditLog;
+use crate::user::model::User;
+use crate::account;
+
+pub mod model;
+
+///
+/// Compare the cleared movements of the account with the statement of the bank and record it:
+/// without a difference the cleared movements up to the statement date are locked.
+#[post("/<id>/reconcile", data = "<json>", format = "application/json")]
+fn reconcile(conn: MoneyManagerDB, id: i64, json: Json<ReconciliationForm>,
+             user: User) -> Result<Json<Reconciliation>, ApiError> {
+    debug!("RECONCILE_ACCOUNT_REQUEST");
+    let account = account::get_and_check(id, &user, &conn)?;
+    let form = json.into_inner();
+    let last = Reconciliation::read_last_reconciled(&account, &conn)
+        .map_err(|e| ApiError::from_db("reconciliation", e))?;
+    let mut v = Validator::new();
+    if let Some(last) = last {
+        v.check(form.statement_date >= last.statement_date, "statement_date",
+                "must not be before the last reconciled statement");
+    }
+    v.finish()?;
+    let result = conn.transaction::<Reconciliation, Error, _>(|| {
+        let cleared_balance = balance::cleared(&account, form.statement_date, &conn)?;
+        let new = NewReconciliation {
+            id_account: account.id,
+            statement_date: form.statement_date,
+            statement_balance: form.statement_balance,
+            cleared_balance,
+            difference: cents(form.statement_balance - cleared_balance)
+        };
+        let mut reconciliation = Reconciliation::create(&new, &conn)?;
+        if reconciliation.difference.abs() < f64::EPSILON {
+            reconciliation = Reconciliation::lock(&reconciliation, &conn)?;
+        }
+        AuditLog::created(&user, &reconciliation, &conn)?;
+        Ok(reconciliation)
+    });
+    result
+        .map(|r| {
+            info!("account {} reconciled at {}: difference {}, {} locked", account.id, r.statement_date,
+                  r.difference, r.locked);
+            Json(r)
+        })
+        .map_err(|e| {
+            error!("Can not reconcile account {} caused by {}", account.id, e);
+            ApiError::internal()
+        })
+}
+
+#[get("/account/<id>")]
+fn read_by_account(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<Reconciliation>>, ApiError> {
+    debug!("READ_BY_ACCOUNT_RECONCILIATION_REQUEST");
+    let account = account::get_and_check(id, &user, &conn)?;
+    let result = Reconciliation::read_by_account(&account, &conn);
+    Reconciliation::unpack(result)
+}
+
+///
+///
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/account", routes![reconcile])
+        .mount("/reconciliation", routes![read_by_account])
+}
+
+///
+/// A movement locked by a reconciliation can not be changed or deleted anymore.
+pub fn check_unlocked(entity: &str, id_reconciliation: Option<i64>) -> Result<(), ApiError> {
+    match id_reconciliation {
+        Some(id) => {
+            warn!("The user attempts to change a {} locked by the reconciliation {}", entity, id);
+            Err(ApiError::conflict(&format!("The {} is locked by a reconciliation", entity)))
+        },
+        None => Ok(())
+    }
+}
+
+///
+/// The statement of the last reconciliation already counts the cleared movements up to its date:
+/// a new cleared movement there would make the account differ from the bank.
+pub fn is_open(id_account: i64, data: DateTime<Utc>, conn: &PgConnection) -> Result<bool, ApiError> {
+    let last = Reconciliation::read_last_statement_date(id_account, conn)
+        .map_err(|e| ApiError::from_db("reconciliation", e))?;
+    Ok(last.map_or(true, |last| data > last))
+}
+
+///
+/// The cleared flag of a form must be false for a movement dated up to the last reconciled statement.
+pub fn check_open(field: &str, cleared: bool, id_account: i64, data: DateTime<Utc>,
+                  conn: &PgConnection) -> Result<(), ApiError> {
+    let mut v = Validator::new();
+    if cleared {
+        v.check(is_open(id_account, data, conn)?, field,
+                "must be false for a movement dated up to the last reconciled statement");
+    }
+    v.finish()
+}
+
+// #################################################################################################
+
+// the amounts have two decimals, the sums of floats do not; + 0.0 turns a -0 into 0
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0 + 0.0
+}
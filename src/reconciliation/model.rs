@@ -0,0 +1,163 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::schema::{reconciliation, transaction, giro};
+use crate::account::model::Account;
+use crate::audit::model::Audited;
+
+///
+/// The check of an account against a statement of the bank: the cleared movements up to the date
+/// must give the balance of the statement. When they do, they are locked.
+#[table_name = "reconciliation"]
+#[belongs_to(Account, foreign_key = "id_account")]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,JsonSchema)]
+pub struct Reconciliation {
+    pub id: i64,
+    pub id_account: i64,
+    pub statement_date: DateTime<Utc>,
+    pub statement_balance: f64,
+    /// the initial balance with the cleared movements up to the statement date
+    pub cleared_balance: f64,
+    /// statement balance - cleared balance, zero when reconciled
+    pub difference: f64,
+    /// the number of transactions and giros locked
+    pub locked: i32,
+    pub creation_date: DateTime<Utc>
+}
+
+#[derive(Debug,Serialize,Deserialize,JsonSchema)]
+pub struct ReconciliationForm {
+    pub statement_date: DateTime<Utc>,
+    pub statement_balance: f64
+}
+
+// only for insert, a reconciliation is never updated
+#[table_name = "reconciliation"]
+#[derive(Debug,Insertable)]
+pub struct NewReconciliation {
+    pub id_account: i64,
+    pub statement_date: DateTime<Utc>,
+    pub statement_balance: f64,
+    pub cleared_balance: f64,
+    pub difference: f64
+}
+
+impl Reconciliation {
+    pub fn create(form: &NewReconciliation, conn: &PgConnection) -> QueryResult<Reconciliation> {
+        diesel::insert_into(reconciliation::table)
+            .values(form)
+            .get_result::<Reconciliation>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// The history of the account, the most recent statement first.
+    pub fn read_by_account(account: &Account, conn: &PgConnection) -> QueryResult<Vec<Reconciliation>> {
+        Reconciliation::belonging_to(account)
+            .order((reconciliation::statement_date.desc(), reconciliation::id.desc()))
+            .load::<Reconciliation>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// The last reconciliation that locked the movements of the account, if any.
+    pub fn read_last_reconciled(account: &Account, conn: &PgConnection) -> QueryResult<Option<Reconciliation>> {
+        Reconciliation::belonging_to(account)
+            .filter(reconciliation::difference.eq(0.0))
+            .order((reconciliation::statement_date.desc(), reconciliation::id.desc()))
+            .first::<Reconciliation>(conn)
+            .optional()
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// The statement date of the last reconciliation without a difference, by the id of the account.
+    pub fn read_last_statement_date(id_account: i64, conn: &PgConnection) -> QueryResult<Option<DateTime<Utc>>> {
+        reconciliation::table
+            .filter(reconciliation::id_account.eq(id_account))
+            .filter(reconciliation::difference.eq(0.0))
+            .select(diesel::dsl::max(reconciliation::statement_date))
+            .first::<Option<DateTime<Utc>>>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Lock the cleared movements of the account up to the statement date that are not locked yet;
+    /// the version changes, so an update of a movement read before fails.
+    pub fn lock(reconciliation: &Reconciliation, conn: &PgConnection) -> QueryResult<Reconciliation> {
+        let until = reconciliation.statement_date;
+        let mut n = diesel::update(transaction::table
+            .filter(transaction::id_account.eq(reconciliation.id_account))
+            .filter(transaction::deleted_at.is_null())
+            .filter(transaction::cleared.eq(true))
+            .filter(transaction::id_reconciliation.is_null())
+            .filter(transaction::data.le(until)))
+            .set((transaction::id_reconciliation.eq(reconciliation.id),
+                  transaction::version.eq(transaction::version + 1)))
+            .execute(conn)?;
+        // each side of a giro is locked by the reconciliation of its own account
+        n += diesel::update(giro::table
+            .filter(giro::id_source_account.eq(reconciliation.id_account))
+            .filter(giro::deleted_at.is_null())
+            .filter(giro::cleared_source.eq(true))
+            .filter(giro::id_reconciliation_source.is_null())
+            .filter(giro::data.le(until)))
+            .set((giro::id_reconciliation_source.eq(reconciliation.id), giro::version.eq(giro::version + 1)))
+            .execute(conn)?;
+        n += diesel::update(giro::table
+            .filter(giro::id_destination_account.eq(reconciliation.id_account))
+            .filter(giro::deleted_at.is_null())
+            .filter(giro::cleared_destination.eq(true))
+            .filter(giro::id_reconciliation_destination.is_null())
+            .filter(giro::data.le(until)))
+            .set((giro::id_reconciliation_destination.eq(reconciliation.id), giro::version.eq(giro::version + 1)))
+            .execute(conn)?;
+        diesel::update(reconciliation)
+            .set(reconciliation::locked.eq(n as i32))
+            .get_result::<Reconciliation>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete_by_account(account: &Account, conn: &PgConnection) -> QueryResult<usize> {
+        // the giros that stay with the other account lose the lock of this side
+        let reconciliations = || reconciliation::table
+            .filter(reconciliation::id_account.eq(account.id))
+            .select(reconciliation::id.nullable());
+        diesel::update(giro::table.filter(giro::id_reconciliation_source.eq_any(reconciliations())))
+            .set(giro::id_reconciliation_source.eq(None::<i64>))
+            .execute(conn)?;
+        diesel::update(giro::table.filter(giro::id_reconciliation_destination.eq_any(reconciliations())))
+            .set(giro::id_reconciliation_destination.eq(None::<i64>))
+            .execute(conn)?;
+        diesel::delete(reconciliation::table
+            .filter(reconciliation::id_account.eq(account.id)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+impl Audited for Reconciliation {
+    const ENTITY: &'static str = "reconciliation";
+    fn entity_id(&self) -> i64 {
+        self.id
+    }
+    fn id_account(&self, _conn: &PgConnection) -> QueryResult<i64> {
+        Ok(self.id_account)
+    }
+}
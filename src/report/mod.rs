@@ -0,0 +1,164 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::{NaiveDate, Utc};
+use rocket_contrib::json::Json;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validator;
+use crate::account::model::{Account, AccountType};
+use crate::account::balance;
+use crate::currency::model::{Currency, ExchangeRate};
+use crate::report::net_worth::{Interval, Ledger, NetWorth, Rates};
+use crate::user::model::User;
+
+pub mod net_worth;
+
+// ten years of weeks, a century of months
+const MAX_PERIODS: usize = 1200;
+
+///
+/// The net worth of the user at the end of every period, from the movements of the accounts
+/// (not from the current balances) converted to the base currency with the rate of that day.
+#[get("/net-worth?<from>&<to>&<interval>&<currency>&<group_by>")]
+fn net_worth(conn: MoneyManagerDB, from: Option<String>, to: Option<String>, interval: Option<String>,
+             currency: Option<i16>, group_by: Option<String>, user: User) -> Result<Json<NetWorth>, ApiError> {
+    debug!("NET_WORTH_REPORT_REQUEST");
+    let accounts = Account::read_by_user(&user, &conn)
+        .map_err(|e| ApiError::from_db("account", e))?;
+    let ledgers = accounts.iter()
+        .map(|account| ledger(account, &conn))
+        .collect::<Result<Vec<Ledger>, ApiError>>()?;
+    let mut v = Validator::new();
+    let from = v.date("from", from.as_ref().map(|s| s.as_str()), false)
+        .map(|d| d.naive_utc().date())
+        .or_else(|| ledgers.iter().map(|l| l.opened).min());
+    let to = v.date("to", to.as_ref().map(|s| s.as_str()), false)
+        .map(|d| d.naive_utc().date())
+        .unwrap_or_else(|| Utc::now().naive_utc().date());
+    let interval = match interval {
+        Some(value) => {
+            let interval = Interval::parse(&value);
+            v.check(interval.is_some(), "interval", "must be week, month, quarter or year");
+            interval.unwrap_or(Interval::Month)
+        },
+        None => Interval::Month
+    };
+    if let Some(value) = &group_by {
+        v.check(value == "account_type", "group_by", "must be account_type");
+    }
+    // without a base currency the one of all the accounts, if they share it
+    let id_currency = currency.or_else(|| {
+        let first = accounts.first()?.id_currency;
+        if accounts.iter().all(|a| a.id_currency == first) { Some(first) } else { None }
+    });
+    match id_currency {
+        Some(id) => { v.reference("currency", Currency::read_by_id(id, &conn))?; },
+        None if accounts.is_empty() => v.error("currency", "is required, there are no accounts"),
+        None => v.error("currency", "is required, the accounts have different currencies")
+    }
+    let periods = from.map(|from| interval.periods(from, to)).unwrap_or_default();
+    v.check(from.map_or(true, |from| from <= to), "from", "must not be after to");
+    v.check(periods.len() <= MAX_PERIODS, "from", &format!("must give at most {} periods", MAX_PERIODS));
+    v.finish()?;
+    let id_currency = id_currency.ok_or_else(ApiError::internal)?;
+
+    let currencies: BTreeSet<i16> = ledgers.iter()
+        .map(|l| l.id_currency)
+        .filter(|id| *id != id_currency)
+        .collect();
+    let mut rates = HashMap::new();
+    for id in currencies {
+        rates.insert(id, read_rates(id, id_currency, &conn)?);
+    }
+    let types = match group_by {
+        Some(_) => Some(AccountType::read(&conn)
+            .map_err(|e| ApiError::from_db("account type", e))?
+            .into_iter()
+            .map(|t| (t.id, t.type_))
+            .collect::<BTreeMap<i32, String>>()),
+        None => None
+    };
+
+    let balances = net_worth::balances(&ledgers, &periods);
+    let mut result = Vec::with_capacity(periods.len());
+    for (index, (from, to)) in periods.iter().enumerate() {
+        let mut converted = Vec::new();
+        for (ledger, balances) in ledgers.iter().zip(&balances) {
+            let balance = match balances[index] {
+                Some(balance) => balance,
+                None => continue
+            };
+            let rate = match rates.get(&ledger.id_currency) {
+                Some(rates) => rates.at(*to),
+                None => Some(1.0)
+            };
+            match rate {
+                Some(rate) => converted.push((ledger.id_account_type, balance * rate)),
+                // a zero is zero in every currency
+                None if balance.abs() < f64::EPSILON => {},
+                None => return Err(missing_rate(ledger.id_currency, id_currency, *to, &conn))
+            }
+        }
+        result.push(net_worth::period(*from, *to, &converted, types.as_ref()));
+    }
+    Ok(Json(NetWorth { id_currency, interval, periods: result }))
+}
+
+///
+///
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/report", routes![net_worth])
+}
+
+// #################################################################################################
+
+fn ledger(account: &Account, conn: &MoneyManagerDB) -> Result<Ledger, ApiError> {
+    let movements: Vec<(NaiveDate, f64)> = balance::movements(account, conn)
+        .map_err(|e| ApiError::from_db("account", e))?
+        .into_iter()
+        .map(|(data, change)| (data.naive_utc().date(), change))
+        .collect();
+    // a movement dated before the creation of the account, e.g. an imported one, opens it
+    let created = account.creation_date.naive_utc().date();
+    let opened = movements.first().map_or(created, |(data, _)| created.min(*data));
+    Ok(Ledger {
+        id_account_type: account.id_account_type,
+        id_currency: account.id_currency,
+        opened,
+        initial_balance: account.initial_balance,
+        movements
+    })
+}
+
+fn read_rates(id_currency: i16, id_base_currency: i16, conn: &MoneyManagerDB) -> Result<Rates, ApiError> {
+    let direct = ExchangeRate::read_pair(id_currency, id_base_currency, conn)
+        .map_err(|e| ApiError::from_db("exchange rate", e))?;
+    let inverse = ExchangeRate::read_pair(id_base_currency, id_currency, conn)
+        .map_err(|e| ApiError::from_db("exchange rate", e))?;
+    Ok(Rates::new(direct, inverse))
+}
+
+fn missing_rate(id_currency: i16, id_base_currency: i16, day: NaiveDate, conn: &MoneyManagerDB) -> ApiError {
+    let code = |id: i16| Currency::read_by_id(id, conn).map(|c| c.code).unwrap_or_else(|_| id.to_string());
+    let mut v = Validator::new();
+    v.error("currency", &format!("there is no exchange rate from {} to {} up to {}",
+                                 code(id_currency), code(id_base_currency), day));
+    v.finish().err().unwrap_or_else(ApiError::internal)
+}
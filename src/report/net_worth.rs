@@ -0,0 +1,189 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::BTreeMap;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::currency::model::ExchangeRate;
+
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Interval {
+    Week,
+    Month,
+    Quarter,
+    Year
+}
+
+///
+/// The total of the accounts of a type at the end of the period.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct TypeTotal {
+    pub id_account_type: i32,
+    #[serde(rename="type")]
+    pub type_: String,
+    pub total: f64
+}
+
+///
+/// The net worth at the end of the period, in the base currency.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct Period {
+    pub from: NaiveDate,
+    /// included
+    pub to: NaiveDate,
+    pub total: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_types: Option<Vec<TypeTotal>>
+}
+
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct NetWorth {
+    pub id_currency: i16,
+    pub interval: Interval,
+    pub periods: Vec<Period>
+}
+
+///
+/// What the report needs of an account: its balance is the initial one plus the movements.
+pub struct Ledger {
+    pub id_account_type: i32,
+    pub id_currency: i16,
+    pub opened: NaiveDate,
+    pub initial_balance: f64,
+    /// the day of the movement and how it changes the balance, in the order of the date
+    pub movements: Vec<(NaiveDate, f64)>
+}
+
+impl Interval {
+    pub fn parse(value: &str) -> Option<Interval> {
+        match value {
+            "week" => Some(Interval::Week),
+            "month" => Some(Interval::Month),
+            "quarter" => Some(Interval::Quarter),
+            "year" => Some(Interval::Year),
+            _ => None
+        }
+    }
+
+    ///
+    /// The first day of the period that contains the day, the weeks start on monday.
+    fn start(self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Interval::Week => day - Duration::days(i64::from(day.weekday().num_days_from_monday())),
+            Interval::Month => day.with_day(1).unwrap_or(day),
+            Interval::Quarter => day.with_day(1).and_then(|d| d.with_month(day.month0() / 3 * 3 + 1)).unwrap_or(day),
+            Interval::Year => day.with_ordinal(1).unwrap_or(day)
+        }
+    }
+
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Interval::Week => start + Duration::days(7),
+            Interval::Month => add_months(start, 1),
+            Interval::Quarter => add_months(start, 3),
+            Interval::Year => add_months(start, 12)
+        }
+    }
+
+    ///
+    /// The periods that cover the days from from to to, the last one ends at to.
+    pub fn periods(self, from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+        let mut periods = Vec::new();
+        let mut start = self.start(from);
+        while start <= to {
+            let next = self.next(start);
+            periods.push((start, (next - Duration::days(1)).min(to)));
+            start = next;
+        }
+        periods
+    }
+}
+
+///
+/// The rates to convert a currency to the base one, the oldest first: a rate from the base
+/// currency counts inverted, the direct one wins on the same day.
+pub struct Rates {
+    rates: Vec<(NaiveDate, f64)>
+}
+
+impl Rates {
+    pub fn new(direct: Vec<ExchangeRate>, inverse: Vec<ExchangeRate>) -> Rates {
+        let mut rates: Vec<(NaiveDate, f64)> = inverse.into_iter().map(|r| (r.date, 1.0 / r.rate))
+            .chain(direct.into_iter().map(|r| (r.date, r.rate)))
+            .collect();
+        // stable, the direct ones stay after the inverse ones of the same day
+        rates.sort_by_key(|r| r.0);
+        Rates { rates }
+    }
+
+    ///
+    /// The rate of the last day up to the given one.
+    pub fn at(&self, day: NaiveDate) -> Option<f64> {
+        self.rates.iter().rev().find(|r| r.0 <= day).map(|r| r.1)
+    }
+}
+
+///
+/// The balance of each ledger at the end of every period, None for the accounts not opened yet.
+pub fn balances(ledgers: &[Ledger], periods: &[(NaiveDate, NaiveDate)]) -> Vec<Vec<Option<f64>>> {
+    ledgers.iter().map(|ledger| {
+        let mut balance = ledger.initial_balance;
+        let mut movements = ledger.movements.iter().peekable();
+        periods.iter().map(|(_, to)| {
+            while let Some(&&(day, change)) = movements.peek() {
+                if day > *to {
+                    break;
+                }
+                movements.next();
+                balance += change;
+            }
+            if ledger.opened <= *to { Some(balance) } else { None }
+        }).collect()
+    }).collect()
+}
+
+///
+/// Sum the converted balances of the period, by account type when types is given.
+pub fn period(from: NaiveDate, to: NaiveDate, converted: &[(i32, f64)],
+              types: Option<&BTreeMap<i32, String>>) -> Period {
+    let total = converted.iter().map(|(_, balance)| balance).sum();
+    let account_types = types.map(|types| {
+        let mut totals: BTreeMap<i32, f64> = BTreeMap::new();
+        for (id_account_type, balance) in converted {
+            *totals.entry(*id_account_type).or_insert(0.0) += balance;
+        }
+        totals.into_iter()
+            .map(|(id_account_type, total)| TypeTotal {
+                id_account_type,
+                type_: types.get(&id_account_type).cloned().unwrap_or_default(),
+                total
+            })
+            .collect()
+    });
+    Period { from, to, total, account_types }
+}
+
+// #################################################################################################
+
+// from the first day of a month
+fn add_months(day: NaiveDate, months: u32) -> NaiveDate {
+    let month0 = day.month0() + months;
+    NaiveDate::from_ymd_opt(day.year() + (month0 / 12) as i32, month0 % 12 + 1, 1).unwrap_or(day)
+}
@@ -0,0 +1,216 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use diesel::QueryResult;
+use regex::Regex;
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::rule::model::{self, Rule};
+use crate::transaction::model::{Transaction, TransactionForm, TransactionDetail};
+use crate::causal::model::Causal;
+use crate::place::model::Place;
+use crate::detail::model::Detail;
+use crate::audit::model::AuditLog;
+use crate::user::model::User;
+
+///
+/// A transaction changed by a rule, or that would be changed by the dry run.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct RuleMatch {
+    pub transaction: Transaction,
+    pub added_details: Vec<i64>
+}
+
+///
+/// A rule ready to be matched, with its regex compiled once.
+pub struct Matcher {
+    pub rule: Rule,
+    regex: Option<Regex>
+}
+
+impl Matcher {
+    ///
+    /// None if the regex can not be compiled, it has been checked when the rule was saved.
+    pub fn new(rule: Rule) -> Option<Matcher> {
+        let regex = match rule.note_regex {
+            Some(ref pattern) => match model::note_regex(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("The regex of the rule {} is not valid: {}", rule.id, e);
+                    return None;
+                }
+            },
+            None => None
+        };
+        Some(Matcher { rule, regex })
+    }
+
+    pub fn matches(&self, id_account: i64, id_beneficiary: Option<i64>, amount: f64, note: Option<&str>) -> bool {
+        let rule = &self.rule;
+        rule.id_account.map_or(true, |id| id == id_account)
+            && rule.id_beneficiary.map_or(true, |id| Some(id) == id_beneficiary)
+            && rule.min_amount.map_or(true, |min| amount >= min)
+            && rule.max_amount.map_or(true, |max| amount <= max)
+            && self.regex.as_ref().map_or(true, |regex| note.map_or(false, |note| regex.is_match(note)))
+    }
+}
+
+///
+/// What the matching rules change: the first rule that sets a field wins, the details add up.
+/// The causals and places in the trash and the deleted details are skipped.
+#[derive(Debug,Default)]
+pub struct Actions {
+    pub id_causal: Option<i64>,
+    pub id_place: Option<i64>,
+    pub id_transaction_type: Option<i32>,
+    pub details: Vec<i64>
+}
+
+impl Actions {
+    pub fn of<'a, I: IntoIterator<Item = &'a Rule>>(rules: I, conn: &PgConnection) -> Actions {
+        let mut actions = Actions::default();
+        for rule in rules {
+            if actions.id_causal.is_none() {
+                actions.id_causal = rule.set_causal.filter(|id| Causal::read_by_id(*id, conn).is_ok());
+            }
+            if actions.id_place.is_none() {
+                actions.id_place = rule.set_place.filter(|id| Place::read_by_id(*id, conn).is_ok());
+            }
+            if actions.id_transaction_type.is_none() {
+                actions.id_transaction_type = rule.set_transaction_type;
+            }
+            for id_detail in &rule.add_details {
+                if !actions.details.contains(id_detail) && Detail::read_by_id(*id_detail, conn).is_ok() {
+                    actions.details.push(*id_detail);
+                }
+            }
+        }
+        actions
+    }
+
+    pub fn apply_to_form(&self, form: &mut TransactionForm) {
+        if let Some(id_causal) = self.id_causal {
+            form.id_causal = id_causal;
+        }
+        if let Some(id_place) = self.id_place {
+            form.id_place = Some(id_place);
+        }
+        if let Some(id_transaction_type) = self.id_transaction_type {
+            form.id_transaction_type = id_transaction_type;
+        }
+    }
+
+    ///
+    /// Link the details to a new transaction.
+    pub fn link_details(&self, transaction: &Transaction, user: &User, conn: &PgConnection) -> QueryResult<()> {
+        add_details(transaction.id, &self.details, user, conn)
+    }
+
+    fn changes(&self, t: &Transaction) -> bool {
+        self.id_causal.map_or(false, |id| id != t.id_causal)
+            || self.id_place.map_or(false, |id| Some(id) != t.id_place)
+            || self.id_transaction_type.map_or(false, |id| id != t.id_transaction_type)
+    }
+
+    fn missing_details(&self, t: &Transaction, conn: &PgConnection) -> QueryResult<Vec<i64>> {
+        let linked: Vec<i64> = TransactionDetail::read_by_transaction(conn, t)?.iter()
+            .map(|td| td.id_detail)
+            .collect();
+        Ok(self.details.iter().filter(|id| !linked.contains(id)).cloned().collect())
+    }
+}
+
+///
+/// The enabled rules of the user that match the new transaction are applied to its form;
+/// the details are linked by the caller once the transaction exists.
+pub fn apply_to_new(form: &mut TransactionForm, user: &User, conn: &PgConnection) -> QueryResult<Actions> {
+    let matching: Vec<Rule> = Rule::read_enabled_by_user(user, conn)?.into_iter()
+        .filter_map(Matcher::new)
+        .filter(|m| m.matches(form.id_account, form.id_beneficiary, form.amount, form.note))
+        .map(|m| m.rule)
+        .collect();
+    let actions = Actions::of(&matching, conn);
+    actions.apply_to_form(form);
+    Ok(actions)
+}
+
+///
+/// Apply the actions to a transaction that matches the rule, None if it is already as the rule wants.
+/// The dry run changes nothing and returns the transaction as it would be.
+pub fn apply_to_existing(transaction: Transaction, actions: &Actions, dry_run: bool, user: &User,
+                         conn: &PgConnection) -> QueryResult<Option<RuleMatch>> {
+    // a reconciled transaction is locked
+    if transaction.id_reconciliation.is_some() {
+        return Ok(None);
+    }
+    let changes = actions.changes(&transaction);
+    let added_details = actions.missing_details(&transaction, conn)?;
+    if !changes && added_details.is_empty() {
+        return Ok(None);
+    }
+    if dry_run {
+        let mut after = transaction;
+        after.id_causal = actions.id_causal.unwrap_or(after.id_causal);
+        after.id_place = actions.id_place.or(after.id_place);
+        after.id_transaction_type = actions.id_transaction_type.unwrap_or(after.id_transaction_type);
+        return Ok(Some(RuleMatch { transaction: after, added_details }));
+    }
+    if changes {
+        let form = TransactionForm {
+            id_account: transaction.id_account,
+            id_transaction_type: actions.id_transaction_type.unwrap_or(transaction.id_transaction_type),
+            id_place: actions.id_place.or(transaction.id_place),
+            id_beneficiary: transaction.id_beneficiary,
+            note: transaction.note.as_ref().map(|s| s.as_str()),
+            amount: transaction.amount,
+            data: transaction.data,
+            id_currency: transaction.id_currency,
+            expense: transaction.expense,
+            id_causal: actions.id_causal.unwrap_or(transaction.id_causal),
+            cleared: transaction.cleared
+        };
+        // changed in the meantime by someone else, the rule can be applied again
+        if Transaction::update(&transaction, &form, conn)? == 0 {
+            return Ok(None);
+        }
+        let after = Transaction::read_by_id(transaction.id, conn)?;
+        AuditLog::updated(user, &transaction, &after, conn)?;
+    }
+    add_details(transaction.id, &added_details, user, conn)?;
+    let after = Transaction::read_by_id(transaction.id, conn)?;
+    Ok(Some(RuleMatch { transaction: after, added_details }))
+}
+
+// #################################################################################################
+
+fn add_details(id_transaction: i64, details: &[i64], user: &User, conn: &PgConnection) -> QueryResult<()> {
+    for id_detail in details {
+        let td = TransactionDetail {
+            id_detail: *id_detail,
+            id_transaction,
+            amount: None
+        };
+        if !TransactionDetail::create(&td, conn) {
+            return Err(Error::RollbackTransaction);
+        }
+        AuditLog::created(user, &td, conn)?;
+    }
+    Ok(())
+}
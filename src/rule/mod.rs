@@ -0,0 +1,165 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::iter;
+use diesel::Connection;
+use diesel::result::Error;
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
+use crate::base_model::BaseModel;
+use crate::base_controller::BaseController;
+use crate::idempotency::{IdempotencyKey, Replayable};
+use crate::rule::model::{Rule, RuleForm};
+use crate::rule::engine::{Actions, Matcher, RuleMatch};
+use crate::transaction::model::{Transaction, TransactionFilter};
+use crate::user::model::User;
+
+pub mod model;
+pub mod engine;
+
+///
+/// The transactions changed by a rule applied to the past, or that would be changed by the dry run.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct RuleApplication {
+    pub dry_run: bool,
+    pub transactions: Vec<RuleMatch>
+}
+
+#[post("/", data = "<json>", format = "application/json")]
+fn create(conn: MoneyManagerDB, json: Json<RuleForm>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
+    debug!("CREATE_RULE_REQUEST");
+    let mut form = json.into_inner();
+    form.id_user = user.id;
+    key.once(&user, "rule", &form, &conn, || {
+        form.validate(&user, &conn)?;
+        let rule = Rule::create(&form, &conn)
+            .map(|r| {
+                info!("rule create successfully {}", r.id);
+                r
+            })
+            .map_err(|e| {
+                error!("Can not create rule caused by {}", e);
+                ApiError::internal()
+            })?;
+        Replayable::json(Status::Ok, &rule)
+    })
+}
+
+#[get("/<id>")]
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Rule>, ApiError> {
+    debug!("READ_ONE_RULE_REQUEST");
+    let rule = get_by_id(id, &conn)?;
+    check_property(&rule, &user)?;
+    Ok(Json(rule))
+}
+
+#[get("/user")]
+fn read_by_user(conn: MoneyManagerDB, user: User) -> Result<Json<Vec<Rule>>, ApiError> {
+    debug!("READ_BY_USER_RULE_REQUEST");
+    let result = Rule::read_by_user(&user, &conn);
+    Rule::unpack(result)
+}
+
+#[put("/<id>", data = "<json>", format = "application/json")]
+fn update(conn: MoneyManagerDB, id: i64, json: Json<RuleForm>, user: User) -> Result<Status, ApiError> {
+    debug!("UPDATE_RULE_REQUEST");
+    let rule = get_by_id(id, &conn)?;
+    check_property(&rule, &user)?;
+    let mut form = json.into_inner();
+    // the rule can not be given to another user
+    form.id_user = user.id;
+    form.validate(&user, &conn)?;
+    let result = Rule::update(&rule, &form, &conn);
+    Rule::finalize_update_delete(result)
+}
+
+#[delete("/<id>")]
+fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("DELETE_RULE_REQUEST");
+    let rule = get_by_id(id, &conn)?;
+    check_property(&rule, &user)?;
+    let result = Rule::delete(&rule, &conn);
+    Rule::finalize_update_delete(result)
+}
+
+///
+/// Apply the rule to the transactions of the user that already exist, also if it is disabled.
+#[post("/<id>/apply?<dry_run>")]
+fn apply(conn: MoneyManagerDB, id: i64, dry_run: Option<bool>, user: User) -> Result<Json<RuleApplication>, ApiError> {
+    debug!("APPLY_RULE_REQUEST");
+    let rule = get_by_id(id, &conn)?;
+    check_property(&rule, &user)?;
+    let dry_run = dry_run.unwrap_or(false);
+    let matcher = Matcher::new(rule).ok_or_else(ApiError::internal)?;
+    let filter = TransactionFilter {
+        id_account: matcher.rule.id_account,
+        ..Default::default()
+    };
+    let candidates = Transaction::search(&user, &filter, i64::max_value(), &conn)
+        .map_err(|e| ApiError::from_db("transaction", e))?;
+    let actions = Actions::of(iter::once(&matcher.rule), &conn);
+    let result = conn.transaction::<Vec<RuleMatch>, Error, _>(|| {
+        let mut transactions = Vec::new();
+        for t in candidates {
+            if !matcher.matches(t.id_account, t.id_beneficiary, t.amount, t.note.as_ref().map(|s| s.as_str())) {
+                continue;
+            }
+            if let Some(m) = engine::apply_to_existing(t, &actions, dry_run, &user, &conn)? {
+                transactions.push(m);
+            }
+        }
+        Ok(transactions)
+    });
+    result
+        .map(|transactions| {
+            info!("rule {} applied to {} transactions (dry run: {})", matcher.rule.id, transactions.len(), dry_run);
+            Json(RuleApplication { dry_run, transactions })
+        })
+        .map_err(|e| {
+            error!("Can not apply rule {} caused by {}", matcher.rule.id, e);
+            ApiError::internal()
+        })
+}
+
+///
+///
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/rule", routes![read_one, read_by_user, create, update, delete, apply])
+}
+
+// #################################################################################################
+
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Rule, ApiError> {
+    Rule::read_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("rule", e))
+}
+
+fn check_property(rule: &Rule, user: &User) -> Result<(), ApiError> {
+    if rule.id_user != user.id {
+        warn!("The user attempts to access rule that does not belong to it!");
+        Err(ApiError::forbidden("rule"))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use regex::{Regex, RegexBuilder};
+
+use crate::schema::rule;
+use crate::account::model::Account;
+use crate::causal::model::Causal;
+use crate::place::model::Place;
+use crate::detail::model::Detail;
+use crate::transaction::model::TransactionType;
+use crate::user::model::User;
+use crate::validation::{Validate, Validator};
+use crate::error::ApiError;
+
+///
+/// A rule of the user: when a transaction matches all the conditions, the actions are applied.
+#[table_name = "rule"]
+#[belongs_to(User, foreign_key = "id_user")]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,JsonSchema)]
+pub struct Rule {
+    pub id: i64,
+    pub id_user: i64,
+    pub name: String,
+    pub priority: i32,
+    pub enabled: bool,
+    pub note_regex: Option<String>,
+    pub min_amount: Option<f64>,
+    pub max_amount: Option<f64>,
+    pub id_account: Option<i64>,
+    pub id_beneficiary: Option<i64>,
+    pub set_causal: Option<i64>,
+    pub set_place: Option<i64>,
+    pub set_transaction_type: Option<i32>,
+    pub add_details: Vec<i64>
+}
+
+// only for insert and update, the update replaces the whole row: a missing field becomes null;
+// owned strings, a regex is full of escapes
+#[table_name = "rule"]
+#[changeset_options(treat_none_as_null = "true")]
+#[derive(Debug,Serialize,Deserialize,Insertable,AsChangeset,JsonSchema)]
+pub struct RuleForm {
+    pub name: String,
+    /// the rules are applied from the lowest priority
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// matched anywhere in the note, the case is ignored
+    pub note_regex: Option<String>,
+    pub min_amount: Option<f64>,
+    pub max_amount: Option<f64>,
+    pub id_account: Option<i64>,
+    pub id_beneficiary: Option<i64>,
+    pub set_causal: Option<i64>,
+    pub set_place: Option<i64>,
+    pub set_transaction_type: Option<i32>,
+    #[serde(default)]
+    pub add_details: Vec<i64>,
+    /// the owner, set from the token
+    #[serde(skip_deserializing)]
+    pub id_user: i64
+}
+
+impl Rule {
+    pub fn create(form: &RuleForm, conn: &PgConnection) -> QueryResult<Rule> {
+        diesel::insert_into(rule::table)
+            .values(form)
+            .get_result::<Rule>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Rule> {
+        rule::table.find(id).first::<Rule>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// In the order they are applied.
+    pub fn read_by_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<Rule>> {
+        rule::table
+            .filter(rule::id_user.eq(user.id))
+            .order((rule::priority, rule::id))
+            .load::<Rule>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_enabled_by_user(user: &User, conn: &PgConnection) -> QueryResult<Vec<Rule>> {
+        rule::table
+            .filter(rule::id_user.eq(user.id))
+            .filter(rule::enabled.eq(true))
+            .order((rule::priority, rule::id))
+            .load::<Rule>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn update(rule: &Rule, form: &RuleForm, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(rule)
+            .set(form)
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete(rule: &Rule, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(rule)
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn delete_by_user(user: &User, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::delete(rule::table.filter(rule::id_user.eq(user.id)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+}
+
+///
+/// The regex of the note as it is matched, the case is ignored.
+pub fn note_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(1 << 20)
+        .build()
+}
+
+impl Validate for RuleForm {
+    fn validate(&self, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("name", &self.name, 64);
+        v.max_length("note_regex", self.note_regex.as_ref().map(|s| s.as_str()), 255);
+        if let Some(ref pattern) = self.note_regex {
+            v.check(note_regex(pattern).is_ok(), "note_regex", "is not a valid regular expression");
+        }
+        if let (Some(min), Some(max)) = (self.min_amount, self.max_amount) {
+            v.check(min <= max, "min_amount", "must not be greater than max_amount");
+        }
+        // a rule without conditions would change every transaction
+        v.check(self.note_regex.is_some() || self.min_amount.is_some() || self.max_amount.is_some()
+                    || self.id_account.is_some() || self.id_beneficiary.is_some(),
+                "note_regex", "at least a condition is required");
+        v.check(self.set_causal.is_some() || self.set_place.is_some() || self.set_transaction_type.is_some()
+                    || !self.add_details.is_empty(),
+                "set_causal", "at least an action is required");
+        if let Some(id_account) = self.id_account {
+            if v.reference("id_account", Account::read_by_id(id_account, conn))?.is_some() {
+                v.account(id_account, user, conn)?;
+            }
+        }
+        if let Some(id_beneficiary) = self.id_beneficiary {
+            if v.reference("id_beneficiary", Account::read_by_id(id_beneficiary, conn))?.is_some() {
+                v.account(id_beneficiary, user, conn)?;
+            }
+        }
+        if let Some(id_causal) = self.set_causal {
+            if let Some(causal) = v.reference("set_causal", Causal::read_by_id(id_causal, conn))? {
+                v.owner("causal", causal.id_user, user)?;
+            }
+        }
+        if let Some(id_place) = self.set_place {
+            if let Some(place) = v.reference("set_place", Place::read_by_id(id_place, conn))? {
+                v.owner("place", place.id_user, user)?;
+            }
+        }
+        if let Some(id_transaction_type) = self.set_transaction_type {
+            v.reference("set_transaction_type", TransactionType::read_by_id(id_transaction_type, conn))?;
+        }
+        for id_detail in &self.add_details {
+            if let Some(detail) = v.reference("add_details", Detail::read_by_id(*id_detail, conn))? {
+                v.owner("detail", detail.id_user, user)?;
+            }
+        }
+        v.finish()
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
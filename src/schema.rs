@@ -9,6 +9,21 @@ table! {
         creation_date -> Timestamptz,
         id_account_type -> Int4,
         id_currency -> Int2,
+        deleted_at -> Nullable<Timestamptz>,
+        version -> Int4,
+    }
+}
+
+table! {
+    access_token (id) {
+        id -> Int8,
+        id_user -> Int8,
+        name -> Varchar,
+        token_hash -> Bpchar,
+        scopes -> Varchar,
+        creation_date -> Timestamptz,
+        expiration_date -> Nullable<Timestamptz>,
+        last_used -> Nullable<Timestamptz>,
     }
 }
 
@@ -27,6 +42,20 @@ table! {
     }
 }
 
+table! {
+    audit_log (id) {
+        id -> Int8,
+        id_user -> Int8,
+        entity -> Varchar,
+        entity_id -> Int8,
+        id_account -> Int8,
+        action -> Varchar,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        creation_date -> Timestamptz,
+    }
+}
+
 table! {
     auth (id) {
         id -> Int8,
@@ -35,6 +64,7 @@ table! {
         salt -> Bpchar,
         stored_key -> Bpchar,
         last_login -> Nullable<Timestamptz>,
+        tokens_valid_after -> Nullable<Timestamptz>,
     }
 }
 
@@ -43,6 +73,8 @@ table! {
         id -> Int8,
         description -> Varchar,
         id_user -> Nullable<Int8>,
+        deleted_at -> Nullable<Timestamptz>,
+        version -> Int4,
     }
 }
 
@@ -60,19 +92,57 @@ table! {
         id -> Int8,
         description -> Varchar,
         id_user -> Nullable<Int8>,
+        version -> Int4,
+    }
+}
+
+table! {
+    exchange_rate (id_currency, id_base_currency, date) {
+        id_currency -> Int2,
+        id_base_currency -> Int2,
+        date -> Date,
+        rate -> Float8,
     }
 }
 
 table! {
     giro (id) {
         id -> Int8,
-        id_source_account -> Int8,
-        id_destination_account -> Int8,
+        id_source_account -> Nullable<Int8>,
+        id_destination_account -> Nullable<Int8>,
         data -> Timestamptz,
         note -> Nullable<Varchar>,
         amount -> Float8,
         expense -> Nullable<Float8>,
         id_currency -> Int2,
+        deleted_at -> Nullable<Timestamptz>,
+        version -> Int4,
+        cleared_source -> Bool,
+        id_reconciliation_source -> Nullable<Int8>,
+        cleared_destination -> Bool,
+        id_reconciliation_destination -> Nullable<Int8>,
+    }
+}
+
+table! {
+    idempotency_key (id_user, key) {
+        id_user -> Int8,
+        key -> Varchar,
+        request_hash -> Bpchar,
+        status -> Int2,
+        response -> Nullable<Jsonb>,
+        creation_date -> Timestamptz,
+    }
+}
+
+table! {
+    password_reset (id) {
+        id -> Int8,
+        id_user -> Int8,
+        token_hash -> Bpchar,
+        expiration_date -> Timestamptz,
+        used_date -> Nullable<Timestamptz>,
+        creation_date -> Timestamptz,
     }
 }
 
@@ -87,6 +157,40 @@ table! {
         phone -> Nullable<Varchar>,
         note -> Nullable<Varchar>,
         id_user -> Nullable<Int8>,
+        deleted_at -> Nullable<Timestamptz>,
+        version -> Int4,
+    }
+}
+
+table! {
+    reconciliation (id) {
+        id -> Int8,
+        id_account -> Int8,
+        statement_date -> Timestamptz,
+        statement_balance -> Float8,
+        cleared_balance -> Float8,
+        difference -> Float8,
+        locked -> Int4,
+        creation_date -> Timestamptz,
+    }
+}
+
+table! {
+    rule (id) {
+        id -> Int8,
+        id_user -> Int8,
+        name -> Varchar,
+        priority -> Int4,
+        enabled -> Bool,
+        note_regex -> Nullable<Varchar>,
+        min_amount -> Nullable<Float8>,
+        max_amount -> Nullable<Float8>,
+        id_account -> Nullable<Int8>,
+        id_beneficiary -> Nullable<Int8>,
+        set_causal -> Nullable<Int8>,
+        set_place -> Nullable<Int8>,
+        set_transaction_type -> Nullable<Int4>,
+        add_details -> Array<Int8>,
     }
 }
 
@@ -103,6 +207,11 @@ table! {
         id_currency -> Int2,
         expense -> Nullable<Float8>,
         id_causal -> Int8,
+        deleted_at -> Nullable<Timestamptz>,
+        version -> Int4,
+        cleared -> Bool,
+        id_reconciliation -> Nullable<Int8>,
+        direction -> Int2,
     }
 }
 
@@ -119,6 +228,7 @@ table! {
         id -> Int4,
         #[sql_name = "type"]
         type_ -> Varchar,
+        direction -> Int2,
     }
 }
 
@@ -132,9 +242,13 @@ table! {
         address -> Nullable<Varchar>,
         birthdate -> Nullable<Date>,
         note -> Nullable<Varchar>,
+        role -> Varchar,
+        disabled -> Bool,
+        deletion_date -> Nullable<Timestamptz>,
     }
 }
 
+joinable!(access_token -> user (id_user));
 joinable!(account -> account_type (id_account_type));
 joinable!(account -> currency (id_currency));
 joinable!(account_user -> account (id_account));
@@ -143,23 +257,35 @@ joinable!(auth -> user (id));
 joinable!(causal -> user (id_user));
 joinable!(detail -> user (id_user));
 joinable!(giro -> currency (id_currency));
+joinable!(idempotency_key -> user (id_user));
+joinable!(password_reset -> user (id_user));
 joinable!(place -> user (id_user));
+joinable!(reconciliation -> account (id_account));
+joinable!(rule -> user (id_user));
 joinable!(transaction -> currency (id_currency));
 joinable!(transaction -> place (id_place));
+joinable!(transaction -> reconciliation (id_reconciliation));
 joinable!(transaction -> transaction_type (id_transaction_type));
 joinable!(transaction_detail -> detail (id_detail));
 joinable!(transaction_detail -> transaction (id_transaction));
 
 allow_tables_to_appear_in_same_query!(
+    access_token,
     account,
     account_type,
     account_user,
+    audit_log,
     auth,
     causal,
     currency,
     detail,
+    exchange_rate,
     giro,
+    idempotency_key,
+    password_reset,
     place,
+    reconciliation,
+    rule,
     transaction,
     transaction_detail,
     transaction_type,
@@ -0,0 +1,305 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::env;
+use std::path::Path;
+use rocket::config::{Config, ConfigError, RocketConfig, Value, Table};
+use rocket_contrib::databases::database_config;
+
+const MIN_JWT_KEY_LEN: usize = 32;
+// the origins allowed before they could be configured
+const DEFAULT_CORS_ORIGINS: [&str; 2] = ["http://rippetanks.ddns.net", "http://localhost"];
+const DEFAULT_LOG_CONFIG: &str = "/etc/money-manager/log-config.yml";
+
+enum Kind {
+    Str,
+    Int,
+    List
+}
+
+// every key of Rocket.toml can be replaced by an environment variable
+const OVERRIDES: [(&str, &str, Kind); 15] = [
+    ("MM_JWT_KEY", "jwt_key", Kind::Str),
+    ("MM_JWT_PRIVATE_KEYS", "jwt_private_keys", Kind::List),
+    ("MM_JWT_EXP", "jwt_exp", Kind::Int),
+    ("MM_RESET_EXP", "reset_exp", Kind::Int),
+    ("MM_RESET_URL", "reset_url", Kind::Str),
+    ("MM_DELETION_GRACE_DAYS", "deletion_grace_days", Kind::Int),
+    ("MM_TRASH_RETENTION_DAYS", "trash_retention_days", Kind::Int),
+    ("MM_IDEMPOTENCY_WINDOW_HOURS", "idempotency_window_hours", Kind::Int),
+    ("MM_HOUSEKEEPING_INTERVAL", "housekeeping_interval", Kind::Int),
+    ("MM_MIGRATIONS", "migrations", Kind::Str),
+    ("MM_CORS_ORIGINS", "cors_origins", Kind::List),
+    ("MM_ATTACHMENT_DIR", "attachment_dir", Kind::Str),
+    ("MM_MAILER", "mailer", Kind::Str),
+    ("MM_MAIL_FROM", "mail_from", Kind::Str),
+    ("MM_SENDMAIL_PATH", "sendmail_path", Kind::Str)
+];
+
+///
+/// The configuration of the server, read from Rocket.toml and from the MM_* variables
+/// and checked once at startup.
+#[derive(Debug,Clone)]
+pub struct Settings {
+    pub jwt_key: String,
+    pub jwt_private_keys: Vec<String>,
+    pub jwt_exp: u64,
+    pub reset_exp: u64,
+    pub reset_url: Option<String>,
+    pub deletion_grace_days: i64,
+    pub trash_retention_days: i64,
+    pub idempotency_window_hours: i64,
+    pub housekeeping_interval: u64,
+    pub migrations: String,
+    pub cors_origins: Vec<String>,
+    pub attachment_dir: Option<String>,
+    pub mailer: String,
+    pub mail_from: String,
+    pub sendmail_path: String
+}
+
+impl Settings {
+    ///
+    /// All the errors are reported together, one per line.
+    pub fn from_config(config: &Config) -> Result<Settings, String> {
+        let mut errors = Vec::new();
+        let settings = Settings {
+            jwt_key: string(config, "jwt_key", "", &mut errors),
+            jwt_private_keys: list(config, "jwt_private_keys", &mut errors).unwrap_or_default(),
+            jwt_exp: int(config, "jwt_exp", 900, &mut errors) as u64,
+            reset_exp: int(config, "reset_exp", 3600, &mut errors) as u64,
+            reset_url: optional(config, "reset_url", &mut errors),
+            deletion_grace_days: int(config, "deletion_grace_days", 30, &mut errors),
+            trash_retention_days: int(config, "trash_retention_days", 30, &mut errors),
+            idempotency_window_hours: int(config, "idempotency_window_hours", 24, &mut errors),
+            housekeeping_interval: int(config, "housekeeping_interval", 3600, &mut errors) as u64,
+            migrations: string(config, "migrations", "run", &mut errors),
+            cors_origins: list(config, "cors_origins", &mut errors)
+                .unwrap_or_else(|| DEFAULT_CORS_ORIGINS.iter().map(|o| o.to_string()).collect()),
+            attachment_dir: optional(config, "attachment_dir", &mut errors),
+            mailer: string(config, "mailer", "log", &mut errors),
+            mail_from: string(config, "mail_from", "money-manager@localhost", &mut errors),
+            sendmail_path: string(config, "sendmail_path", "/usr/sbin/sendmail", &mut errors)
+        };
+        settings.check(config, &mut errors);
+        if errors.is_empty() {
+            Ok(settings)
+        } else {
+            Err(format!("invalid configuration:\n    {}", errors.join("\n    ")))
+        }
+    }
+
+    fn check(&self, config: &Config, errors: &mut Vec<String>) {
+        if self.jwt_key.is_empty() && self.jwt_private_keys.is_empty() {
+            errors.push("jwt_key or jwt_private_keys is required".to_string());
+        }
+        // a weak shared secret is tolerated only in development
+        if !config.environment.is_dev() && !self.jwt_key.is_empty() && self.jwt_key.len() < MIN_JWT_KEY_LEN {
+            errors.push(format!("jwt_key must be at least {} characters long outside development", MIN_JWT_KEY_LEN));
+        }
+        for path in &self.jwt_private_keys {
+            if !Path::new(path).is_file() {
+                errors.push(format!("jwt_private_keys: {} does not exist", path));
+            }
+        }
+        if self.jwt_exp == 0 || self.jwt_exp > i64::max_value() as u64 {
+            errors.push("jwt_exp must be greater than zero".to_string());
+        }
+        if self.reset_exp == 0 || self.reset_exp > i64::max_value() as u64 {
+            errors.push("reset_exp must be greater than zero".to_string());
+        }
+        if self.deletion_grace_days < 0 {
+            errors.push("deletion_grace_days must not be negative".to_string());
+        }
+        if self.trash_retention_days < 0 {
+            errors.push("trash_retention_days must not be negative".to_string());
+        }
+        if self.idempotency_window_hours <= 0 {
+            errors.push("idempotency_window_hours must be greater than zero".to_string());
+        }
+        if self.housekeeping_interval > i64::max_value() as u64 {
+            errors.push("housekeeping_interval must not be negative".to_string());
+        }
+        if let Some(ref url) = self.reset_url {
+            if !is_http(url) || !url.contains("{token}") {
+                errors.push("reset_url must be an http(s) URL containing {token}".to_string());
+            }
+        }
+        if !["run", "verify", "off"].contains(&self.migrations.as_str()) {
+            errors.push(format!("migrations must be run, verify or off, not {}", self.migrations));
+        }
+        for origin in &self.cors_origins {
+            if origin != "*" && !is_http(origin) {
+                errors.push(format!("cors_origins: {} is not an http(s) origin", origin));
+            }
+        }
+        if let Some(ref dir) = self.attachment_dir {
+            if !Path::new(dir).is_dir() {
+                errors.push(format!("attachment_dir: {} is not a directory", dir));
+            }
+        }
+        if !["log", "sendmail"].contains(&self.mailer.as_str()) {
+            errors.push(format!("mailer must be log or sendmail, not {}", self.mailer));
+        }
+        if let Err(e) = database_config("db", config) {
+            errors.push(format!("databases.db: {:?}", e));
+        }
+    }
+}
+
+///
+/// The Rocket configuration of the active environment with the MM_* variables applied.
+/// MM_DATABASE_URL replaces the URL of the database.
+pub fn rocket_config() -> Result<Config, String> {
+    let mut config = read_rocket_toml()?;
+    let mut errors = Vec::new();
+    for (var, key, kind) in OVERRIDES.iter() {
+        let value = match env::var(var) {
+            Ok(value) => value,
+            Err(_) => continue
+        };
+        let value = match kind {
+            Kind::Str => Value::String(value),
+            Kind::Int => match value.trim().parse::<i64>() {
+                Ok(n) => Value::Integer(n),
+                Err(_) => {
+                    errors.push(format!("{} must be an integer", var));
+                    continue;
+                }
+            },
+            Kind::List => Value::Array(value.split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| Value::String(s.to_string()))
+                .collect())
+        };
+        debug!("{} overridden by {}", key, var);
+        config.extras.insert(key.to_string(), value);
+    }
+    if let Ok(url) = env::var("MM_DATABASE_URL") {
+        let mut databases = match config.extras.get("databases") {
+            Some(Value::Table(t)) => t.clone(),
+            _ => Table::new()
+        };
+        let mut db = match databases.get("db") {
+            Some(Value::Table(t)) => t.clone(),
+            _ => Table::new()
+        };
+        db.insert("url".to_string(), Value::String(url));
+        databases.insert("db".to_string(), Value::Table(db));
+        config.extras.insert("databases".to_string(), Value::Table(databases));
+    }
+    if errors.is_empty() {
+        Ok(config)
+    } else {
+        Err(format!("invalid configuration:\n    {}", errors.join("\n    ")))
+    }
+}
+
+///
+/// The configuration of the active environment, ready to launch the server.
+pub fn load() -> Result<(Config, Settings), String> {
+    let config = rocket_config()?;
+    let settings = Settings::from_config(&config)?;
+    Ok((config, settings))
+}
+
+///
+/// The logger starts before Rocket, so its file can be changed only with MM_LOG_CONFIG.
+pub fn log_config_path() -> String {
+    if let Ok(path) = env::var("MM_LOG_CONFIG") {
+        return path;
+    }
+    if !cfg!(windows) && Path::new(DEFAULT_LOG_CONFIG).exists() {
+        DEFAULT_LOG_CONFIG.to_string()
+    } else {
+        "log-config.yml".to_string()
+    }
+}
+
+// #################################################################################################
+
+// what Rocket reads at launch, but a bad file is an error instead of the end of the process
+fn read_rocket_toml() -> Result<Config, String> {
+    let config = match RocketConfig::read() {
+        Ok(config) => config,
+        Err(ConfigError::NotFound) => RocketConfig::active_default().map_err(|e| describe(&e))?,
+        Err(ConfigError::IoError) => {
+            warn!("Can not read Rocket.toml, the defaults are used");
+            RocketConfig::active_default().map_err(|e| describe(&e))?
+        },
+        Err(e) => return Err(describe(&e))
+    };
+    Ok(config.active().clone())
+}
+
+fn describe(e: &ConfigError) -> String {
+    match e {
+        ConfigError::ParseError(_, path, description, Some((line, column))) =>
+            format!("{}:{}:{}: {}", path.display(), line, column, description),
+        ConfigError::ParseError(_, path, description, None) => format!("{}: {}", path.display(), description),
+        _ => format!("invalid configuration: {}", e)
+    }
+}
+
+fn string(config: &Config, key: &str, default: &str, errors: &mut Vec<String>) -> String {
+    optional(config, key, errors).unwrap_or_else(|| default.to_string())
+}
+
+fn optional(config: &Config, key: &str, errors: &mut Vec<String>) -> Option<String> {
+    match config.get_str(key) {
+        Ok(value) => Some(value.to_string()),
+        Err(ConfigError::Missing(_)) => None,
+        Err(_) => {
+            errors.push(format!("{} must be a string", key));
+            None
+        }
+    }
+}
+
+fn int(config: &Config, key: &str, default: i64, errors: &mut Vec<String>) -> i64 {
+    match config.get_int(key) {
+        Ok(value) => value,
+        Err(ConfigError::Missing(_)) => default,
+        Err(_) => {
+            errors.push(format!("{} must be an integer", key));
+            default
+        }
+    }
+}
+
+fn list(config: &Config, key: &str, errors: &mut Vec<String>) -> Option<Vec<String>> {
+    match config.get_slice(key) {
+        Ok(values) => Some(values.iter().filter_map(|v| match v.as_str() {
+            Some(s) => Some(s.to_string()),
+            None => {
+                errors.push(format!("{} must contain only strings", key));
+                None
+            }
+        }).collect()),
+        Err(ConfigError::Missing(_)) => None,
+        Err(_) => {
+            errors.push(format!("{} must be a list of strings", key));
+            None
+        }
+    }
+}
+
+fn is_http(value: &str) -> bool {
+    (value.starts_with("http://") || value.starts_with("https://"))
+        && !value.chars().any(char::is_whitespace)
+}
@@ -0,0 +1,226 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::Connection;
+use diesel::result::Error;
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use serde_json::Value;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::{Validate, Validator};
+use crate::patch;
+use crate::transaction::model::{Transaction, TransactionForm, BulkOperation, BulkRequest, BulkResult, BulkReport};
+use crate::audit::model::AuditLog;
+use crate::idempotency::{IdempotencyKey, Replayable};
+use crate::rule::engine;
+use crate::user::model::User;
+use crate::transaction;
+use crate::reconciliation;
+
+// the items of a request, a filter must not match more transactions
+const MAX_ITEMS: usize = 1000;
+
+// the fields that an update can change
+const FIELDS: [&str; 5] = ["id_causal", "id_place", "id_transaction_type", "note", "cleared"];
+
+#[post("/", data = "<json>", format = "application/json")]
+fn bulk(conn: MoneyManagerDB, json: Json<BulkRequest>, user: User,
+        key: IdempotencyKey) -> Result<Replayable, ApiError> {
+    debug!("BULK_TRANSACTION_REQUEST");
+    let request = json.into_inner();
+    key.once(&user, "transaction/bulk", &request, &conn, || execute(&request, &user, &conn))
+}
+
+///
+///
+pub fn get_mount() -> Vec<rocket::Route> {
+    routes![bulk]
+}
+
+// #################################################################################################
+
+fn execute(request: &BulkRequest, user: &User, conn: &MoneyManagerDB) -> Result<Replayable, ApiError> {
+    validate(request)?;
+    let ids = select(request, user, conn)?;
+    // the update and the move are patches of the selected transactions
+    let changes = match request.operation {
+        BulkOperation::Update => request.fields.clone().map(Value::Object),
+        BulkOperation::Move => request.id_account.map(|id| serde_json::json!({ "id_account": id })),
+        _ => None
+    }.unwrap_or(Value::Null);
+    let mut results = Vec::new();
+    let outcome = conn.transaction::<(), Error, _>(|| {
+        match request.operation {
+            BulkOperation::Create => {
+                for (index, form) in request.transactions.iter().flatten().enumerate() {
+                    results.push(run(index, None, Status::Ok, conn, || create(form, user, conn)));
+                }
+            },
+            BulkOperation::Delete => {
+                for (index, id) in ids.iter().enumerate() {
+                    results.push(run(index, Some(*id), Status::NoContent, conn, || delete(*id, user, conn)));
+                }
+            },
+            BulkOperation::Update | BulkOperation::Move => {
+                for (index, id) in ids.iter().enumerate() {
+                    results.push(run(index, Some(*id), Status::NoContent, conn, || change(*id, &changes, user, conn)));
+                }
+            }
+        }
+        if request.all_or_nothing && results.iter().any(|r| r.status >= 400) {
+            return Err(Error::RollbackTransaction);
+        }
+        Ok(())
+    });
+    match outcome {
+        Ok(_) => {
+            info!("bulk {:?} of {} transactions", request.operation, results.len());
+            Replayable::json(Status::Ok, &BulkReport { committed: true, results })
+        },
+        Err(Error::RollbackTransaction) => {
+            warn!("bulk {:?} rolled back, an item failed", request.operation);
+            Replayable::json(Status::Conflict, &BulkReport { committed: false, results })
+        },
+        Err(e) => {
+            error!("Can not run the bulk {:?} caused by {}", request.operation, e);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+fn validate(request: &BulkRequest) -> Result<(), ApiError> {
+    let mut v = Validator::new();
+    let too_many = format!("must be at most {} items", MAX_ITEMS);
+    if request.operation == BulkOperation::Create {
+        match &request.transactions {
+            Some(transactions) => {
+                v.check(!transactions.is_empty(), "transactions", "must not be empty");
+                v.check(transactions.len() <= MAX_ITEMS, "transactions", &too_many);
+            },
+            None => v.error("transactions", "is required to create")
+        }
+    } else {
+        v.check(request.ids.is_some() != request.filter.is_some(), "ids", "either ids or filter is required");
+        if let Some(ids) = &request.ids {
+            v.check(ids.len() <= MAX_ITEMS, "ids", &too_many);
+        }
+    }
+    match request.operation {
+        BulkOperation::Update => match &request.fields {
+            Some(fields) => {
+                v.check(!fields.is_empty(), "fields", "must not be empty");
+                v.check(fields.keys().all(|k| FIELDS.contains(&k.as_str())), "fields",
+                        "only id_causal, id_place, id_transaction_type, note and cleared can be changed");
+            },
+            None => v.error("fields", "is required to update")
+        },
+        BulkOperation::Move => v.check(request.id_account.is_some(), "id_account", "is required to move"),
+        _ => {}
+    }
+    v.finish()
+}
+
+///
+/// The ids of the request or the ones of the transactions that match the filter.
+fn select(request: &BulkRequest, user: &User, conn: &MoneyManagerDB) -> Result<Vec<i64>, ApiError> {
+    if let Some(ids) = &request.ids {
+        return Ok(ids.clone());
+    }
+    match &request.filter {
+        Some(filter) => {
+            let found = Transaction::search(user, filter, MAX_ITEMS as i64 + 1, conn)
+                .map_err(|e| ApiError::from_db("transaction", e))?;
+            let mut v = Validator::new();
+            v.check(found.len() <= MAX_ITEMS, "filter", &format!("must match at most {} transactions", MAX_ITEMS));
+            v.finish()?;
+            Ok(found.iter().map(|t| t.id).collect())
+        },
+        None => Ok(Vec::new())
+    }
+}
+
+///
+/// Every item has its savepoint: a failed one is undone, the others are kept.
+fn run<F>(index: usize, id: Option<i64>, status: Status, conn: &MoneyManagerDB, op: F) -> BulkResult
+    where F: FnOnce() -> Result<i64, ApiError> {
+    let mut failure = None;
+    let result = conn.transaction::<i64, Error, _>(|| {
+        op().map_err(|e| {
+            failure = Some(e);
+            Error::RollbackTransaction
+        })
+    });
+    let e = match (result, failure) {
+        (Ok(id), _) => return BulkResult { index, id: Some(id), status: status.code, code: None, message: None },
+        (Err(_), Some(e)) => e,
+        (Err(e), None) => {
+            error!("Can not run the bulk item {} caused by {}", index, e);
+            ApiError::internal()
+        }
+    };
+    let mut message = e.message;
+    for field in e.fields {
+        message = format!("{}; {} {}", message, field.field, field.message);
+    }
+    BulkResult { index, id, status: e.status.code, code: Some(e.code.to_string()), message: Some(message) }
+}
+
+fn create(form: &Value, user: &User, conn: &MoneyManagerDB) -> Result<i64, ApiError> {
+    let json = form.to_string();
+    let mut form = patch::form::<TransactionForm>(&json)?;
+    let actions = engine::apply_to_new(&mut form, user, conn)
+        .map_err(|e| ApiError::from_db("rule", e))?;
+    form.validate(user, conn)?;
+    reconciliation::check_open("cleared", form.cleared, form.id_account, form.data, conn)?;
+    let created = Transaction::create(&form, conn)
+        .and_then(|t| AuditLog::created(user, &t, conn).map(|_| t))
+        .and_then(|t| actions.link_details(&t, user, conn).map(|_| t.id));
+    created.map_err(|e| ApiError::from_db("transaction", e))
+}
+
+fn change(id: i64, changes: &Value, user: &User, conn: &MoneyManagerDB) -> Result<i64, ApiError> {
+    let before = transaction::get_and_check(id, user, conn)?;
+    reconciliation::check_unlocked("transaction", before.id_reconciliation)?;
+    let merged = patch::apply(&before, changes)?;
+    let form = patch::form::<TransactionForm>(&merged)?;
+    form.validate_update(&before, user, conn)?;
+    reconciliation::check_open("cleared", form.cleared, form.id_account, form.data, conn)?;
+    let n = Transaction::update(&before, &form, conn)
+        .map_err(|e| ApiError::from_db("transaction", e))?;
+    if n == 0 {
+        return Err(ApiError::precondition_failed());
+    }
+    Transaction::read_by_id(id, conn)
+        .and_then(|after| AuditLog::updated(user, &before, &after, conn))
+        .map(|_| id)
+        .map_err(|e| ApiError::from_db("transaction", e))
+}
+
+fn delete(id: i64, user: &User, conn: &MoneyManagerDB) -> Result<i64, ApiError> {
+    let transaction = transaction::get_and_check(id, user, conn)?;
+    reconciliation::check_unlocked("transaction", transaction.id_reconciliation)?;
+    let n = Transaction::delete(&transaction, conn)
+        .map_err(|e| ApiError::from_db("transaction", e))?;
+    if n == 0 {
+        return Err(ApiError::precondition_failed());
+    }
+    AuditLog::deleted(user, &transaction, conn)
+        .map(|_| id)
+        .map_err(|e| ApiError::from_db("transaction", e))
+}
@@ -15,84 +15,171 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use diesel::Connection;
+use diesel::result::Error;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
+use serde_json::Value;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
+use crate::patch;
 use crate::base_model::BaseModel;
-use crate::base_controller::BaseController;
-use crate::transaction::model::{Transaction, TransactionForm};
+use crate::base_controller::{BaseController, IfMatch, Tagged};
+use crate::transaction::model::{Transaction, TransactionForm, LedgerEntry};
+use crate::audit::model::AuditLog;
+use crate::idempotency::{IdempotencyKey, Replayable};
+use crate::rule::engine;
+use crate::account::model::Account;
+use crate::account::balance;
 use crate::account;
+use crate::reconciliation;
 use crate::user::model::User;
 
 pub mod model;
 
 mod transaction_type;
 mod transaction_detail;
+mod bulk;
+pub mod suggestion;
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<TransactionForm>, user: User) -> Result<Json<Transaction>, Status> {
+fn create(conn: MoneyManagerDB, json: Json<TransactionForm>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_TRANSACTION_REQUEST");
     let form = json.into_inner();
-    account::check(form.id_account, &user, &conn)?;
-    Transaction::create(&form, &conn)
-        .map(|t| {
-            info!("transaction create successfully {}", t.id);
-            Json(t)
-        })
-        .map_err(|e| {
-           error!("Can not create transaction caused by {}", e);
-            Status::InternalServerError
-        })
+    key.once(&user, "transaction", &form, &conn, || {
+        // the rules of the user can change the causal, the place and the type
+        let mut form = form.clone();
+        let actions = engine::apply_to_new(&mut form, &user, &conn)
+            .map_err(|e| ApiError::from_db("rule", e))?;
+        // also checks the account, the causal and the place of the user
+        form.validate(&user, &conn)?;
+        reconciliation::check_open("cleared", form.cleared, form.id_account, form.data, &conn)?;
+        let result = conn.transaction::<Transaction, Error, _>(|| {
+            let transaction = Transaction::create(&form, &conn)?;
+            AuditLog::created(&user, &transaction, &conn)?;
+            actions.link_details(&transaction, &user, &conn)?;
+            Ok(transaction)
+        });
+        let transaction = result
+            .map(|t| {
+                info!("transaction create successfully {}", t.id);
+                t
+            })
+            .map_err(|e| {
+               error!("Can not create transaction caused by {}", e);
+                ApiError::internal()
+            })?;
+        Replayable::json(Status::Ok, &transaction)
+    })
 }
 
 #[get("/<id>")]
-fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Transaction>, Status> {
+fn read_one(conn: MoneyManagerDB, id: i64, user: User) -> Result<Tagged<Json<Transaction>>, ApiError> {
     debug!("READ_ONE_TRANSACTION_REQUEST");
     let transaction = get_by_id(id, &conn)?;
     // user can access his own transaction
     account::check(transaction.id_account, &user, &conn)?;
-    Ok(Json(transaction))
+    let version = transaction.version;
+    Ok(Tagged(Json(transaction), version))
 }
 
-#[get("/account/<id>?<limit>&<offset>")]
-pub fn read_by_account(conn: MoneyManagerDB, id: i64, user: User, limit: Option<i64>,
-                       offset: Option<i64>) -> Result<Json<Vec<Transaction>>, Custom<String>> {
+///
+/// The transactions are in the order of the date, with the running balance each has the balance after it.
+#[get("/account/<id>?<limit>&<offset>&<running_balance>")]
+pub fn read_by_account(conn: MoneyManagerDB, id: i64, user: User, limit: Option<i64>, offset: Option<i64>,
+                       running_balance: Option<bool>) -> Result<Json<Vec<LedgerEntry>>, ApiError> {
     debug!("READ_BY_ACCOUNT_TRANSACTION_REQUEST");
-    let account = account::get_and_check(id, &user, &conn)
-        .map_err(|s| Custom(s, String::new()))?;
-    let result = Transaction::read_by_account(&account, &conn,
-                                              offset.unwrap_or(0),
-                                              limit.unwrap_or(i64::max_value()));
-    Transaction::unpack(result)
+    let account = account::get_and_check(id, &user, &conn)?;
+    let offset = offset.unwrap_or(0).max(0);
+    let limit = limit.unwrap_or(i64::max_value()).max(0);
+    let result = if running_balance.unwrap_or(false) {
+        balance::running(&account, offset, limit, &conn).map(|ledger| ledger.into_iter()
+            .map(|(transaction, balance)| LedgerEntry { transaction, running_balance: Some(balance) })
+            .collect())
+    } else {
+        Transaction::read_by_account(&account, &conn, offset, limit)
+            .map(|transactions| transactions.into_iter()
+                .map(|transaction| LedgerEntry { transaction, running_balance: None })
+                .collect())
+    };
+    LedgerEntry::unpack(result)
 }
 
 #[put("/<id>", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, id: i64, json: Json<TransactionForm>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, id: i64, json: Json<Value>, user: User,
+          if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("UPDATE_TRANSACTION_REQUEST");
     let transaction = get_by_id(id, &conn)?;
     // check if transaction can be updated
     check_property(&transaction, &user, &conn)?;
-    let result = Transaction::update(&transaction, &json.into_inner(), &conn);
-    Transaction::finalize_update_delete(result)
+    if_match.check(transaction.version)?;
+    // the body replaces the transaction, but a missing cleared does not clear it
+    let body = patch::replace(&transaction, &json, &["cleared"])?;
+    let form = patch::form::<TransactionForm>(&body)?;
+    form.validate_update(&transaction, &user, &conn)?;
+    save(&transaction, &form, &user, &conn)
+}
+
+#[patch("/<id>", data = "<json>", format = "application/json")]
+fn patch(conn: MoneyManagerDB, id: i64, json: Json<Value>, user: User,
+         if_match: IfMatch) -> Result<Status, ApiError> {
+    debug!("PATCH_TRANSACTION_REQUEST");
+    let transaction = get_by_id(id, &conn)?;
+    check_property(&transaction, &user, &conn)?;
+    if_match.check(transaction.version)?;
+    // only the fields of the patch change, the others are the current ones
+    let merged = patch::apply(&transaction, &json)?;
+    let form = patch::form::<TransactionForm>(&merged)?;
+    form.validate_update(&transaction, &user, &conn)?;
+    save(&transaction, &form, &user, &conn)
 }
 
 #[delete("/<id>")]
-fn delete(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id: i64, user: User, if_match: IfMatch) -> Result<Status, ApiError> {
     debug!("DELETE_TRANSACTION_REQUEST");
     let transaction = get_by_id(id, &conn)?;
     // check if causal can be deleted
     check_property(&transaction, &user, &conn)?;
-    let result = Transaction::delete(&transaction, &conn);
+    if_match.check(transaction.version)?;
+    reconciliation::check_unlocked("transaction", transaction.id_reconciliation)?;
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Transaction::delete(&transaction, &conn)?;
+        if n > 0 {
+            AuditLog::deleted(&user, &transaction, &conn)?;
+        }
+        Ok(n)
+    });
+    Transaction::finalize_versioned(result)
+}
+
+#[post("/<id>/restore")]
+fn restore(conn: MoneyManagerDB, id: i64, user: User) -> Result<Status, ApiError> {
+    debug!("RESTORE_TRANSACTION_REQUEST");
+    let transaction = Transaction::read_deleted_by_id(id, &conn)
+        .map_err(|e| ApiError::from_db("transaction", e))?;
+    check_property(&transaction, &user, &conn)?;
+    if Account::read_by_id(transaction.id_account, &conn).is_err() {
+        return Err(ApiError::conflict("The account of the transaction is in the trash, it must be restored first"));
+    }
+    if transaction.cleared && !reconciliation::is_open(transaction.id_account, transaction.data, &conn)? {
+        return Err(ApiError::conflict("The transaction is cleared up to the last reconciled statement, it can not be restored"));
+    }
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Transaction::restore(&transaction, &conn)?;
+        let after = Transaction::read_by_id(transaction.id, &conn)?;
+        AuditLog::restored(&user, &after, &conn)?;
+        Ok(n)
+    });
     Transaction::finalize_update_delete(result)
 }
 
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/transaction", routes![read_one, read_by_account, create, update, delete])
+    rocket.mount("/transaction", routes![read_one, read_by_account, create, update, patch, delete, restore])
 }
 
 ///
@@ -101,6 +188,12 @@ pub fn mount_transaction_type(rocket: rocket::Rocket) -> rocket::Rocket {
     rocket.mount("/transaction/type", transaction_type::get_mount())
 }
 
+///
+///
+pub fn mount_admin_transaction_type(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/admin/transaction/type", transaction_type::get_admin_mount())
+}
+
 ///
 ///
 pub fn mount_transaction_detail(rocket: rocket::Rocket) -> rocket::Rocket {
@@ -109,7 +202,19 @@ pub fn mount_transaction_detail(rocket: rocket::Rocket) -> rocket::Rocket {
 
 ///
 ///
-pub fn get_and_check(id_transaction: i64, user: &User, conn: &MoneyManagerDB) -> Result<Transaction, Status> {
+pub fn mount_transaction_bulk(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/transaction/bulk", bulk::get_mount())
+}
+
+///
+///
+pub fn mount_transaction_suggestion(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/transaction/suggest", suggestion::get_mount())
+}
+
+///
+///
+pub fn get_and_check(id_transaction: i64, user: &User, conn: &MoneyManagerDB) -> Result<Transaction, ApiError> {
     let transaction = get_by_id(id_transaction, conn)?;
     check_property(&transaction, user, conn)?;
     Ok(transaction)
@@ -117,24 +222,32 @@ pub fn get_and_check(id_transaction: i64, user: &User, conn: &MoneyManagerDB) ->
 
 // #################################################################################################
 
-fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Transaction, Status> {
+fn get_by_id(id: i64, conn: &MoneyManagerDB) -> Result<Transaction, ApiError> {
     Transaction::read_by_id(id, &conn)
-        .map_err(|e| {
-            error!("Can not read transaction: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("transaction", e))
 }
 
-fn check_property(transaction: &Transaction, user: &User, conn: &MoneyManagerDB) -> Result<(), Status> {
+fn check_property(transaction: &Transaction, user: &User, conn: &MoneyManagerDB) -> Result<(), ApiError> {
     let c = account::check(transaction.id_account, user, conn);
     if c.is_err() {
         warn!("The user attempts to access transaction that does not belong to it!");
-        Err(Status::Forbidden)
+        Err(ApiError::forbidden("transaction"))
     } else {
         Ok(())
     }
 }
+
+fn save(transaction: &Transaction, form: &TransactionForm, user: &User, conn: &MoneyManagerDB) -> Result<Status, ApiError> {
+    reconciliation::check_unlocked("transaction", transaction.id_reconciliation)?;
+    reconciliation::check_open("cleared", form.cleared, form.id_account, form.data, conn)?;
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = Transaction::update(transaction, form, conn)?;
+        if n == 0 {
+            return Ok(0);
+        }
+        let after = Transaction::read_by_id(transaction.id, conn)?;
+        AuditLog::updated(user, transaction, &after, conn)?;
+        Ok(n)
+    });
+    Transaction::finalize_versioned(result)
+}
@@ -17,22 +17,29 @@
 
 use diesel;
 use diesel::prelude::*;
+use diesel::pg::PgConnection;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use serde_json::{Map, Value};
 
-use crate::schema::{transaction, transaction_type, transaction_detail};
+use crate::schema::{transaction, transaction_type, transaction_detail, account_user};
 use crate::account::model::Account;
 use crate::currency::model::Currency;
 use crate::causal:: model::Causal;
 use crate::detail::model::Detail;
-use crate::database::MoneyManagerDB;
+use crate::user::model::User;
+use crate::place::model::Place;
+use crate::validation::{Validate, Validator};
+use crate::audit::model::Audited;
+use crate::error::ApiError;
 
 #[table_name = "transaction"]
 #[belongs_to(Account, foreign_key = "id_account")]
 #[belongs_to(TransactionType, foreign_key="id_transaction_type")]
 #[belongs_to(Currency, foreign_key="id_currency")]
 #[belongs_to(Causal, foreign_key="id_causal")]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Associations,JsonSchema)]
 pub struct Transaction {
     pub id: i64,
     pub id_account: i64,
@@ -44,12 +51,22 @@ pub struct Transaction {
     pub data: DateTime<Utc>,
     pub id_currency: i16,
     pub expense: Option<f64>,
-    pub id_causal: i64
+    pub id_causal: i64,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32,
+    /// it appears on the statement of the bank
+    pub cleared: bool,
+    /// the reconciliation that locked it
+    pub id_reconciliation: Option<i64>,
+    /// 1 when it increases the balance, -1 when it decreases it: the direction of the type when it was
+    /// recorded, the sign of the amount for the transactions recorded before the types had one
+    pub direction: i16
 }
 
-// only for insert and update
+// only for insert and update, the update replaces the whole row: a missing field becomes null
 #[table_name = "transaction"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
+#[derive(Debug,Clone,Serialize,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct TransactionForm<'a> {
     pub id_account: i64,
     pub id_transaction_type: i32,
@@ -60,30 +77,106 @@ pub struct TransactionForm<'a> {
     pub data: DateTime<Utc>,
     pub id_currency: i16,
     pub expense: Option<f64>,
-    pub id_causal: i64
+    pub id_causal: i64,
+    /// missing it is false on create and unchanged on update
+    #[serde(default)]
+    pub cleared: bool
+}
+
+///
+/// A row of the ledger of an account, the balance of the account after it only when asked.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct LedgerEntry {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub running_balance: Option<f64>
 }
 
 #[table_name="transaction_type"]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
 pub struct TransactionType {
     pub id: i32,
     #[serde(rename="type")]
-    pub type_: String
+    pub type_: String,
+    pub direction: i16
 }
 
 // only for insert and update
 #[table_name="transaction_type"]
-#[derive(Debug,Deserialize,Insertable,AsChangeset)]
+#[derive(Debug,Deserialize,Insertable,AsChangeset,JsonSchema)]
 pub struct TransactionTypeForm<'a> {
     #[serde(rename="type")]
-    pub type_: &'a str
+    pub type_: &'a str,
+    /// 1 when the new transactions of this type increase the balance, -1 when they decrease it;
+    /// missing it is -1 on create and unchanged on update
+    pub direction: Option<i16>
+}
+
+///
+/// The transactions of the user that match all the given conditions.
+#[derive(Debug,Default,Serialize,Deserialize,JsonSchema)]
+pub struct TransactionFilter {
+    pub id_account: Option<i64>,
+    pub id_causal: Option<i64>,
+    pub id_place: Option<i64>,
+    pub id_transaction_type: Option<i32>,
+    pub from: Option<DateTime<Utc>>,
+    /// excluded
+    pub to: Option<DateTime<Utc>>,
+    /// part of the note, the case is ignored
+    pub note: Option<String>,
+    pub cleared: Option<bool>
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize,JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOperation {
+    Create,
+    Update,
+    Delete,
+    Move
+}
+
+///
+/// One operation on many transactions, chosen by ids or by a filter (not for create).
+#[derive(Debug,Serialize,Deserialize,JsonSchema)]
+pub struct BulkRequest {
+    pub operation: BulkOperation,
+    /// when an item fails nothing is written
+    #[serde(default)]
+    pub all_or_nothing: bool,
+    pub ids: Option<Vec<i64>>,
+    pub filter: Option<TransactionFilter>,
+    /// update: id_causal, id_place, id_transaction_type, note and cleared, a null clears the place and the note
+    pub fields: Option<Map<String, Value>>,
+    /// move: the destination account
+    pub id_account: Option<i64>,
+    /// create: the new transactions
+    pub transactions: Option<Vec<Value>>
+}
+
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct BulkResult {
+    /// the position in ids, transactions or the matches of the filter
+    pub index: usize,
+    pub id: Option<i64>,
+    pub status: u16,
+    pub code: Option<String>,
+    pub message: Option<String>
+}
+
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct BulkReport {
+    pub committed: bool,
+    pub results: Vec<BulkResult>
 }
 
 #[table_name="transaction_detail"]
 #[primary_key(id_detail,id_transaction)]
 #[belongs_to(Detail, foreign_key = "id_detail")]
 #[belongs_to(Transaction, foreign_key="id_transaction")]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Insertable,AsChangeset,Associations)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,Insertable,AsChangeset,Associations,JsonSchema)]
 pub struct TransactionDetail {
     pub id_detail: i64,
     pub id_transaction: i64,
@@ -91,110 +184,312 @@ pub struct TransactionDetail {
 }
 
 impl Transaction {
-    pub fn create(form: &TransactionForm, conn: &MoneyManagerDB) -> QueryResult<Transaction> {
+    ///
+    /// The transaction moves the balance in the direction of its type.
+    pub fn create(form: &TransactionForm, conn: &PgConnection) -> QueryResult<Transaction> {
+        let direction = TransactionType::read_by_id(form.id_transaction_type, conn)?.direction;
+        Transaction::create_directed(form, direction, conn)
+    }
+    ///
+    /// The transaction keeps the given direction, e.g. one out of an archive.
+    pub fn create_directed(form: &TransactionForm, direction: i16, conn: &PgConnection) -> QueryResult<Transaction> {
         diesel::insert_into(transaction::table)
-            .values(form)
-            .get_result::<Transaction>(&*(*conn))
+            .values((form, transaction::direction.eq(direction)))
+            .get_result::<Transaction>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<Transaction>> {
-        transaction::table.load::<Transaction>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<Transaction>> {
+        transaction::table
+            .filter(transaction::deleted_at.is_null())
+            .load::<Transaction>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<Transaction> {
-        transaction::table.find(id).first::<Transaction>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<Transaction> {
+        transaction::table.find(id)
+            .filter(transaction::deleted_at.is_null())
+            .first::<Transaction>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_account(account: &Account, conn: &MoneyManagerDB, offset: i64,
+    pub fn read_deleted_by_id(id: i64, conn: &PgConnection) -> QueryResult<Transaction> {
+        transaction::table.find(id)
+            .filter(transaction::deleted_at.is_not_null())
+            .first::<Transaction>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_account(account: &Account, conn: &PgConnection, offset: i64,
                            limit: i64) -> QueryResult<Vec<Transaction>> {
         transaction::table
             .filter(transaction::id_account.eq(account.id))
+            .filter(transaction::deleted_at.is_null())
+            .order((transaction::data, transaction::id))
             .offset(offset)
             .limit(limit)
-            .load::<Transaction>(&*(*conn))
+            .load::<Transaction>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(transaction: &Transaction, form: &TransactionForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::update(transaction)
-            .set(form)
-            .execute(&*(*conn))
+    ///
+    /// At most limit transactions of the accounts of the user, in the order of creation.
+    pub fn search(user: &User, filter: &TransactionFilter, limit: i64, conn: &PgConnection) -> QueryResult<Vec<Transaction>> {
+        let accounts = account_user::table
+            .filter(account_user::id_user.eq(user.id))
+            .select(account_user::id_account);
+        let mut query = transaction::table
+            .filter(transaction::id_account.eq_any(accounts))
+            .filter(transaction::deleted_at.is_null())
+            .into_boxed();
+        if let Some(id_account) = filter.id_account {
+            query = query.filter(transaction::id_account.eq(id_account));
+        }
+        if let Some(id_causal) = filter.id_causal {
+            query = query.filter(transaction::id_causal.eq(id_causal));
+        }
+        if let Some(id_place) = filter.id_place {
+            query = query.filter(transaction::id_place.eq(id_place));
+        }
+        if let Some(id_transaction_type) = filter.id_transaction_type {
+            query = query.filter(transaction::id_transaction_type.eq(id_transaction_type));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(transaction::data.ge(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(transaction::data.lt(to));
+        }
+        if let Some(note) = &filter.note {
+            let escaped = note.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            query = query.filter(transaction::note.ilike(format!("%{}%", escaped)));
+        }
+        if let Some(cleared) = filter.cleared {
+            query = query.filter(transaction::cleared.eq(cleared));
+        }
+        query
+            .order(transaction::id)
+            .limit(limit)
+            .load::<Transaction>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// The most recent transactions of the accounts of the user.
+    pub fn read_latest_by_user(user: &User, limit: i64, conn: &PgConnection) -> QueryResult<Vec<Transaction>> {
+        let accounts = account_user::table
+            .filter(account_user::id_user.eq(user.id))
+            .select(account_user::id_account);
+        transaction::table
+            .filter(transaction::id_account.eq_any(accounts))
+            .filter(transaction::deleted_at.is_null())
+            .order((transaction::data.desc(), transaction::id.desc()))
+            .limit(limit)
+            .load::<Transaction>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Only the version that was read is updated, no row means that it has changed in the meantime.
+    ///
+    /// The direction changes only with the type.
+    pub fn update(transaction: &Transaction, form: &TransactionForm, conn: &PgConnection) -> QueryResult<usize> {
+        let direction = if form.id_transaction_type == transaction.id_transaction_type {
+            transaction.direction
+        } else {
+            TransactionType::read_by_id(form.id_transaction_type, conn)?.direction
+        };
+        diesel::update(transaction::table.find(transaction.id).filter(transaction::version.eq(transaction.version)))
+            .set((form, transaction::direction.eq(direction), transaction::version.eq(transaction.version + 1)))
+            .execute(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    ///
+    /// Move the transaction to the trash.
+    pub fn delete(transaction: &Transaction, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(transaction::table.find(transaction.id).filter(transaction::version.eq(transaction.version)))
+            .set((transaction::deleted_at.eq(Utc::now()), transaction::version.eq(transaction.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(transaction: &Transaction, conn: &MoneyManagerDB) -> QueryResult<usize> {
-        diesel::delete(transaction)
-            .execute(&*(*conn))
+    pub fn restore(transaction: &Transaction, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(transaction)
+            .set((transaction::deleted_at.eq(None::<DateTime<Utc>>), transaction::version.eq(transaction.version + 1)))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
 
 impl TransactionDetail {
-    pub fn create(td: &TransactionDetail, conn: &MoneyManagerDB) -> bool {
+    pub fn create(td: &TransactionDetail, conn: &PgConnection) -> bool {
         diesel::insert_into(transaction_detail::table)
             .values(td)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e }).is_ok()
     }
-    pub fn read_by_transaction(conn: &MoneyManagerDB, transaction: &Transaction) -> QueryResult<Vec<TransactionDetail>> {
+    pub fn read_by_transaction(conn: &PgConnection, transaction: &Transaction) -> QueryResult<Vec<TransactionDetail>> {
         TransactionDetail::belonging_to(transaction)
-            .load::<TransactionDetail>(&*(*conn))
+            .load::<TransactionDetail>(conn)
+            .map_err(|e| { warn!("{}", e); e })
+    }
+    pub fn read_by_transactions(ids: &[i64], conn: &PgConnection) -> QueryResult<Vec<TransactionDetail>> {
+        transaction_detail::table
+            .filter(transaction_detail::id_transaction.eq_any(ids))
+            .load::<TransactionDetail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_detail(conn: &MoneyManagerDB, detail: &Detail) -> QueryResult<Vec<TransactionDetail>> {
+    pub fn read_by_detail(conn: &PgConnection, detail: &Detail) -> QueryResult<Vec<TransactionDetail>> {
+        let transactions = transaction::table
+            .filter(transaction::deleted_at.is_null())
+            .select(transaction::id);
         TransactionDetail::belonging_to(detail)
-            .load::<TransactionDetail>(&*(*conn))
+            .filter(transaction_detail::id_transaction.eq_any(transactions))
+            .load::<TransactionDetail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_td(conn: &MoneyManagerDB, detail: &Detail, transaction: &Transaction) -> QueryResult<TransactionDetail> {
+    pub fn read_by_td(conn: &PgConnection, detail: &Detail, transaction: &Transaction) -> QueryResult<TransactionDetail> {
         transaction_detail::table
             .filter(transaction_detail::id_detail.eq(detail.id))
             .filter(transaction_detail::id_transaction.eq(transaction.id))
-            .first::<TransactionDetail>(&*(*conn))
+            .first::<TransactionDetail>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(td: &TransactionDetail, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn update(td: &TransactionDetail, conn: &PgConnection) -> QueryResult<usize> {
         diesel::update(td)
             .set(td)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(conn: &MoneyManagerDB, td: &TransactionDetail) -> QueryResult<usize> {
+    pub fn delete(conn: &PgConnection, td: &TransactionDetail) -> QueryResult<usize> {
         diesel::delete(td)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete_by_td(conn: &MoneyManagerDB, transaction: &Transaction, detail: &Detail) -> QueryResult<usize> {
+    pub fn delete_by_td(conn: &PgConnection, transaction: &Transaction, detail: &Detail) -> QueryResult<usize> {
         diesel::delete(transaction_detail::table
             .filter(transaction_detail::id_transaction.eq(transaction.id))
             .filter(transaction_detail::id_detail.eq(detail.id)))
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
 
+impl Audited for Transaction {
+    const ENTITY: &'static str = "transaction";
+    fn entity_id(&self) -> i64 {
+        self.id
+    }
+    fn id_account(&self, _conn: &PgConnection) -> QueryResult<i64> {
+        Ok(self.id_account)
+    }
+}
+
+// logged with the transaction as id, the detail is in the body
+impl Audited for TransactionDetail {
+    const ENTITY: &'static str = "transaction_detail";
+    fn entity_id(&self) -> i64 {
+        self.id_transaction
+    }
+    fn id_account(&self, conn: &PgConnection) -> QueryResult<i64> {
+        transaction::table.find(self.id_transaction)
+            .select(transaction::id_account)
+            .first::<i64>(conn)
+    }
+}
+
 impl TransactionType {
-    pub fn create(form: &TransactionTypeForm, conn: &MoneyManagerDB) -> QueryResult<TransactionType> {
+    pub fn create(form: &TransactionTypeForm, conn: &PgConnection) -> QueryResult<TransactionType> {
         diesel::insert_into(transaction_type::table)
             .values(form)
-            .get_result::<TransactionType>(&*(*conn))
+            .get_result::<TransactionType>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<TransactionType>> {
-        transaction_type::table.load::<TransactionType>(&*(*conn))
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<TransactionType>> {
+        transaction_type::table.load::<TransactionType>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn read_by_id(id: i32, conn: &MoneyManagerDB) -> QueryResult<TransactionType> {
-        transaction_type::table.find(id).first::<TransactionType>(&*(*conn))
+    pub fn read_by_id(id: i32, conn: &PgConnection) -> QueryResult<TransactionType> {
+        transaction_type::table.find(id).first::<TransactionType>(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn update(tt: &TransactionType, form: &TransactionTypeForm, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn update(tt: &TransactionType, form: &TransactionTypeForm, conn: &PgConnection) -> QueryResult<usize> {
         diesel::update(tt)
             .set(form)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
-    pub fn delete(tt: &TransactionType, conn: &MoneyManagerDB) -> QueryResult<usize> {
+    pub fn delete(tt: &TransactionType, conn: &PgConnection) -> QueryResult<usize> {
         diesel::delete(tt)
-            .execute(&*(*conn))
+            .execute(conn)
             .map_err(|e| { warn!("{}", e); e })
     }
 }
+
+impl<'a> Validate for TransactionForm<'a> {
+    fn validate(&self, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        self.check(None, user, conn)
+    }
+}
+
+impl<'a> TransactionForm<'a> {
+    ///
+    /// Validate the form that replaces the transaction: on a shared account the causal, the place
+    /// and the beneficiary it keeps can belong to another owner, only the new ones must be of the user.
+    pub fn validate_update(&self, before: &Transaction, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        self.check(Some(before), user, conn)
+    }
+    fn check(&self, before: Option<&Transaction>, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.positive("amount", self.amount);
+        v.not_negative("expense", self.expense);
+        v.max_length("note", self.note, 255);
+        let account = v.reference("id_account", Account::read_by_id(self.id_account, conn))?;
+        if account.is_some() {
+            v.account(self.id_account, user, conn)?;
+        }
+        v.reference("id_transaction_type", TransactionType::read_by_id(self.id_transaction_type, conn))?;
+        if v.reference("id_currency", Currency::read_by_id(self.id_currency, conn))?.is_some() {
+            if let Some(account) = account {
+                v.check(account.id_currency == self.id_currency, "id_currency",
+                        "must be the currency of the account");
+            }
+        }
+        if let Some(causal) = v.reference("id_causal", Causal::read_by_id(self.id_causal, conn))? {
+            if before.map_or(true, |t| t.id_causal != self.id_causal) {
+                v.owner("causal", causal.id_user, user)?;
+            }
+        }
+        if let Some(id_place) = self.id_place {
+            if let Some(place) = v.reference("id_place", Place::read_by_id(id_place, conn))? {
+                if before.map_or(true, |t| t.id_place != self.id_place) {
+                    v.owner("place", place.id_user, user)?;
+                }
+            }
+        }
+        if let Some(id_beneficiary) = self.id_beneficiary {
+            v.check(id_beneficiary != self.id_account, "id_beneficiary", "must be another account");
+            if v.reference("id_beneficiary", Account::read_by_id(id_beneficiary, conn))?.is_some() {
+                if before.map_or(true, |t| t.id_beneficiary != self.id_beneficiary) {
+                    v.account(id_beneficiary, user, conn)?;
+                }
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for TransactionDetail {
+    fn validate(&self, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.check(self.amount.map_or(true, |a| a > 0), "amount", "must be greater than zero");
+        if let Some(t) = v.reference("id_transaction", Transaction::read_by_id(self.id_transaction, conn))? {
+            v.account(t.id_account, user, conn)?;
+        }
+        if let Some(detail) = v.reference("id_detail", Detail::read_by_id(self.id_detail, conn))? {
+            v.owner("detail", detail.id_user, user)?;
+        }
+        v.finish()
+    }
+}
+
+impl<'a> Validate for TransactionTypeForm<'a> {
+    fn validate(&self, _user: &User, _conn: &PgConnection) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("type", self.type_, 32);
+        v.check(self.direction.map_or(true, |d| d == 1 || d == -1), "direction", "must be 1 or -1");
+        v.finish()
+    }
+}
@@ -0,0 +1,201 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::transaction::model::{Transaction, TransactionDetail};
+use crate::causal::model::Causal;
+use crate::place::model::Place;
+use crate::detail::model::Detail;
+use crate::user::model::User;
+use crate::account;
+
+// the transactions of the history that are compared, the most recent ones
+const HISTORY: i64 = 2000;
+const MAX_SUGGESTIONS: usize = 5;
+
+// how much each input counts in the similarity of a past transaction
+const NOTE_WEIGHT: f64 = 0.6;
+const AMOUNT_WEIGHT: f64 = 0.3;
+const ACCOUNT_WEIGHT: f64 = 0.1;
+
+///
+/// A causal, a place or a detail used by similar transactions: the score is its share
+/// of the similarity of the history (0..1), uses the number of similar transactions.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct Suggestion {
+    pub id: i64,
+    pub name: String,
+    pub score: f64,
+    pub uses: usize
+}
+
+///
+/// The best first.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct Suggestions {
+    pub causals: Vec<Suggestion>,
+    pub places: Vec<Suggestion>,
+    pub details: Vec<Suggestion>
+}
+
+#[get("/?<note>&<amount>&<account>")]
+fn suggest(conn: MoneyManagerDB, note: Option<String>, amount: Option<f64>, account: Option<i64>,
+           user: User) -> Result<Json<Suggestions>, ApiError> {
+    debug!("SUGGEST_TRANSACTION_REQUEST");
+    if let Some(id_account) = account {
+        account::check(id_account, &user, &conn)?;
+    }
+    let history = Transaction::read_latest_by_user(&user, HISTORY, &conn)
+        .map_err(|e| ApiError::from_db("transaction", e))?;
+    let tokens = note.as_ref().map(|note| tokenize(note));
+    let similar: Vec<(Transaction, f64)> = history.into_iter()
+        .map(|t| {
+            let s = similarity(&t, tokens.as_deref(), amount, account);
+            (t, s)
+        })
+        .filter(|(_, s)| *s > 0.0)
+        .collect();
+
+    let mut causals = Tally::default();
+    let mut places = Tally::default();
+    for (t, s) in &similar {
+        causals.add(t.id_causal, *s);
+        if let Some(id_place) = t.id_place {
+            places.add(id_place, *s);
+        }
+    }
+    let scores: HashMap<i64, f64> = similar.iter().map(|(t, s)| (t.id, *s)).collect();
+    let ids: Vec<i64> = scores.keys().cloned().collect();
+    let mut details = Tally::default();
+    for td in TransactionDetail::read_by_transactions(&ids, &conn).map_err(|e| ApiError::from_db("detail", e))? {
+        details.add(td.id_detail, scores[&td.id_transaction]);
+    }
+
+    // the entities in the trash or of other users (shared accounts) are not suggested
+    let causals = causals.rank(|id| Causal::read_by_id(id, &conn).ok()
+        .filter(|c| c.id_user.map_or(true, |u| u == user.id))
+        .map(|c| c.description));
+    let places = places.rank(|id| Place::read_by_id(id, &conn).ok()
+        .filter(|p| p.id_user.map_or(true, |u| u == user.id))
+        .map(|p| p.name));
+    let details = details.rank(|id| Detail::read_by_id(id, &conn).ok()
+        .filter(|d| d.id_user.map_or(true, |u| u == user.id))
+        .map(|d| d.description));
+    Ok(Json(Suggestions { causals, places, details }))
+}
+
+///
+///
+pub fn get_mount() -> Vec<rocket::Route> {
+    routes![suggest]
+}
+
+// #################################################################################################
+
+///
+/// The similarity of a past transaction with the new one (0..1), only the given inputs count.
+/// Without any input every transaction is equal: the suggestions are the most used ones.
+fn similarity(t: &Transaction, tokens: Option<&[String]>, amount: Option<f64>, account: Option<i64>) -> f64 {
+    let mut score = 0.0;
+    let mut weight = 0.0;
+    if let Some(tokens) = tokens {
+        let note = t.note.as_ref().map(|n| tokenize(n)).unwrap_or_default();
+        score += NOTE_WEIGHT * jaccard(tokens, &note);
+        weight += NOTE_WEIGHT;
+    }
+    if let Some(amount) = amount {
+        score += AMOUNT_WEIGHT * proximity(amount, t.amount);
+        weight += AMOUNT_WEIGHT;
+    }
+    if let Some(id_account) = account {
+        if t.id_account == id_account {
+            score += ACCOUNT_WEIGHT;
+        }
+        weight += ACCOUNT_WEIGHT;
+    }
+    if weight > 0.0 {
+        score / weight
+    } else {
+        1.0
+    }
+}
+
+///
+/// The words of a note in lower case; the numbers (dates, card numbers...) do not describe it.
+fn tokenize(note: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = note
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() > 1 && !t.chars().all(|c| c.is_numeric()))
+        .map(|t| t.to_lowercase())
+        .collect();
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+fn jaccard(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let common = a.iter().filter(|t| b.contains(t)).count();
+    common as f64 / (a.len() + b.len() - common) as f64
+}
+
+///
+/// 1 for the same amount, towards 0 as one becomes a multiple of the other.
+fn proximity(a: f64, b: f64) -> f64 {
+    let (a, b) = (a.abs(), b.abs());
+    if a == 0.0 && b == 0.0 {
+        1.0
+    } else {
+        a.min(b) / a.max(b)
+    }
+}
+
+///
+/// The sum of the similarities of the transactions that use an entity, so the frequent ones win.
+#[derive(Default)]
+struct Tally {
+    entries: HashMap<i64, (f64, usize)>
+}
+
+impl Tally {
+    fn add(&mut self, id: i64, score: f64) {
+        let entry = self.entries.entry(id).or_insert((0.0, 0));
+        entry.0 += score;
+        entry.1 += 1;
+    }
+
+    fn rank<F: Fn(i64) -> Option<String>>(self, name: F) -> Vec<Suggestion> {
+        let total: f64 = self.entries.values().map(|(s, _)| s).sum();
+        let mut ranked: Vec<(i64, f64, usize)> = self.entries.into_iter()
+            .map(|(id, (s, uses))| (id, s, uses))
+            .collect();
+        // the id breaks the ties, the same request always gets the same answer
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        ranked.into_iter()
+            .filter_map(|(id, s, uses)| name(id).map(|name| Suggestion { id, name, score: s / total, uses }))
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+}
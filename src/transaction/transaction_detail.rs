@@ -15,71 +15,89 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use diesel::Connection;
+use diesel::result::Error;
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use rocket::response::status::Custom;
-use diesel::result::Error;
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::validation::Validate;
 use crate::base_model::BaseModel;
 use crate::base_controller::BaseController;
 use crate::transaction::model::{Transaction, TransactionDetail};
 use crate::detail::model::Detail;
+use crate::audit::model::AuditLog;
+use crate::idempotency::{IdempotencyKey, Replayable};
 use crate::user::model::User;
 use crate::transaction;
 use crate::detail;
 
 #[post("/", data = "<json>", format = "application/json")]
-fn create(conn: MoneyManagerDB, json: Json<TransactionDetail>, user: User) -> Result<Status, Status> {
+fn create(conn: MoneyManagerDB, json: Json<TransactionDetail>, user: User,
+          key: IdempotencyKey) -> Result<Replayable, ApiError> {
     debug!("CREATE_TRANSACTION_DETAIL_REQUEST");
     let form = json.into_inner();
-    transaction::get_and_check(form.id_transaction, &user, &conn)?;
-    detail::get_and_check(form.id_detail, &user, &conn)?;
-    if TransactionDetail::create(&form, &conn) {
-        info!("transaction detail create successfully");
-        Ok(Status::NoContent)
-    } else {
-        error!("Can not create transaction detail");
-        Err(Status::InternalServerError)
-    }
+    key.once(&user, "transaction_detail", &form, &conn, || {
+        form.validate(&user, &conn)?;
+        let result = conn.transaction::<(), Error, _>(|| {
+            if !TransactionDetail::create(&form, &conn) {
+                return Err(Error::RollbackTransaction);
+            }
+            AuditLog::created(&user, &form, &conn)
+        });
+        if result.is_ok() {
+            info!("transaction detail create successfully");
+            Ok(Replayable::empty(Status::NoContent))
+        } else {
+            error!("Can not create transaction detail");
+            Err(ApiError::internal())
+        }
+    })
 }
 
 #[get("/transaction/<id>")]
-fn read_by_transaction(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<TransactionDetail>>, Custom<String>> {
+fn read_by_transaction(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<TransactionDetail>>, ApiError> {
     debug!("READ_BY_TRANSACTION_TRANSACTION_DETAIL_REQUEST");
-    let transaction = transaction::get_and_check(id, &user, &conn)
-        .map_err(|s| Custom(s, String::new()))?;
+    let transaction = transaction::get_and_check(id, &user, &conn)?;
     let result = TransactionDetail::read_by_transaction(&conn, &transaction);
     TransactionDetail::unpack(result)
 }
 
 #[get("/detail/<id>")]
-pub fn read_by_detail(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<TransactionDetail>>, Custom<String>> {
+pub fn read_by_detail(conn: MoneyManagerDB, id: i64, user: User) -> Result<Json<Vec<TransactionDetail>>, ApiError> {
     debug!("READ_BY_DETAIL_TRANSACTION_DETAIL_REQUEST");
-    let detail = detail::get_and_check(id, &user, &conn)
-        .map_err(|s| Custom(s, String::new()))?;
+    let detail = detail::get_and_check(id, &user, &conn)?;
     let result = TransactionDetail::read_by_detail(&conn, &detail);
     TransactionDetail::unpack(result)
 }
 
 #[put("/", data = "<json>", format = "application/json")]
-fn update(conn: MoneyManagerDB, json: Json<TransactionDetail>, user: User) -> Result<Status, Status> {
+fn update(conn: MoneyManagerDB, json: Json<TransactionDetail>, user: User) -> Result<Status, ApiError> {
     debug!("UPDATE_TRANSACTION_DETAIL_REQUEST");
     let form = json.into_inner();
     let transaction = transaction::get_and_check(form.id_transaction, &user, &conn)?;
     let detail = detail::get_and_check(form.id_detail, &user, &conn)?;
     let td = get_by_td(&transaction, &detail, &conn)?;
-    let result = TransactionDetail::update(&td, &conn);
+    form.validate(&user, &conn)?;
+    let result = conn.transaction::<usize, Error, _>(|| {
+        let n = TransactionDetail::update(&form, &conn)?;
+        AuditLog::updated(&user, &td, &form, &conn)?;
+        Ok(n)
+    });
     TransactionDetail::finalize_update_delete(result)
 }
 
 #[delete("/transaction/<id_transaction>/detail/<id_detail>")]
-fn delete(conn: MoneyManagerDB, id_transaction: i64, id_detail: i64, user: User) -> Result<Status, Status> {
+fn delete(conn: MoneyManagerDB, id_transaction: i64, id_detail: i64, user: User) -> Result<Status, ApiError> {
     debug!("DELETE_TRANSACTION_DETAIL_REQUEST");
     let transaction = transaction::get_and_check(id_transaction, &user, &conn)?;
     let detail = detail::get_and_check(id_detail, &user, &conn)?;
-    // let td = get_by_td(&transaction, &detail, &conn)?;
-    let result = TransactionDetail::delete_by_td(&conn, &transaction, &detail);
+    let td = get_by_td(&transaction, &detail, &conn)?;
+    let result = conn.transaction::<usize, Error, _>(|| {
+        AuditLog::deleted(&user, &td, &conn)?;
+        TransactionDetail::delete_by_td(&conn, &transaction, &detail)
+    });
     TransactionDetail::finalize_update_delete(result)
 }
 
@@ -91,14 +109,7 @@ pub fn get_mount() -> Vec<rocket::Route> {
 
 // #################################################################################################
 
-fn get_by_td(transaction: &Transaction, detail: &Detail, conn: &MoneyManagerDB) -> Result<TransactionDetail, Status> {
+fn get_by_td(transaction: &Transaction, detail: &Detail, conn: &MoneyManagerDB) -> Result<TransactionDetail, ApiError> {
     TransactionDetail::read_by_td(&conn, detail, transaction)
-        .map_err(|e| {
-            error!("Can not read transaction detail: {}", e);
-            if e.eq(&Error::NotFound) {
-                Status::NotFound
-            } else {
-                Status::InternalServerError
-            }
-        })
+        .map_err(|e| ApiError::from_db("transaction detail", e))
 }
@@ -0,0 +1,43 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use chrono::{Duration, Utc};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
+use crate::settings::Settings;
+use crate::trash::model::Trash;
+use crate::user::model::User;
+
+pub mod model;
+
+#[get("/")]
+fn read(conn: MoneyManagerDB, settings: State<Settings>, user: User) -> Result<Json<Trash>, ApiError> {
+    debug!("READ_TRASH_REQUEST");
+    let since = Utc::now() - Duration::days(settings.trash_retention_days);
+    Trash::read(&user, since, &conn)
+        .map(Json)
+        .map_err(|e| ApiError::from_db("trash", e))
+}
+
+///
+///
+pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket.mount("/trash", routes![read])
+}
@@ -0,0 +1,126 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use schemars::JsonSchema;
+
+use crate::schema::{account, account_user, transaction, transaction_detail, giro, place, causal};
+use crate::account::model::Account;
+use crate::transaction::model::Transaction;
+use crate::giro::model::Giro;
+use crate::place::model::Place;
+use crate::causal::model::Causal;
+use crate::user::model::User;
+
+///
+/// What the user deleted and can still restore, the most recent first.
+#[derive(Debug,Serialize,JsonSchema)]
+pub struct Trash {
+    pub accounts: Vec<Account>,
+    pub transactions: Vec<Transaction>,
+    pub giros: Vec<Giro>,
+    pub places: Vec<Place>,
+    pub causals: Vec<Causal>
+}
+
+impl Trash {
+    pub fn read(user: &User, since: DateTime<Utc>, conn: &PgConnection) -> QueryResult<Trash> {
+        let accounts = account_user::table
+            .filter(account_user::id_user.eq(user.id))
+            .select(account_user::id_account)
+            .load::<i64>(conn)?;
+        Ok(Trash {
+            accounts: account::table
+                .filter(account::id.eq_any(&accounts))
+                .filter(account::deleted_at.gt(since))
+                .order(account::deleted_at.desc())
+                .load::<Account>(conn)?,
+            transactions: transaction::table
+                .filter(transaction::id_account.eq_any(&accounts))
+                .filter(transaction::deleted_at.gt(since))
+                .order(transaction::deleted_at.desc())
+                .load::<Transaction>(conn)?,
+            giros: giro::table
+                .filter(giro::id_source_account.eq_any(&accounts).or(giro::id_destination_account.eq_any(&accounts)))
+                .filter(giro::deleted_at.gt(since))
+                .order(giro::deleted_at.desc())
+                .load::<Giro>(conn)?,
+            places: place::table
+                .filter(place::id_user.eq(user.id))
+                .filter(place::deleted_at.gt(since))
+                .order(place::deleted_at.desc())
+                .load::<Place>(conn)?,
+            causals: causal::table
+                .filter(causal::id_user.eq(user.id))
+                .filter(causal::deleted_at.gt(since))
+                .order(causal::deleted_at.desc())
+                .load::<Causal>(conn)?
+        })
+    }
+}
+
+///
+/// Delete for good what is in the trash since before the date, the number of rows is returned.
+/// A causal still used by a transaction stays in the trash.
+pub fn purge(before: DateTime<Utc>, conn: &PgConnection) -> QueryResult<usize> {
+    conn.transaction::<usize, Error, _>(|| {
+        let mut n = 0;
+        let transactions = transaction::table
+            .filter(transaction::deleted_at.lt(before))
+            .select(transaction::id);
+        diesel::delete(transaction_detail::table.filter(transaction_detail::id_transaction.eq_any(transactions)))
+            .execute(conn)?;
+        n += diesel::delete(transaction::table.filter(transaction::deleted_at.lt(before)))
+            .execute(conn)?;
+        n += diesel::delete(giro::table.filter(giro::deleted_at.lt(before)))
+            .execute(conn)?;
+        let accounts = account::table
+            .filter(account::deleted_at.lt(before))
+            .load::<Account>(conn)?;
+        for account in &accounts {
+            n += Account::purge(account, conn)?;
+        }
+        let places = place::table
+            .filter(place::deleted_at.lt(before))
+            .select(place::id.nullable());
+        diesel::update(transaction::table.filter(transaction::id_place.eq_any(places)))
+            .set(transaction::id_place.eq(None::<i64>))
+            .execute(conn)?;
+        n += diesel::delete(place::table.filter(place::deleted_at.lt(before)))
+            .execute(conn)?;
+        let causals = causal::table
+            .filter(causal::deleted_at.lt(before))
+            .select(causal::id)
+            .load::<i64>(conn)?;
+        for id_causal in causals {
+            let used = transaction::table
+                .filter(transaction::id_causal.eq(id_causal))
+                .count()
+                .get_result::<i64>(conn)?;
+            if used == 0 {
+                n += diesel::delete(causal::table.find(id_causal))
+                    .execute(conn)?;
+            }
+        }
+        Ok(n)
+    }).map_err(|e| { error!("Can not purge the trash: {}", e); e })
+}
@@ -0,0 +1,284 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+use serde::{Serialize, Deserialize};
+
+use crate::user::model::{User, UserForm};
+use crate::auth::model::Auth;
+use crate::auth::auth;
+use crate::account::model::{Account, AccountForm, AccountUser};
+use crate::causal::model::{Causal, CausalForm};
+use crate::place::model::{Place, PlaceForm};
+use crate::detail::model::{Detail, DetailForm};
+use crate::transaction::model::{Transaction, TransactionForm, TransactionDetail};
+use crate::giro::model::{Giro, GiroForm};
+
+const ARCHIVE_VERSION: u32 = 1;
+
+///
+/// Everything that belongs to a user, with the ids of the database it comes from.
+#[derive(Debug,Serialize,Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub email: String,
+    pub user: User,
+    pub accounts: Vec<Account>,
+    pub causals: Vec<Causal>,
+    pub places: Vec<Place>,
+    pub details: Vec<Detail>,
+    pub transactions: Vec<Transaction>,
+    pub transaction_details: Vec<TransactionDetail>,
+    pub giros: Vec<Giro>
+}
+
+///
+/// Collect the data of the user. The causals, places and details of other users used
+/// by the transactions of the shared accounts are included, otherwise they could not be imported.
+pub fn export(user: &User, conn: &PgConnection) -> QueryResult<Archive> {
+    let email = Auth::read_by_user(user, conn)?.email;
+    let accounts = Account::read_by_user(user, conn)?;
+    let mut transactions = Vec::new();
+    let mut transaction_details = Vec::new();
+    let mut giros: Vec<Giro> = Vec::new();
+    for account in &accounts {
+        for t in Transaction::read_by_account(account, conn, 0, i64::max_value())? {
+            transaction_details.extend(TransactionDetail::read_by_transaction(conn, &t)?);
+            transactions.push(t);
+        }
+        for g in Giro::read_by_source(account, conn)?.into_iter().chain(Giro::read_by_destination(account, conn)?) {
+            if !giros.iter().any(|x| x.id == g.id) {
+                giros.push(g);
+            }
+        }
+    }
+    let mut causals = Causal::read_for_user(user, conn)?;
+    causals.retain(|c| c.id_user == Some(user.id));
+    for t in &transactions {
+        if !causals.iter().any(|c| c.id == t.id_causal) {
+            causals.push(Causal::read_any_by_id(t.id_causal, conn)?);
+        }
+    }
+    let mut places = Place::read_by_user(user, conn)?;
+    for id_place in transactions.iter().filter_map(|t| t.id_place) {
+        if !places.iter().any(|p| p.id == id_place) {
+            places.push(Place::read_any_by_id(id_place, conn)?);
+        }
+    }
+    let mut details = Detail::read_by_user(user, conn)?;
+    for td in &transaction_details {
+        if !details.iter().any(|d| d.id == td.id_detail) {
+            details.push(Detail::read_by_id(td.id_detail, conn)?);
+        }
+    }
+    Ok(Archive {
+        version: ARCHIVE_VERSION,
+        email,
+        user: User::read_by_id(user.id, conn)?,
+        accounts,
+        causals,
+        places,
+        details,
+        transactions,
+        transaction_details,
+        giros
+    })
+}
+
+///
+/// Create a new user from an archive, in a single transaction; every row gets a new id.
+/// The password is random: the user has to reset it.
+/// The global causals, places and details are reused when they still exist,
+/// the giros towards accounts that are not in the archive are skipped.
+pub fn import(archive: &Archive, conn: &PgConnection) -> Result<User, String> {
+    if archive.version != ARCHIVE_VERSION {
+        return Err(format!("archive version {} not supported", archive.version));
+    }
+    match Auth::read_by_email(&archive.email, conn) {
+        Ok(_) => return Err(format!("the email {} is already registered", archive.email)),
+        Err(Error::NotFound) => (),
+        Err(e) => return Err(e.to_string())
+    }
+    conn.transaction::<User, Error, _>(|| {
+        let user = import_user(archive, conn)?;
+        let accounts = import_accounts(archive, &user, conn)?;
+        let causals = import_causals(archive, &user, conn)?;
+        let places = import_places(archive, &user, conn)?;
+        let details = import_details(archive, &user, conn)?;
+        let mut transactions = HashMap::new();
+        for t in &archive.transactions {
+            let form = TransactionForm {
+                id_account: mapped(&accounts, t.id_account)?,
+                id_transaction_type: t.id_transaction_type,
+                id_place: match t.id_place {
+                    Some(id) => Some(mapped(&places, id)?),
+                    None => None
+                },
+                id_beneficiary: t.id_beneficiary.and_then(|id| accounts.get(&id).cloned()),
+                note: t.note.as_ref().map(|s| s.as_str()),
+                amount: t.amount,
+                data: t.data,
+                id_currency: t.id_currency,
+                expense: t.expense,
+                id_causal: mapped(&causals, t.id_causal)?,
+                cleared: t.cleared
+            };
+            transactions.insert(t.id, Transaction::create_directed(&form, t.direction, conn)?.id);
+        }
+        for td in &archive.transaction_details {
+            let form = TransactionDetail {
+                id_detail: mapped(&details, td.id_detail)?,
+                id_transaction: mapped(&transactions, td.id_transaction)?,
+                amount: td.amount
+            };
+            if !TransactionDetail::create(&form, conn) {
+                return Err(Error::RollbackTransaction);
+            }
+        }
+        for g in &archive.giros {
+            match (g.id_source_account.and_then(|id| accounts.get(&id)),
+                   g.id_destination_account.and_then(|id| accounts.get(&id))) {
+                (Some(source), Some(destination)) => {
+                    let form = GiroForm {
+                        id_source_account: *source,
+                        id_destination_account: *destination,
+                        data: g.data,
+                        note: g.note.as_ref().map(|s| s.as_str()),
+                        amount: g.amount,
+                        expense: g.expense,
+                        id_currency: g.id_currency,
+                        cleared_source: g.cleared_source,
+                        cleared_destination: g.cleared_destination
+                    };
+                    Giro::create(&form, conn)?;
+                },
+                _ => warn!("giro {} skipped, one of its accounts is not in the archive", g.id)
+            }
+        }
+        info!("The user {} has been imported as {}", archive.user.id, user.id);
+        Ok(user)
+    }).map_err(|e| e.to_string())
+}
+
+// #################################################################################################
+
+fn import_user(archive: &Archive, conn: &PgConnection) -> QueryResult<User> {
+    let u = &archive.user;
+    let form = UserForm {
+        name: u.name.clone(),
+        surname: u.surname.clone(),
+        phone: u.phone.clone(),
+        country: u.country.clone(),
+        address: u.address.clone(),
+        birthdate: u.birthdate,
+        note: u.note.clone()
+    };
+    let user = User::create(&form, conn)?;
+    let (password, _) = auth::create_random_token();
+    let auth = auth::create_auth(&archive.email, &password, None, user.id)
+        .map_err(|()| Error::RollbackTransaction)?;
+    Auth::create(&auth, conn)?;
+    Ok(user)
+}
+
+fn import_accounts(archive: &Archive, user: &User, conn: &PgConnection) -> QueryResult<HashMap<i64, i64>> {
+    let mut ids = HashMap::new();
+    for a in &archive.accounts {
+        let form = AccountForm {
+            name: &a.name,
+            status: a.status,
+            note: a.note.as_ref().map(|s| s.as_str()),
+            current_balance: a.current_balance,
+            initial_balance: a.initial_balance,
+            creation_date: a.creation_date,
+            id_account_type: a.id_account_type,
+            id_currency: a.id_currency
+        };
+        let account = Account::create(&form, conn)?;
+        AccountUser::create(&AccountUser { id_account: account.id, id_user: user.id }, conn)?;
+        ids.insert(a.id, account.id);
+    }
+    Ok(ids)
+}
+
+fn import_causals(archive: &Archive, user: &User, conn: &PgConnection) -> QueryResult<HashMap<i64, i64>> {
+    let mut ids = HashMap::new();
+    for c in &archive.causals {
+        if c.id_user.is_none() && is_global(Causal::read_by_id(c.id, conn).map(|c| c.id_user))? {
+            ids.insert(c.id, c.id);
+            continue;
+        }
+        let form = CausalForm { description: &c.description, id_user: Some(user.id) };
+        ids.insert(c.id, Causal::create(&form, conn)?.id);
+    }
+    Ok(ids)
+}
+
+fn import_places(archive: &Archive, user: &User, conn: &PgConnection) -> QueryResult<HashMap<i64, i64>> {
+    let mut ids = HashMap::new();
+    for p in &archive.places {
+        if p.id_user.is_none() && is_global(Place::read_by_id(p.id, conn).map(|p| p.id_user))? {
+            ids.insert(p.id, p.id);
+            continue;
+        }
+        let form = PlaceForm {
+            name: &p.name,
+            address: p.address.clone(),
+            country: p.country.clone(),
+            email: p.email.clone(),
+            website: p.website.clone(),
+            phone: p.phone.clone(),
+            note: p.note.clone(),
+            id_user: Some(user.id)
+        };
+        ids.insert(p.id, Place::create(&form, conn)?.id);
+    }
+    Ok(ids)
+}
+
+fn import_details(archive: &Archive, user: &User, conn: &PgConnection) -> QueryResult<HashMap<i64, i64>> {
+    let mut ids = HashMap::new();
+    for d in &archive.details {
+        if d.id_user.is_none() && is_global(Detail::read_by_id(d.id, conn).map(|d| d.id_user))? {
+            ids.insert(d.id, d.id);
+            continue;
+        }
+        let form = DetailForm { description: &d.description, id_user: Some(user.id) };
+        ids.insert(d.id, Detail::create(&form, conn)?.id);
+    }
+    Ok(ids)
+}
+
+///
+/// A reference to a row that is not in the archive: the archive is broken.
+fn mapped(ids: &HashMap<i64, i64>, id: i64) -> QueryResult<i64> {
+    ids.get(&id).cloned().ok_or_else(|| {
+        error!("the row {} is referenced but it is not in the archive", id);
+        Error::NotFound
+    })
+}
+
+fn is_global(owner: QueryResult<Option<i64>>) -> QueryResult<bool> {
+    match owner {
+        Ok(id_user) => Ok(id_user.is_none()),
+        Err(Error::NotFound) => Ok(false),
+        Err(e) => Err(e)
+    }
+}
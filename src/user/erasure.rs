@@ -0,0 +1,161 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel;
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::result::Error;
+
+use crate::schema::{user, auth, access_token, password_reset, account_user, transaction,
+                    transaction_detail, place, detail, causal};
+use crate::account::model::{Account, AccountUser};
+use crate::user::model::User;
+use crate::audit::model::AuditLog;
+use crate::idempotency::model::StoredResponse;
+use crate::rule::model::Rule;
+use crate::causal::model::{Causal, CausalForm};
+
+///
+/// Erase everything that belongs to the user (GDPR), in a single transaction.
+/// The accounts shared with other users are kept, only the link with the user is removed;
+/// the causals used by the transactions of those accounts pass to the other owners.
+pub fn erase(user: &User, conn: &PgConnection) -> QueryResult<()> {
+    conn.transaction::<(), Error, _>(|| {
+        for au in AccountUser::read_by_user(conn, user)? {
+            // also the accounts in the trash
+            let account = Account::read_any_by_id(au.id_account, conn)?;
+            if AccountUser::count_by_account(account.id, conn)? > 1 {
+                debug!("account {} is shared, only the link is removed", account.id);
+                // the other users see who left the account
+                AuditLog::deleted(user, &au, conn)?;
+                AccountUser::delete(conn, user, &account)?;
+            } else {
+                AuditLog::delete_by_account(account.id, conn)?;
+                Account::purge(&account, conn)?;
+            }
+        }
+        Rule::delete_by_user(user, conn)?;
+        erase_places(user, conn)?;
+        erase_details(user, conn)?;
+        erase_causals(user, conn)?;
+        diesel::delete(access_token::table.filter(access_token::id_user.eq(user.id)))
+            .execute(conn)?;
+        StoredResponse::delete_by_user(user, conn)?;
+        diesel::delete(password_reset::table.filter(password_reset::id_user.eq(user.id)))
+            .execute(conn)?;
+        diesel::delete(auth::table.find(user.id))
+            .execute(conn)?;
+        diesel::delete(user::table.find(user.id))
+            .execute(conn)?;
+        info!("The user {} has been erased", user.id);
+        Ok(())
+    }).map_err(|e| { error!("Can not erase user {}: {}", user.id, e); e })
+}
+
+///
+/// Erase the users whose grace period is over.
+pub fn purge_scheduled(conn: &PgConnection) -> QueryResult<usize> {
+    let users = User::read_due_for_deletion(conn)?;
+    let mut n = 0;
+    for user in users {
+        // a failure must not block the other users
+        if erase(&user, conn).is_ok() {
+            n += 1;
+        }
+    }
+    Ok(n)
+}
+
+// #################################################################################################
+
+fn erase_places(user: &User, conn: &PgConnection) -> QueryResult<usize> {
+    let places = place::table
+        .filter(place::id_user.eq(user.id))
+        .select(place::id.nullable());
+    diesel::update(transaction::table.filter(transaction::id_place.eq_any(places)))
+        .set(transaction::id_place.eq(None::<i64>))
+        .execute(conn)?;
+    diesel::delete(place::table.filter(place::id_user.eq(user.id)))
+        .execute(conn)
+}
+
+fn erase_details(user: &User, conn: &PgConnection) -> QueryResult<usize> {
+    let details = detail::table
+        .filter(detail::id_user.eq(user.id))
+        .select(detail::id);
+    diesel::delete(transaction_detail::table.filter(transaction_detail::id_detail.eq_any(details)))
+        .execute(conn)?;
+    diesel::delete(detail::table.filter(detail::id_user.eq(user.id)))
+        .execute(conn)
+}
+
+fn erase_causals(user: &User, conn: &PgConnection) -> QueryResult<()> {
+    let causals = causal::table
+        .filter(causal::id_user.eq(user.id))
+        .load::<Causal>(conn)?;
+    for causal in causals {
+        // the transactions still here belong to accounts shared with other users
+        let accounts = transaction::table
+            .filter(transaction::id_causal.eq(causal.id))
+            .select(transaction::id_account)
+            .distinct()
+            .order(transaction::id_account)
+            .load::<i64>(conn)?;
+        if accounts.is_empty() {
+            diesel::delete(causal::table.find(causal.id))
+                .execute(conn)?;
+            continue;
+        }
+        // an owner of each account gets the causal, the first one keeps it and the others have a copy
+        let mut copies: Vec<(i64, i64)> = Vec::new();
+        for id_account in accounts {
+            let owners = account_user::table
+                .filter(account_user::id_account.eq(id_account))
+                .filter(account_user::id_user.ne(user.id))
+                .select(account_user::id_user)
+                .order(account_user::id_user)
+                .load::<i64>(conn)?;
+            let copy = copies.iter().find(|(id_user, _)| owners.contains(id_user)).cloned();
+            let id_causal = match (copy, owners.first()) {
+                (Some((_, id_causal)), _) => id_causal,
+                (None, Some(&id_user)) if copies.is_empty() => {
+                    diesel::update(causal::table.find(causal.id))
+                        .set(causal::id_user.eq(id_user))
+                        .execute(conn)?;
+                    copies.push((id_user, causal.id));
+                    causal.id
+                },
+                (None, Some(&id_user)) => {
+                    let form = CausalForm { description: &causal.description, id_user: Some(id_user) };
+                    let id_causal = Causal::create(&form, conn)?.id;
+                    copies.push((id_user, id_causal));
+                    id_causal
+                },
+                // only the shared accounts are left
+                (None, None) => return Err(Error::NotFound)
+            };
+            if id_causal != causal.id {
+                diesel::update(transaction::table
+                    .filter(transaction::id_account.eq(id_account))
+                    .filter(transaction::id_causal.eq(causal.id)))
+                    .set(transaction::id_causal.eq(id_causal))
+                    .execute(conn)?;
+            }
+        }
+    }
+    Ok(())
+}
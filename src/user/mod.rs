@@ -16,19 +16,26 @@
 */
 
 use serde::Deserialize;
-use rocket_contrib::json::Json;
+use schemars::JsonSchema;
+use rocket_contrib::json::{Json, JsonValue};
 use rocket::http::Status;
+use rocket::State;
 use rocket::response::status::Custom;
-use chrono::NaiveDate;
+use chrono::{Duration, NaiveDate, Utc};
 
 use crate::database::MoneyManagerDB;
+use crate::error::ApiError;
 use crate::base_model::BaseModel;
+use crate::base_controller::BaseController;
+use crate::settings::Settings;
 use crate::user::model::{User, UserForm};
 
 pub mod model;
+pub mod erasure;
+pub mod archive;
 
-#[derive(Debug,Deserialize)]
-struct UserJSON<'a> {
+#[derive(Debug,Deserialize,JsonSchema)]
+pub(crate) struct UserJSON<'a> {
     pub name: &'a str,
     pub surname: &'a str,
     pub phone: Option<&'a str>,
@@ -39,7 +46,7 @@ struct UserJSON<'a> {
 }
 
 #[post("/", data = "<user>", format = "application/json")]
-fn create(conn: MoneyManagerDB, user: Json<UserJSON>) -> Result<Json<User>, Status> {
+fn create(conn: MoneyManagerDB, user: Json<UserJSON>) -> Result<Json<User>, ApiError> {
     debug!("CREATE_USER_REQUEST");
     let insert = UserForm {
         name: user.name.to_string(),
@@ -50,6 +57,7 @@ fn create(conn: MoneyManagerDB, user: Json<UserJSON>) -> Result<Json<User>, Stat
         birthdate: user.birthdate,
         note: user.note.map(|n| n.to_string())
     };
+    insert.validate()?;
     User::create(&insert, &conn)
         .map(|u| {
             info!("user create successfully {}", u.id);
@@ -57,14 +65,14 @@ fn create(conn: MoneyManagerDB, user: Json<UserJSON>) -> Result<Json<User>, Stat
         })
         .map_err(|e| {
             error!("Can not create user caused by {}", e);
-            Status::InternalServerError
+            ApiError::internal()
         })
 }
 
 /* DISABLED FOR SECURITY REASON */
 #[allow(dead_code)]
 #[get("/")]
-fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<User>>, Custom<String>> {
+fn read(conn: MoneyManagerDB, _user: User) -> Result<Json<Vec<User>>, ApiError> {
     debug!("READ_USER_REQUEST");
     let result = User::read(&conn);
     User::unpack(result)
@@ -93,7 +101,7 @@ fn read_for_user(user: User) -> Json<User> {
 }
 
 #[put("/user", data = "<user_json>")]
-fn update_for_user(conn: MoneyManagerDB, user_json: Json<UserJSON>, user: User) -> Status {
+fn update_for_user(conn: MoneyManagerDB, user_json: Json<UserJSON>, user: User) -> Result<Status, ApiError> {
     debug!("UPDATE_FOR_USER_REQUEST");
     let update = UserForm {
         name: user_json.name.to_string(),
@@ -104,29 +112,51 @@ fn update_for_user(conn: MoneyManagerDB, user_json: Json<UserJSON>, user: User)
         birthdate: user_json.birthdate,
         note: user_json.note.map(|n| n.to_string())
     };
+    update.validate()?;
     if User::update(user.id, &update, &conn) {
         info!("The user {} has updated his user private data!", user.id);
-        Status::NoContent
+        Ok(Status::NoContent)
     } else {
         warn!("The user attempts to update user private data but an error occurred!");
-        Status::InternalServerError
+        Err(ApiError::internal())
     }
 }
 
 #[delete("/user")]
-fn delete_for_user(conn: MoneyManagerDB, user: User) -> Status {
+fn delete_for_user(conn: MoneyManagerDB, user: User, settings: State<Settings>) -> Result<Custom<JsonValue>, ApiError> {
     debug!("DELETE_FOR_USER_REQUEST");
-    if User::delete(user.id, &conn) {
-        info!("The user {} has deleted his user private data!", user.id);
-        Status::NoContent
-    } else {
-        warn!("The user attempts to delete user private data but an error occurred!");
-        Status::InternalServerError
+    if settings.deletion_grace_days == 0 {
+        erasure::erase(&user, &conn).map_err(|_| ApiError::internal())?;
+        info!("The user {} has erased all his data!", user.id);
+        return Ok(Custom(Status::Ok, json!({ "deletion_date": Utc::now() })));
+    }
+    // the data are erased at the end of the grace period, the user can still change his mind
+    let date = Utc::now() + Duration::days(settings.deletion_grace_days);
+    User::update_deletion_date(user.id, Some(date), &conn)
+        .map(|_| {
+            info!("The user {} has scheduled the deletion of his data at {}", user.id, date);
+            Custom(Status::Accepted, json!({ "deletion_date": date }))
+        })
+        .map_err(|e| {
+            error!("Can not schedule the deletion of user {}: {}", user.id, e);
+            ApiError::internal()
+        })
+}
+
+#[delete("/user/deletion")]
+fn cancel_deletion_for_user(conn: MoneyManagerDB, user: User) -> Result<Status, ApiError> {
+    debug!("CANCEL_DELETION_FOR_USER_REQUEST");
+    if user.deletion_date.is_none() {
+        warn!("The user {} has not scheduled a deletion!", user.id);
+        return Err(ApiError::new(Status::NotFound, "not_found", "No deletion is scheduled"));
     }
+    let result = User::update_deletion_date(user.id, None, &conn);
+    info!("The user {} has cancelled the deletion of his data", user.id);
+    User::finalize_update_delete(result)
 }
 
 ///
 ///
 pub fn mount(rocket: rocket::Rocket) -> rocket::Rocket {
-    rocket.mount("/user", routes![read_for_user, create, update_for_user, delete_for_user])
+    rocket.mount("/user", routes![read_for_user, create, update_for_user, delete_for_user, cancel_deletion_for_user])
 }
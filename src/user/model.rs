@@ -17,14 +17,20 @@
 
 use diesel;
 use diesel::prelude::*;
-use chrono::NaiveDate;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 
 use crate::schema::user;
-use crate::database::MoneyManagerDB;
+use crate::validation::Validator;
+use crate::error::ApiError;
+
+pub const ROLE_USER: &str = "user";
+pub const ROLE_ADMIN: &str = "admin";
 
 #[table_name = "user"]
-#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable)]
+#[derive(Debug,Serialize,Deserialize,Queryable,Identifiable,JsonSchema)]
 pub struct User {
     pub id: i64,
     pub name: String,
@@ -33,7 +39,10 @@ pub struct User {
     pub country: Option<String>,
     pub address: Option<String>,
     pub birthdate: Option<NaiveDate>,
-    pub note: Option<String>
+    pub note: Option<String>,
+    pub role: String,
+    pub disabled: bool,
+    pub deletion_date: Option<DateTime<Utc>>
 }
 
 // only for insert and update
@@ -50,24 +59,60 @@ pub struct UserForm {
 }
 
 impl User {
-    pub fn create(user: &UserForm, conn: &MoneyManagerDB) -> QueryResult<User> {
+    pub fn create(user: &UserForm, conn: &PgConnection) -> QueryResult<User> {
         diesel::insert_into(user::table)
             .values(user)
-            .get_result::<User>(&*(*conn))
+            .get_result::<User>(conn)
     }
-    pub fn read(conn: &MoneyManagerDB) -> QueryResult<Vec<User>> {
-        user::table.load::<User>(&**conn)
+    pub fn read(conn: &PgConnection) -> QueryResult<Vec<User>> {
+        user::table.load::<User>(conn)
     }
-    pub fn read_by_id(id: i64, conn: &MoneyManagerDB) -> QueryResult<User> {
-        user::table.find(id).first::<User>(&*(*conn))
+    pub fn read_by_id(id: i64, conn: &PgConnection) -> QueryResult<User> {
+        user::table.find(id).first::<User>(conn)
     }
-    pub fn update(id: i64, user: &UserForm, conn: &MoneyManagerDB) -> bool {
+    pub fn update(id: i64, user: &UserForm, conn: &PgConnection) -> bool {
         diesel::update(user::table.find(id))
             .set(user)
-            .execute(&*(*conn)).is_ok()
+            .execute(conn).is_ok()
+    }
+    pub fn update_role(id: i64, role: &str, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(user::table.find(id))
+            .set(user::role.eq(role))
+            .execute(conn)
+    }
+    pub fn update_disabled(id: i64, disabled: bool, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(user::table.find(id))
+            .set(user::disabled.eq(disabled))
+            .execute(conn)
     }
-    pub fn delete(id: i64, conn: &MoneyManagerDB) -> bool {
-        diesel::delete(user::table.find(id))
-            .execute(&*(*conn)).is_ok()
+    ///
+    /// The user will be erased at the given date, unless the deletion is cancelled.
+    pub fn update_deletion_date(id: i64, date: Option<DateTime<Utc>>, conn: &PgConnection) -> QueryResult<usize> {
+        diesel::update(user::table.find(id))
+            .set(user::deletion_date.eq(date))
+            .execute(conn)
+    }
+    pub fn read_due_for_deletion(conn: &PgConnection) -> QueryResult<Vec<User>> {
+        user::table
+            .filter(user::deletion_date.le(Utc::now()))
+            .load::<User>(conn)
+    }
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN
+    }
+}
+
+impl UserForm {
+    ///
+    /// The user does not exist yet on registration, there is nothing to read.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.not_blank("name", &self.name, 32);
+        v.not_blank("surname", &self.surname, 32);
+        v.max_length("phone", self.phone.as_ref().map(|s| s.as_str()), 16);
+        v.max_length("country", self.country.as_ref().map(|s| s.as_str()), 64);
+        v.max_length("address", self.address.as_ref().map(|s| s.as_str()), 128);
+        v.max_length("note", self.note.as_ref().map(|s| s.as_str()), 255);
+        v.finish()
     }
 }
@@ -0,0 +1,212 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use diesel::result::Error;
+use diesel::pg::PgConnection;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+use crate::error::{ApiError, FieldError};
+use crate::user::model::User;
+use crate::account::model::AccountUser;
+
+///
+/// A form that can be checked before it is written.
+/// The references (currency, type, causal...) are read with the connection of the request:
+/// a missing reference is a field error (422), a reference to the data of another user is 403.
+pub trait Validate {
+    fn validate(&self, user: &User, conn: &PgConnection) -> Result<(), ApiError>;
+}
+
+const MIN_PASSWORD: usize = 8;
+const MAX_PASSWORD: usize = 128;
+
+///
+/// Collect the errors of the fields, so the client receives all of them at once.
+pub struct Validator {
+    errors: Vec<FieldError>
+}
+
+impl Validator {
+    pub fn new() -> Validator {
+        Validator {
+            errors: Vec::new()
+        }
+    }
+
+    pub fn error(&mut self, field: &str, message: &str) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.to_string()
+        });
+    }
+
+    pub fn check(&mut self, valid: bool, field: &str, message: &str) {
+        if !valid {
+            self.error(field, message);
+        }
+    }
+
+    pub fn not_blank(&mut self, field: &str, value: &str, max: usize) {
+        if value.trim().is_empty() {
+            self.error(field, "must not be empty");
+        } else {
+            self.max_length(field, Some(value), max);
+        }
+    }
+
+    pub fn max_length(&mut self, field: &str, value: Option<&str>, max: usize) {
+        if let Some(value) = value {
+            if value.chars().count() > max {
+                self.error(field, &format!("must be at most {} characters", max));
+            }
+        }
+    }
+
+    pub fn positive(&mut self, field: &str, value: f64) {
+        self.check(value.is_finite() && value > 0.0, field, "must be greater than zero");
+    }
+
+    pub fn not_negative(&mut self, field: &str, value: Option<f64>) {
+        if let Some(value) = value {
+            self.check(value.is_finite() && value >= 0.0, field, "must not be negative");
+        }
+    }
+
+    pub fn password(&mut self, field: &str, value: &str) {
+        let length = value.chars().count();
+        self.check(length >= MIN_PASSWORD, field, &format!("must be at least {} characters", MIN_PASSWORD));
+        self.check(length <= MAX_PASSWORD, field, &format!("must be at most {} characters", MAX_PASSWORD));
+    }
+
+    pub fn email(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.check(is_email(value), field, "is not a valid email");
+        }
+    }
+
+    pub fn url(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.check(is_url(value), field, "is not a valid URL");
+        }
+    }
+
+    ///
+    /// A date of the query string: an instant (RFC 3339) or a day (YYYY-MM-DD) in UTC,
+    /// that stands for its first instant or, with end_of_day, for its last one.
+    pub fn date(&mut self, field: &str, value: Option<&str>, end_of_day: bool) -> Option<DateTime<Utc>> {
+        let value = value?;
+        if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+            return Some(instant.with_timezone(&Utc));
+        }
+        match NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|day| day.and_hms_opt(0, 0, 0)) {
+            Ok(Some(midnight)) => {
+                let start = Utc.from_utc_datetime(&midnight);
+                // the database keeps the microseconds
+                Some(if end_of_day { start + Duration::days(1) - Duration::microseconds(1) } else { start })
+            },
+            _ => {
+                self.error(field, "must be a date (YYYY-MM-DD) or a date-time (RFC 3339)");
+                None
+            }
+        }
+    }
+
+    ///
+    /// A referenced entity must exist; the database errors are not field errors.
+    pub fn reference<T>(&mut self, field: &str, result: Result<T, Error>) -> Result<Option<T>, ApiError> {
+        match result {
+            Ok(t) => Ok(Some(t)),
+            Err(Error::NotFound) => {
+                self.error(field, "does not exist");
+                Ok(None)
+            },
+            Err(e) => {
+                error!("Can not validate {}: {}", field, e);
+                Err(ApiError::internal())
+            }
+        }
+    }
+
+    ///
+    /// A user-scoped entity can be referenced only if it is global or it belongs to the user.
+    /// It exists, so it is not a field error: the user is not allowed to use it.
+    pub fn owner(&self, entity: &str, id_user: Option<i64>, user: &User) -> Result<(), ApiError> {
+        if id_user.is_some() && id_user != Some(user.id) {
+            warn!("The user {} references {} that does not belong to it!", user.id, entity);
+            Err(ApiError::forbidden(entity))
+        } else {
+            Ok(())
+        }
+    }
+
+    ///
+    /// An account can be referenced only by its users.
+    pub fn account(&self, id_account: i64, user: &User, conn: &PgConnection) -> Result<(), ApiError> {
+        match AccountUser::read_for_check(conn, user, id_account) {
+            Ok(_) => Ok(()),
+            Err(Error::NotFound) => {
+                warn!("The user {} references account {} that does not belong to it!", user.id, id_account);
+                Err(ApiError::forbidden("account"))
+            },
+            Err(e) => {
+                error!("Can not check account {}: {}", id_account, e);
+                Err(ApiError::internal())
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            debug!("validation failed: {:?}", self.errors);
+            Err(ApiError::unprocessable(self.errors))
+        }
+    }
+}
+
+// #################################################################################################
+
+fn is_email(value: &str) -> bool {
+    let mut parts = value.splitn(2, '@');
+    match (parts.next(), parts.next()) {
+        (Some(local), Some(domain)) => {
+            !local.is_empty() && !domain.contains('@') && is_host(domain)
+                && !value.chars().any(char::is_whitespace)
+        },
+        _ => false
+    }
+}
+
+fn is_url(value: &str) -> bool {
+    let rest = if value.starts_with("http://") {
+        &value[7..]
+    } else if value.starts_with("https://") {
+        &value[8..]
+    } else {
+        return false;
+    };
+    let host = rest.split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+    let host = host.rsplitn(2, ':').last().unwrap_or("");
+    is_host(host) && !value.chars().any(char::is_whitespace)
+}
+
+fn is_host(value: &str) -> bool {
+    value.contains('.') && value.split('.').all(|label| {
+        !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    })
+}
@@ -0,0 +1,92 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::{Session, TestApp};
+
+///
+/// A session that uses a new personal access token of the user.
+fn access_token(app: &TestApp, session: &Session, scopes: serde_json::Value) -> Session {
+    let reply = app.post("/auth/tokens", session, json!({ "name": "script", "scopes": scopes, "expiration_date": null }));
+    assert_eq!(reply.status, Status::Ok, "can not create the access token: {}", reply.body);
+    Session { id: session.id, email: session.email.clone(), token: reply.body["token"].as_str().unwrap().to_string() }
+}
+
+#[test]
+fn the_write_scope_can_not_erase_the_account() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let script = access_token(&app, &alice, json!(["write"]));
+    assert_eq!(app.delete("/user/user", &script).status, Status::Forbidden);
+    assert_eq!(app.put("/user/user", &script, json!({ "name": "Mallory", "surname": "Mallory" })).status, Status::Forbidden);
+    assert_eq!(app.get("/user/user", &alice).body["name"], "Test");
+}
+
+#[test]
+fn a_resource_scope_writes_only_its_resource() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let causal = app.causal(&alice, "Groceries");
+    let rules = access_token(&app, &alice, json!(["rules:write"]));
+    let rule = json!({ "name": "Supermarket", "note_regex": "market", "set_causal": causal });
+    assert_eq!(app.post("/rule", &rules, rule.clone()).status, Status::Ok);
+    assert_eq!(app.post("/causal", &rules, json!({ "description": "Rent" })).status, Status::Forbidden);
+
+    let accounts = access_token(&app, &alice, json!(["accounts:write"]));
+    assert_eq!(app.post("/rule", &accounts, rule).status, Status::Forbidden);
+}
+
+#[test]
+fn the_read_scope_can_not_write() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reader = access_token(&app, &alice, json!(["read"]));
+    let reply = app.get("/causal/user", &reader);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body, json!([]));
+    assert_eq!(app.post("/causal", &reader, json!({ "description": "Rent" })).status, Status::Forbidden);
+    // the tokens are managed only with the password
+    assert_eq!(app.get("/auth/tokens", &reader).status, Status::Forbidden);
+}
+
+#[test]
+fn an_unknown_scope_is_refused() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.post("/auth/tokens", &alice, json!({ "name": "script", "scopes": ["users:write"], "expiration_date": null }));
+    assert_eq!(reply.status, Status::BadRequest);
+}
+
+#[test]
+fn the_name_and_the_expiration_are_validated() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.post("/auth/tokens", &alice, json!({
+        "name": " ",
+        "scopes": ["read"],
+        "expiration_date": "2019-09-19T00:00:00Z"
+    }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["name", "expiration_date"]);
+
+    let reply = app.post("/auth/tokens", &alice, json!({ "name": "x".repeat(65), "scopes": ["read"], "expiration_date": null }));
+    assert_eq!(common::fields(&reply), vec!["name"]);
+}
@@ -0,0 +1,95 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::EUR;
+
+#[test]
+fn the_owner_reads_updates_and_deletes_the_account() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let id = app.account(&alice, "Wallet", EUR);
+    let reply = app.get(&format!("/account/{}", id), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["name"], "Wallet");
+    let reply = app.put(&format!("/account/{}", id), &alice, json!({
+        "name": "Bank",
+        "status": true,
+        "note": null,
+        "current_balance": 0.0,
+        "initial_balance": 10.0,
+        "creation_date": "2019-09-19T00:00:00Z",
+        "id_account_type": 1,
+        "id_currency": EUR
+    }));
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(app.get(&format!("/account/{}", id), &alice).body["name"], "Bank");
+    assert_eq!(app.delete(&format!("/account/{}", id), &alice).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/account/{}", id), &alice).status, Status::NotFound);
+}
+
+#[test]
+fn the_account_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let id = app.account(&alice, "Wallet", EUR);
+    let reply = app.get(&format!("/account/{}", id), &bob);
+    assert_eq!(reply.status, Status::Forbidden);
+    assert_eq!(reply.body["code"], "forbidden");
+    assert_eq!(app.delete(&format!("/account/{}", id), &bob).status, Status::Forbidden);
+    assert_eq!(app.get(&format!("/account/{}", id), &alice).status, Status::Ok);
+}
+
+#[test]
+fn a_user_lists_only_its_accounts() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let id = app.account(&alice, "Wallet", EUR);
+    app.account(&bob, "Bank", EUR);
+    let reply = app.get("/account/user", &alice);
+    assert_eq!(reply.status, Status::Ok);
+    let accounts = reply.body.as_array().unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0]["id"].as_i64(), Some(id));
+}
+
+#[test]
+fn an_invalid_account_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.post("/account", &alice, json!({
+        "name": " ",
+        "status": true,
+        "note": null,
+        "current_balance": 0.0,
+        "initial_balance": 0.0,
+        "creation_date": "2019-09-19T00:00:00Z",
+        "id_account_type": 1,
+        "id_currency": 999
+    }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    let fields = common::fields(&reply);
+    assert!(fields.contains(&"name".to_string()));
+    assert!(fields.contains(&"id_currency".to_string()));
+}
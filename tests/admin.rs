@@ -0,0 +1,61 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+
+#[macro_use]
+mod common;
+
+use rocket::http::{Method, Status};
+
+#[test]
+fn only_an_admin_reads_the_users() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.get("/admin/user", &alice);
+    assert_eq!(reply.status, Status::Forbidden);
+    assert_eq!(reply.body["code"], "forbidden");
+    // the role is read at every request, the token does not change
+    app.promote(&alice);
+    let reply = app.get("/admin/user", &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body[0]["id"].as_i64(), Some(alice.id));
+}
+
+#[test]
+fn a_disabled_user_is_refused_until_enabled() {
+    let app = test_app!();
+    let admin = app.register("admin@example.com");
+    let bob = app.register("bob@example.com");
+    app.promote(&admin);
+    let put = |path: String| app.request(Method::Put, &path, Some(&admin.token), None);
+    assert_eq!(put(format!("/admin/user/{}/disable", bob.id)).status, Status::NoContent);
+    assert_eq!(app.get("/user/user", &bob).status, Status::Forbidden);
+    assert_eq!(put(format!("/admin/user/{}/enable", bob.id)).status, Status::NoContent);
+    assert_eq!(app.get("/user/user", &bob).status, Status::Ok);
+    // not even by mistake
+    assert_eq!(put(format!("/admin/user/{}/disable", admin.id)).status, Status::BadRequest);
+}
+
+#[test]
+fn only_an_admin_changes_the_reference_data() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let currency = serde_json::json!({ "name": "Swiss franc", "code": "CHF", "number": 756 });
+    assert_eq!(app.post("/admin/currency", &alice, currency.clone()).status, Status::Forbidden);
+    app.promote(&alice);
+    assert_eq!(app.post("/admin/currency", &alice, currency).status, Status::Ok);
+}
@@ -0,0 +1,96 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::EUR;
+
+#[test]
+fn the_changes_of_a_transaction_are_logged_in_order() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, wallet, causal, 10.0);
+    let reply = app.put(&format!("/transaction/{}", id), &alice, common::transaction(wallet, causal, 12.5));
+    assert_eq!(reply.status, Status::NoContent);
+    let reply = app.delete(&format!("/transaction/{}", id), &alice);
+    assert_eq!(reply.status, Status::NoContent);
+
+    let reply = app.get(&format!("/audit?entity=transaction&id={}", id), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    let entries = reply.body.as_array().unwrap();
+    let actions: Vec<&str> = entries.iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, vec!["create", "update", "delete"]);
+    assert!(entries.iter().all(|e| e["id_user"].as_i64() == Some(alice.id)));
+    assert!(entries[0]["before"].is_null());
+    assert_eq!(entries[1]["before"]["amount"].as_f64(), Some(10.0));
+    assert_eq!(entries[1]["after"]["amount"].as_f64(), Some(12.5));
+    assert!(entries[2]["after"].is_null());
+}
+
+#[test]
+fn the_log_of_another_user_account_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let reply = app.get(&format!("/audit?entity=account&id={}", wallet), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    let reply = app.get(&format!("/audit?entity=account&id={}", wallet), &bob);
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn an_unknown_entity_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.get("/audit?entity=user&id=1", &alice);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["entity".to_string()]);
+}
+
+#[test]
+fn the_user_of_the_destination_account_reads_the_log_of_a_giro() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let bank = app.account(&alice, "Bank", EUR);
+    let joint = app.account(&alice, "Joint", EUR);
+    app.share(&bob, joint);
+    let reply = app.post("/giro", &alice, json!({
+        "id_source_account": bank,
+        "id_destination_account": joint,
+        "data": "2019-09-21T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR
+    }));
+    assert_eq!(reply.status, Status::Ok);
+
+    let path = format!("/audit?entity=giro&id={}", reply.body["id"]);
+    let reply = app.get(&path, &bob);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    assert_eq!(reply.body.as_array().map(|e| e.len()), Some(1));
+    let carol = app.register("carol@example.com");
+    assert_eq!(app.get(&path, &carol).status, Status::Forbidden);
+}
@@ -0,0 +1,200 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use data_encoding::BASE64URL_NOPAD;
+use ring::{digest, hmac, rand, signature};
+use rocket::config::Value;
+use rocket::http::{Header, Method, Status};
+use serde_json::json;
+
+use common::{Session, TestApp};
+
+///
+/// A PKCS#8 Ed25519 key in a temporary file, the name must be unique among the tests.
+fn ed25519_key_file(name: &str) -> String {
+    let pkcs8 = signature::Ed25519KeyPair::generate_pkcs8(&rand::SystemRandom::new()).unwrap();
+    let path = env::temp_dir().join(format!("mm-{}-{}.der", name, std::process::id()));
+    fs::write(&path, &pkcs8[..]).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+///
+/// A session token signed with the shared secret of the tests.
+fn hs256_token(id_user: i64) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let header = BASE64URL_NOPAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let claims = json!({ "sub": id_user.to_string(), "exp": now + 900, "iat": now });
+    let message = format!("{}.{}", header, BASE64URL_NOPAD.encode(claims.to_string().as_bytes()));
+    let key = hmac::SigningKey::new(&digest::SHA256, common::JWT_KEY.as_bytes());
+    format!("{}.{}", message, BASE64URL_NOPAD.encode(hmac::sign(&key, message.as_bytes()).as_ref()))
+}
+
+///
+/// A sendmail that writes the email to a file, the name must be unique among the tests.
+fn sendmail_to_file(name: &str) -> (String, String) {
+    let mail = env::temp_dir().join(format!("mm-{}-{}.eml", name, std::process::id()));
+    let script = env::temp_dir().join(format!("mm-{}-{}.sh", name, std::process::id()));
+    fs::write(&script, format!("#!/bin/sh\ncat > '{}'\n", mail.display())).unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    (script.to_str().unwrap().to_string(), mail.to_str().unwrap().to_string())
+}
+
+#[test]
+fn login_returns_a_token_for_the_user() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.get("/user/user", &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["id"].as_i64(), Some(alice.id));
+}
+
+#[test]
+fn login_with_a_wrong_password_is_unauthorized() {
+    let app = test_app!();
+    app.register("alice@example.com");
+    let reply = app.login("alice@example.com", "wrong").err().expect("login accepted a wrong password");
+    assert_eq!(reply.status, Status::Unauthorized);
+    assert_eq!(reply.body["code"], "unauthorized");
+}
+
+#[test]
+fn login_with_an_unknown_email_is_refused() {
+    let app = test_app!();
+    let reply = app.login("nobody@example.com", common::PASSWORD).err().expect("login accepted an unknown email");
+    assert_eq!(reply.status, Status::NotFound);
+}
+
+#[test]
+fn a_request_without_token_is_unauthorized() {
+    let app = test_app!();
+    let reply = app.request(Method::Get, "/user/user", None, None);
+    assert_eq!(reply.status, Status::Unauthorized);
+    assert_eq!(reply.body["code"], "unauthorized");
+}
+
+#[test]
+fn a_forged_token_is_unauthorized() {
+    let app = test_app!();
+    let mut alice = app.register("alice@example.com");
+    alice.token.push('x');
+    assert_eq!(app.get("/user/user", &alice).status, Status::Unauthorized);
+}
+
+#[test]
+fn without_ed25519_keys_the_shared_secret_signs_the_tokens() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let forged = Session { id: alice.id, email: alice.email.clone(), token: hs256_token(alice.id) };
+    assert_eq!(app.get("/user/user", &forged).status, Status::Ok);
+}
+
+#[test]
+fn with_ed25519_keys_the_shared_secret_is_refused() {
+    let key = ed25519_key_file("refuse-hs256");
+    let keys = Value::Array(vec![Value::from(key.as_str())]);
+    let app = test_app!(TestApp::with_extras(vec![("jwt_private_keys", keys)]));
+    let alice = app.register("alice@example.com");
+    assert_eq!(app.get("/user/user", &alice).status, Status::Ok);
+    let forged = Session { id: alice.id, email: alice.email.clone(), token: hs256_token(alice.id) };
+    assert_eq!(app.get("/user/user", &forged).status, Status::Unauthorized);
+    fs::remove_file(key).unwrap();
+}
+
+#[test]
+fn the_old_authentication_header_is_still_accepted() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let header = Header::new("Authentication", alice.token.clone());
+    let reply = app.request_with_headers(Method::Get, "/user/user", None, None, vec![header]);
+    assert_eq!(reply.status, Status::Ok);
+    let header = Header::new("Authorization", format!("Basic {}", alice.token));
+    let reply = app.request_with_headers(Method::Get, "/user/user", None, None, vec![header]);
+    assert_eq!(reply.status, Status::Unauthorized);
+}
+
+#[test]
+fn the_jwks_publishes_the_ed25519_keys() {
+    let app = test_app!();
+    let reply = app.request(Method::Get, "/.well-known/jwks.json", None, None);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["keys"], json!([]));
+
+    let current = ed25519_key_file("jwks-current");
+    let previous = ed25519_key_file("jwks-previous");
+    let keys = Value::Array(vec![Value::from(current.as_str()), Value::from(previous.as_str())]);
+    let app = test_app!(TestApp::with_extras(vec![("jwt_private_keys", keys)]));
+    let reply = app.request(Method::Get, "/.well-known/jwks.json", None, None);
+    let keys = reply.body["keys"].as_array().unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0]["kty"], "OKP");
+    assert_eq!(keys[0]["crv"], "Ed25519");
+    // the token says which key signed it
+    let alice = app.register("alice@example.com");
+    let header = alice.token.split('.').next().map(|h| BASE64URL_NOPAD.decode(h.as_bytes()).unwrap()).unwrap();
+    let header: serde_json::Value = serde_json::from_slice(&header).unwrap();
+    assert_eq!(header["alg"], "EdDSA");
+    assert_eq!(header["kid"], keys[0]["kid"]);
+    fs::remove_file(current).unwrap();
+    fs::remove_file(previous).unwrap();
+}
+
+#[test]
+fn a_password_reset_changes_the_password_once_and_ends_the_sessions() {
+    let (sendmail, mail) = sendmail_to_file("reset");
+    let app = test_app!(TestApp::with_extras(vec![
+        ("mailer", Value::from("sendmail")),
+        ("sendmail_path", Value::from(sendmail.as_str())),
+        ("reset_url", Value::from("https://example.com/reset?token={token}"))
+    ]));
+    let alice = app.register("alice@example.com");
+    let reply = app.post("/auth/tokens", &alice, json!({ "name": "script", "scopes": ["read"], "expiration_date": null }));
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    let script = Session { id: alice.id, email: alice.email.clone(), token: reply.body["token"].as_str().unwrap().to_string() };
+    assert_eq!(app.get("/account/user", &script).status, Status::Ok);
+    // an unknown email has the same answer and no email
+    let reply = app.request(Method::Post, "/auth/password/forgot", None, Some(json!({ "email": "nobody@example.com" })));
+    assert_eq!(reply.status, Status::NoContent);
+    assert!(fs::metadata(&mail).is_err());
+    let reply = app.request(Method::Post, "/auth/password/forgot", None, Some(json!({ "email": alice.email })));
+    assert_eq!(reply.status, Status::NoContent);
+    let email = fs::read_to_string(&mail).unwrap();
+    assert!(email.contains("To: alice@example.com"), "{}", email);
+    let token = email.split("?token=").nth(1).and_then(|s| s.split_whitespace().next()).unwrap().to_string();
+
+    let reply = app.request(Method::Post, "/auth/password/reset", None, Some(json!({ "token": token, "password": "x" })));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["password"]);
+    let reset = json!({ "token": token, "password": "a new password" });
+    let reply = app.request(Method::Post, "/auth/password/reset", None, Some(reset.clone()));
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(app.get("/user/user", &alice).status, Status::Unauthorized);
+    // the access tokens created before the reset are revoked as well
+    assert_eq!(app.get("/account/user", &script).status, Status::Unauthorized);
+    assert!(app.login(&alice.email, common::PASSWORD).is_err());
+    assert!(app.login(&alice.email, "a new password").is_ok());
+    let reply = app.request(Method::Post, "/auth/password/reset", None, Some(reset));
+    assert_eq!(reply.status, Status::BadRequest);
+    fs::remove_file(sendmail).unwrap();
+    fs::remove_file(mail).unwrap();
+}
@@ -0,0 +1,120 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::{EUR, EXPENSE, INCOME};
+
+#[test]
+fn the_balance_at_a_date_counts_the_giros_on_both_sides() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let other = app.causal(&alice, "Other");
+    app.expense(&alice, wallet, other, 5.0, "2019-09-25T12:00:00Z");
+    app.expense(&alice, wallet, other, 10.0, "2019-09-20T12:00:00Z");
+    let reply = app.post("/giro", &alice, json!({
+        "id_source_account": bank,
+        "id_destination_account": wallet,
+        "data": "2019-09-21T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR
+    }));
+    assert_eq!(reply.status, Status::Ok);
+
+    let balance = |id: i64, at: &str| app.get(&format!("/account/{}/balance?at={}", id, at), &alice).body["balance"].as_f64();
+    assert_eq!(balance(wallet, "2019-09-20"), Some(-10.0));
+    assert_eq!(balance(wallet, "2019-09-21T12:00:00Z"), Some(40.0));
+    assert_eq!(balance(bank, "2019-09-21"), Some(-50.0));
+    let reply = app.get(&format!("/account/{}/balance", wallet), &alice);
+    assert_eq!(reply.body["balance"].as_f64(), Some(35.0));
+
+    let reply = app.get(&format!("/account/{}/balance?at=yesterday", wallet), &alice);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["at"]);
+}
+
+#[test]
+fn the_ledger_has_the_running_balance_in_the_order_of_the_date() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let other = app.causal(&alice, "Other");
+    app.expense(&alice, wallet, other, 5.0, "2019-09-25T12:00:00Z");
+    app.expense(&alice, wallet, other, 10.0, "2019-09-20T12:00:00Z");
+    app.expense(&alice, wallet, other, 1.0, "2019-09-30T12:00:00Z");
+    // at the same time of the second expense, it comes first
+    let reply = app.post("/giro", &alice, json!({
+        "id_source_account": bank,
+        "id_destination_account": wallet,
+        "data": "2019-09-25T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR
+    }));
+    assert_eq!(reply.status, Status::Ok);
+
+    let path = format!("/transaction/account/{}?running_balance=true", wallet);
+    let reply = app.get(&path, &alice);
+    assert_eq!(reply.status, Status::Ok);
+    let balances: Vec<_> = reply.body.as_array().unwrap().iter().map(|t| t["running_balance"].as_f64()).collect();
+    assert_eq!(balances, vec![Some(-10.0), Some(35.0), Some(34.0)]);
+    let reply = app.get(&format!("{}&offset=1&limit=1", path), &alice);
+    assert_eq!(reply.body[0]["amount"].as_f64(), Some(5.0));
+    assert_eq!(reply.body[0]["running_balance"].as_f64(), Some(35.0));
+    let reply = app.get(&format!("{}&offset=2", path), &alice);
+    assert_eq!(reply.body[0]["running_balance"].as_f64(), Some(34.0));
+    assert_eq!(app.get(&format!("{}&offset=3", path), &alice).body, json!([]));
+
+    let reply = app.get(&format!("/transaction/account/{}", wallet), &alice);
+    assert_eq!(reply.body[0]["amount"].as_f64(), Some(10.0));
+    assert!(reply.body[0].get("running_balance").is_none());
+}
+
+#[test]
+fn a_transaction_keeps_its_direction_until_its_type_changes() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let admin = app.register("admin@example.com");
+    app.promote(&admin);
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let other = app.causal(&alice, "Other");
+    let id = app.transaction(&alice, wallet, other, 10.0);
+    let balance = || app.get(&format!("/account/{}/balance", wallet), &alice).body["balance"].as_f64();
+    assert_eq!(balance(), Some(-10.0));
+
+    // the direction of the type is the one of the new transactions
+    let reply = app.put(&format!("/admin/transaction/type/{}", EXPENSE), &admin, json!({ "type": "Expense", "direction": 1 }));
+    assert_eq!(reply.status, Status::NoContent, "{}", reply.body);
+    assert_eq!(balance(), Some(-10.0));
+    let reply = app.patch(&format!("/transaction/{}", id), &alice, json!({ "note": "lunch" }));
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(balance(), Some(-10.0));
+    let reply = app.patch(&format!("/transaction/{}", id), &alice, json!({ "id_transaction_type": INCOME }));
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(balance(), Some(10.0));
+    assert_eq!(app.get(&format!("/transaction/{}", id), &alice).body["direction"], 1);
+}
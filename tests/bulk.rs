@@ -0,0 +1,80 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::EUR;
+
+#[test]
+fn a_bulk_update_reports_every_item() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let groceries = app.causal(&alice, "Groceries");
+    let food = app.causal(&alice, "Food");
+    let first = app.transaction(&alice, wallet, groceries, 10.0);
+    let second = app.transaction(&alice, wallet, groceries, 20.0);
+    let other = app.transaction(&bob, app.account(&bob, "Bank", EUR), app.causal(&bob, "Rent"), 30.0);
+
+    let reply = app.post("/transaction/bulk", &alice, json!({
+        "operation": "update",
+        "ids": [first, second, other],
+        "fields": { "id_causal": food, "note": "market" }
+    }));
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["committed"], true);
+    let statuses: Vec<_> = reply.body["results"].as_array().unwrap().iter()
+        .map(|r| r["status"].as_u64().unwrap())
+        .collect();
+    assert_eq!(statuses, vec![204, 204, 403]);
+    let reply = app.get(&format!("/transaction/{}", second), &alice);
+    assert_eq!(reply.body["id_causal"].as_i64(), Some(food));
+    assert_eq!(reply.body["note"], "market");
+    assert_eq!(reply.body["amount"].as_f64(), Some(20.0));
+}
+
+#[test]
+fn an_all_or_nothing_bulk_is_rolled_back_when_an_item_fails() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    app.transaction(&alice, wallet, causal, 10.0);
+    app.transaction(&alice, wallet, causal, 20.0);
+
+    let reply = app.post("/transaction/bulk", &alice, json!({
+        "operation": "create",
+        "all_or_nothing": true,
+        "transactions": [common::transaction(wallet, causal, 5.0), common::transaction(wallet, causal, -5.0)]
+    }));
+    assert_eq!(reply.status, Status::Conflict);
+    assert_eq!(reply.body["committed"], false);
+    assert_eq!(reply.body["results"][1]["status"].as_u64(), Some(422));
+
+    let reply = app.post("/transaction/bulk", &alice, json!({
+        "operation": "delete",
+        "filter": { "id_account": wallet, "id_causal": causal }
+    }));
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["results"].as_array().map(|r| r.len()), Some(2));
+    assert_eq!(app.get(&format!("/transaction/account/{}", wallet), &alice).body, json!([]));
+}
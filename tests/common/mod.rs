@@ -0,0 +1,298 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use diesel::Connection;
+use diesel::connection::SimpleConnection;
+use diesel::pg::PgConnection;
+use rocket::config::{Config, Environment, LoggingLevel, Value};
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::local::Client;
+use serde_json::json;
+
+use money_manager_server::controller;
+use money_manager_server::settings::Settings;
+
+pub const PASSWORD: &str = "password";
+pub const JWT_KEY: &str = "integration-tests-only";
+pub const EUR: i64 = 1;
+pub const USD: i64 = 2;
+// added with the direction of the transaction types, the seeded ones are means of payment
+pub const INCOME: i64 = 7;
+pub const EXPENSE: i64 = 8;
+
+///
+/// The server of a test, or the return from the test when there is no database to run it against.
+/// Without arguments it is `TestApp::new()`, otherwise the expression giving the `Option<TestApp>`.
+macro_rules! test_app {
+    () => {
+        test_app!(crate::common::TestApp::new())
+    };
+    ($app:expr) => {
+        match $app { Some(app) => app, None => return }
+    };
+}
+
+static SCHEMAS: AtomicUsize = AtomicUsize::new(0);
+
+///
+/// The server against a schema created for the test and dropped at the end.
+/// The tests run only when TEST_DATABASE_URL points to a Postgres database that can be used freely,
+/// when CI is set they fail without it.
+pub struct TestApp {
+    pub client: Client,
+    admin: PgConnection,
+    schema: String
+}
+
+///
+/// A registered user with a valid token.
+pub struct Session {
+    pub id: i64,
+    pub email: String,
+    pub token: String
+}
+
+///
+/// Status, ETag and body of a response, the body is Null when it is not JSON.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: Status,
+    pub etag: Option<String>,
+    pub body: serde_json::Value
+}
+
+impl TestApp {
+    pub fn new() -> Option<TestApp> {
+        TestApp::with_extras(Vec::new())
+    }
+
+    ///
+    /// The server with some keys of Rocket.toml added or replaced.
+    pub fn with_extras(extras: Vec<(&str, Value)>) -> Option<TestApp> {
+        let url = match env::var("TEST_DATABASE_URL") {
+            Ok(url) => url,
+            // a CI run without the database would pass without testing anything
+            Err(_) if env::var_os("CI").is_some() => panic!("TEST_DATABASE_URL is required when CI is set"),
+            Err(_) => {
+                eprintln!("TEST_DATABASE_URL is not set, the test is skipped");
+                return None;
+            }
+        };
+        let schema = format!("test_{}_{}", process::id(), SCHEMAS.fetch_add(1, Ordering::SeqCst));
+        let admin = PgConnection::establish(&url).expect("TEST_DATABASE_URL is not reachable");
+        admin.batch_execute(&format!("CREATE SCHEMA {}", schema)).expect("can not create the schema");
+        // every connection of the pool works in the schema of the test
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let schema_url = format!("{}{}options=-c%20search_path%3D{}", url, separator, schema);
+
+        let mut db = HashMap::new();
+        db.insert("url", Value::from(schema_url));
+        db.insert("pool_size", Value::from(2));
+        let mut databases = HashMap::new();
+        databases.insert("db", Value::from(db));
+        let mut builder = Config::build(Environment::Development)
+            .log_level(LoggingLevel::Off)
+            .extra("databases", databases)
+            .extra("jwt_key", JWT_KEY)
+            .extra("migrations", "run")
+            .extra("housekeeping_interval", 0);
+        for (key, value) in extras {
+            builder = builder.extra(key, value);
+        }
+        let config = builder.finalize().expect("invalid test configuration");
+        let settings = Settings::from_config(&config).expect("invalid test settings");
+        let client = Client::new(controller::rocket(config, settings)).expect("the server does not start");
+        Some(TestApp { client, admin, schema })
+    }
+
+    pub fn request(&self, method: Method, path: &str, token: Option<&str>, body: Option<serde_json::Value>) -> Reply {
+        self.request_with_headers(method, path, token, body, Vec::new())
+    }
+
+    pub fn request_with_headers(&self, method: Method, path: &str, token: Option<&str>, body: Option<serde_json::Value>,
+                                headers: Vec<Header<'static>>) -> Reply {
+        let mut request = self.client.req(method, path.to_string());
+        for header in headers {
+            request.add_header(header);
+        }
+        if let Some(token) = token {
+            request.add_header(Header::new("Authorization", format!("Bearer {}", token)));
+        }
+        if let Some(body) = body {
+            request.add_header(ContentType::JSON);
+            request.set_body(body.to_string());
+        }
+        let mut response = request.dispatch();
+        let etag = response.headers().get_one("ETag").map(|s| s.to_string());
+        let body = response.body_string()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or(serde_json::Value::Null);
+        Reply { status: response.status(), etag, body }
+    }
+
+    pub fn get(&self, path: &str, session: &Session) -> Reply {
+        self.request(Method::Get, path, Some(&session.token), None)
+    }
+
+    pub fn post(&self, path: &str, session: &Session, body: serde_json::Value) -> Reply {
+        self.request(Method::Post, path, Some(&session.token), Some(body))
+    }
+
+    pub fn put(&self, path: &str, session: &Session, body: serde_json::Value) -> Reply {
+        self.request(Method::Put, path, Some(&session.token), Some(body))
+    }
+
+    pub fn patch(&self, path: &str, session: &Session, body: serde_json::Value) -> Reply {
+        self.request(Method::Patch, path, Some(&session.token), Some(body))
+    }
+
+    pub fn delete(&self, path: &str, session: &Session) -> Reply {
+        self.request(Method::Delete, path, Some(&session.token), None)
+    }
+
+    ///
+    /// Create the user and its credentials, then login.
+    pub fn register(&self, email: &str) -> Session {
+        let user = self.request(Method::Post, "/user", None, Some(json!({
+            "name": "Test",
+            "surname": email
+        })));
+        assert_eq!(user.status, Status::Ok, "can not create the user: {}", user.body);
+        let id = user.body["id"].as_i64().unwrap();
+        let auth = self.request(Method::Post, &format!("/auth/{}", id), None, Some(json!({
+            "email": email,
+            "password": PASSWORD
+        })));
+        assert_eq!(auth.status, Status::NoContent, "can not create the auth: {}", auth.body);
+        let token = self.login(email, PASSWORD).expect("can not login");
+        Session { id, email: email.to_string(), token }
+    }
+
+    ///
+    /// Give the admin role to the user, straight in the database.
+    pub fn promote(&self, session: &Session) {
+        self.admin.batch_execute(&format!("UPDATE {}.\"user\" SET role = 'admin' WHERE id = {}", self.schema, session.id))
+            .expect("can not promote the user");
+    }
+
+    ///
+    /// Give the user access to an account of someone else, straight in the database.
+    pub fn share(&self, session: &Session, id_account: i64) {
+        self.admin.batch_execute(&format!("INSERT INTO {}.account_user (id_account, id_user) VALUES ({}, {})",
+                                          self.schema, id_account, session.id))
+            .expect("can not share the account");
+    }
+
+    pub fn login(&self, email: &str, password: &str) -> Result<String, Reply> {
+        let reply = self.request(Method::Post, "/auth/login", None, Some(json!({
+            "email": email,
+            "password": password
+        })));
+        match reply.body["token"].as_str() {
+            Some(token) if reply.status == Status::Ok => Ok(token.to_string()),
+            _ => Err(reply)
+        }
+    }
+
+    pub fn account(&self, session: &Session, name: &str, id_currency: i64) -> i64 {
+        let reply = self.post("/account", session, json!({
+            "name": name,
+            "status": true,
+            "note": null,
+            "current_balance": 0.0,
+            "initial_balance": 0.0,
+            "creation_date": "2019-09-19T00:00:00Z",
+            "id_account_type": 1,
+            "id_currency": id_currency
+        }));
+        assert_eq!(reply.status, Status::Ok, "can not create the account: {}", reply.body);
+        reply.body["id"].as_i64().unwrap()
+    }
+
+    pub fn causal(&self, session: &Session, description: &str) -> i64 {
+        let reply = self.post("/causal", session, json!({ "description": description }));
+        assert_eq!(reply.status, Status::Ok, "can not create the causal: {}", reply.body);
+        reply.body["id"].as_i64().unwrap()
+    }
+
+    pub fn detail(&self, session: &Session, description: &str) -> i64 {
+        let reply = self.post("/detail", session, json!({ "description": description }));
+        assert_eq!(reply.status, Status::Ok, "can not create the detail: {}", reply.body);
+        reply.body["id"].as_i64().unwrap()
+    }
+
+    pub fn place(&self, session: &Session, name: &str) -> i64 {
+        let reply = self.post("/place", session, json!({ "name": name }));
+        assert_eq!(reply.status, Status::Ok, "can not create the place: {}", reply.body);
+        reply.body["id"].as_i64().unwrap()
+    }
+
+    pub fn transaction(&self, session: &Session, id_account: i64, id_causal: i64, amount: f64) -> i64 {
+        let reply = self.post("/transaction", session, transaction(id_account, id_causal, amount));
+        assert_eq!(reply.status, Status::Ok, "can not create the transaction: {}", reply.body);
+        reply.body["id"].as_i64().unwrap()
+    }
+
+    ///
+    /// A transaction of the expense type at the date.
+    pub fn expense(&self, session: &Session, id_account: i64, id_causal: i64, amount: f64, data: &str) -> i64 {
+        let mut body = transaction(id_account, id_causal, amount);
+        body["data"] = json!(data);
+        let reply = self.post("/transaction", session, body);
+        assert_eq!(reply.status, Status::Ok, "can not create the transaction: {}", reply.body);
+        reply.body["id"].as_i64().unwrap()
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        if let Err(e) = self.admin.batch_execute(&format!("DROP SCHEMA {} CASCADE", self.schema)) {
+            eprintln!("can not drop the schema {}: {}", self.schema, e);
+        }
+    }
+}
+
+///
+/// The body of an expense of the given amount, in euro.
+pub fn transaction(id_account: i64, id_causal: i64, amount: f64) -> serde_json::Value {
+    json!({
+        "id_account": id_account,
+        "id_transaction_type": EXPENSE,
+        "id_place": null,
+        "id_beneficiary": null,
+        "note": null,
+        "amount": amount,
+        "data": "2019-09-20T12:00:00Z",
+        "id_currency": EUR,
+        "expense": null,
+        "id_causal": id_causal
+    })
+}
+
+///
+/// The field errors of a 422 response.
+pub fn fields(reply: &Reply) -> Vec<String> {
+    reply.body["fields"].as_array()
+        .map(|fields| fields.iter().filter_map(|f| f["field"].as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,64 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::{Header, Method, Status};
+
+use common::EUR;
+
+fn if_match(etag: &str) -> Vec<Header<'static>> {
+    vec![Header::new("If-Match", etag.to_string())]
+}
+
+#[test]
+fn an_update_with_a_stale_etag_is_refused() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, wallet, causal, 10.0);
+    let path = format!("/transaction/{}", id);
+    let etag = app.get(&path, &alice).etag.expect("no ETag");
+
+    let body = common::transaction(wallet, causal, 12.0);
+    let reply = app.request_with_headers(Method::Put, &path, Some(&alice.token), Some(body), if_match(&etag));
+    assert_eq!(reply.status, Status::NoContent);
+    let reply = app.get(&path, &alice);
+    assert_ne!(reply.etag.as_ref(), Some(&etag));
+    assert_eq!(reply.body["amount"].as_f64(), Some(12.0));
+
+    // a second client still holds the first version
+    let body = common::transaction(wallet, causal, 15.0);
+    let reply = app.request_with_headers(Method::Put, &path, Some(&alice.token), Some(body), if_match(&etag));
+    assert_eq!(reply.status, Status::PreconditionFailed);
+    let reply = app.request_with_headers(Method::Delete, &path, Some(&alice.token), None, if_match(&etag));
+    assert_eq!(reply.status, Status::PreconditionFailed);
+    assert_eq!(app.get(&path, &alice).body["amount"].as_f64(), Some(12.0));
+}
+
+#[test]
+fn an_update_without_if_match_is_accepted() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let place = app.place(&alice, "Market");
+    let path = format!("/place/{}", place);
+    let version = app.get(&path, &alice).body["version"].as_i64().unwrap();
+    assert_eq!(app.put(&path, &alice, serde_json::json!({ "name": "Bakery" })).status, Status::NoContent);
+    assert_eq!(app.get(&path, &alice).body["version"].as_i64(), Some(version + 1));
+}
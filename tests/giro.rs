@@ -0,0 +1,83 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::EUR;
+
+fn giro(source: i64, destination: i64, amount: f64) -> serde_json::Value {
+    json!({
+        "id_source_account": source,
+        "id_destination_account": destination,
+        "data": "2019-09-20T12:00:00Z",
+        "note": null,
+        "amount": amount,
+        "expense": null,
+        "id_currency": EUR
+    })
+}
+
+#[test]
+fn a_giro_between_the_accounts_of_the_user_is_created() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let reply = app.post("/giro", &alice, giro(bank, wallet, 50.0));
+    assert_eq!(reply.status, Status::Ok);
+    let id = reply.body["id"].as_i64().unwrap();
+    let reply = app.get(&format!("/giro/account/destination/{}", wallet), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body[0]["id"].as_i64(), Some(id));
+}
+
+#[test]
+fn a_giro_towards_the_account_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&bob, "Bank", EUR);
+    let reply = app.post("/giro", &alice, giro(wallet, bank, 50.0));
+    assert_eq!(reply.status, Status::Forbidden);
+    let reply = app.post("/giro", &alice, giro(bank, wallet, 50.0));
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn a_giro_on_the_same_account_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let reply = app.post("/giro", &alice, giro(wallet, wallet, 50.0));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["id_destination_account".to_string()]);
+}
+
+#[test]
+fn a_giro_towards_a_missing_account_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let reply = app.post("/giro", &alice, giro(wallet, 999, 50.0));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["id_destination_account".to_string()]);
+}
@@ -0,0 +1,46 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::{Method, Status};
+
+#[test]
+fn the_server_is_alive_and_ready() {
+    let app = test_app!();
+    let reply = app.request(Method::Get, "/health", None, None);
+    assert_eq!(reply.status, Status::Ok);
+    let reply = app.request(Method::Get, "/ready", None, None);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["status"], "ready");
+}
+
+#[test]
+fn the_metrics_count_requests_and_authentication_failures() {
+    let app = test_app!();
+    app.register("alice@example.com");
+    assert!(app.login("alice@example.com", "wrong").is_err());
+    let mut response = app.client.get("/metrics").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.body_string().unwrap();
+    assert!(body.contains("http_requests_total{route=\"POST /auth/login\",status=\"200\"} 1"), "{}", body);
+    assert!(body.contains("http_request_duration_seconds_count{route=\"POST /user\",status=\"200\"} 1"), "{}", body);
+    assert!(body.contains("db_pool_max_connections 2"), "{}", body);
+    // the counters of the failures are global to the process, other tests can increase them
+    assert!(!body.contains("auth_failures_total{reason=\"login\"} 0"), "{}", body);
+}
@@ -0,0 +1,68 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::{Header, Method, Status};
+
+use common::EUR;
+
+fn key(value: &str) -> Vec<Header<'static>> {
+    vec![Header::new("Idempotency-Key", value.to_string())]
+}
+
+#[test]
+fn a_retry_with_the_same_key_returns_the_first_transaction() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let body = common::transaction(wallet, causal, 10.0);
+
+    let first = app.request_with_headers(Method::Post, "/transaction", Some(&alice.token),
+                                         Some(body.clone()), key("retry-1"));
+    assert_eq!(first.status, Status::Ok);
+    let retry = app.request_with_headers(Method::Post, "/transaction", Some(&alice.token),
+                                         Some(body), key("retry-1"));
+    assert_eq!(retry.status, Status::Ok);
+    assert_eq!(retry.body["id"], first.body["id"]);
+    let list = app.get(&format!("/transaction/account/{}", wallet), &alice);
+    assert_eq!(list.body.as_array().map(|l| l.len()), Some(1));
+}
+
+#[test]
+fn a_key_reused_with_another_body_is_refused() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+
+    let reply = app.request_with_headers(Method::Post, "/transaction", Some(&alice.token),
+                                         Some(common::transaction(wallet, causal, 10.0)), key("k"));
+    assert_eq!(reply.status, Status::Ok);
+    let reply = app.request_with_headers(Method::Post, "/transaction", Some(&alice.token),
+                                         Some(common::transaction(wallet, causal, 99.0)), key("k"));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(reply.body["fields"][0]["field"], "Idempotency-Key");
+
+    // the keys of a user do not collide with the ones of the others
+    let reply = app.request_with_headers(Method::Post, "/causal", Some(&bob.token),
+                                         Some(serde_json::json!({ "description": "Rent" })), key("k"));
+    assert_eq!(reply.status, Status::Ok);
+}
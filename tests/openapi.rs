@@ -0,0 +1,59 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::env;
+use std::fs;
+use rocket::config::{Config, Environment, LoggingLevel};
+
+use money_manager_server::{controller, openapi};
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/openapi.json");
+
+// no database: only the routes are mounted
+fn document() -> serde_json::Value {
+    let config = Config::build(Environment::Development)
+        .log_level(LoggingLevel::Off)
+        .finalize()
+        .unwrap();
+    let rocket = controller::mount(rocket::custom(config));
+    openapi::document(rocket.routes())
+}
+
+#[test]
+fn every_route_is_described() {
+    let doc = document();
+    for (path, item) in doc["paths"].as_object().unwrap() {
+        for (method, operation) in item.as_object().unwrap() {
+            assert_ne!(operation["summary"], "Not documented", "{} {} is not described", method, path);
+        }
+    }
+}
+
+///
+/// The published document changes only on purpose: UPDATE_SNAPSHOTS=1 cargo test rewrites it.
+#[test]
+fn the_document_matches_the_snapshot() {
+    let actual = serde_json::to_string_pretty(&document()).unwrap() + "\n";
+    if env::var("UPDATE_SNAPSHOTS").is_ok() {
+        fs::create_dir_all(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots")).unwrap();
+        fs::write(SNAPSHOT, &actual).unwrap();
+        eprintln!("{} written, review it and commit it", SNAPSHOT);
+        return;
+    }
+    let expected = fs::read_to_string(SNAPSHOT).expect("the OpenAPI snapshot is missing, run UPDATE_SNAPSHOTS=1 cargo test");
+    assert!(actual == expected, "the OpenAPI document changed, run UPDATE_SNAPSHOTS=1 cargo test and review the diff");
+}
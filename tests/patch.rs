@@ -0,0 +1,66 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::{Header, Method, Status};
+use serde_json::json;
+
+use common::EUR;
+
+#[test]
+fn a_patch_changes_only_the_given_fields() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, wallet, causal, 10.0);
+    let path = format!("/transaction/{}", id);
+
+    assert_eq!(app.patch(&path, &alice, json!({ "note": "bread" })).status, Status::NoContent);
+    let reply = app.get(&path, &alice);
+    assert_eq!(reply.body["note"], "bread");
+    assert_eq!(reply.body["amount"].as_f64(), Some(10.0));
+    assert_eq!(reply.body["id_causal"].as_i64(), Some(causal));
+
+    // null clears an optional field
+    assert_eq!(app.patch(&path, &alice, json!({ "note": null })).status, Status::NoContent);
+    assert_eq!(app.get(&path, &alice).body["note"], serde_json::Value::Null);
+    // and a required one can not be cleared
+    assert_eq!(app.patch(&path, &alice, json!({ "amount": null })).status, Status::BadRequest);
+}
+
+#[test]
+fn a_patch_is_validated_and_checked_like_an_update() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let path = format!("/account/{}", wallet);
+
+    assert_eq!(app.patch(&path, &bob, json!({ "name": "Mine" })).status, Status::Forbidden);
+    assert_eq!(app.patch(&path, &alice, json!({ "id_currency": 9999 })).status, Status::UnprocessableEntity);
+
+    let etag = app.get(&path, &alice).etag.expect("no ETag");
+    assert_eq!(app.patch(&path, &alice, json!({ "name": "Pocket" })).status, Status::NoContent);
+    let stale = vec![Header::new("If-Match", etag)];
+    let reply = app.request_with_headers(Method::Patch, &path, Some(&alice.token),
+                                         Some(json!({ "name": "Purse" })), stale);
+    assert_eq!(reply.status, Status::PreconditionFailed);
+    assert_eq!(app.get(&path, &alice).body["name"], "Pocket");
+}
@@ -0,0 +1,170 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use common::{TestApp, EUR};
+
+fn cleared(app: &TestApp, session: &common::Session, id_account: i64, id_causal: i64, amount: f64) -> i64 {
+    let mut body = common::transaction(id_account, id_causal, amount);
+    body["cleared"] = json!(true);
+    let reply = app.post("/transaction", session, body);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    reply.body["id"].as_i64().unwrap()
+}
+
+fn giro(app: &TestApp, session: &common::Session, source: i64, destination: i64, cleared: (bool, bool)) -> common::Reply {
+    app.post("/giro", session, json!({
+        "id_source_account": source,
+        "id_destination_account": destination,
+        "data": "2019-09-21T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR,
+        "cleared_source": cleared.0,
+        "cleared_destination": cleared.1
+    }))
+}
+
+#[test]
+fn a_matching_statement_locks_the_cleared_movements() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let other = app.causal(&alice, "Other");
+    let wrong = cleared(&app, &alice, wallet, other, 10.0);
+    let open = app.transaction(&alice, wallet, other, 20.0);
+    let reply = app.post("/giro", &alice, json!({
+        "id_source_account": bank,
+        "id_destination_account": wallet,
+        "data": "2019-09-21T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR,
+        "cleared_destination": true
+    }));
+    assert_eq!(reply.status, Status::Ok);
+
+    let path = format!("/account/{}/reconcile", wallet);
+    let reply = app.post(&path, &alice, json!({ "statement_date": "2019-09-30T00:00:00Z", "statement_balance": 35.0 }));
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    assert_eq!(reply.body["cleared_balance"].as_f64(), Some(40.0));
+    assert_eq!(reply.body["difference"].as_f64(), Some(-5.0));
+    assert_eq!(reply.body["locked"], 0);
+    // the expense was 15
+    assert_eq!(app.delete(&format!("/transaction/{}", wrong), &alice).status, Status::NoContent);
+    let locked = cleared(&app, &alice, wallet, other, 15.0);
+
+    let reply = app.post(&path, &alice, json!({ "statement_date": "2019-09-30T00:00:00Z", "statement_balance": 35.0 }));
+    assert_eq!(reply.body["difference"].as_f64(), Some(0.0));
+    assert_eq!(reply.body["locked"], 2);
+
+    let reply = app.patch(&format!("/transaction/{}", locked), &alice, json!({ "amount": 16.0 }));
+    assert_eq!(reply.status, Status::Conflict);
+    assert_eq!(app.delete(&format!("/transaction/{}", locked), &alice).status, Status::Conflict);
+    let reply = app.patch(&format!("/transaction/{}", open), &alice, json!({ "amount": 21.0 }));
+    assert_eq!(reply.status, Status::NoContent);
+
+    let reply = app.get(&format!("/reconciliation/account/{}", wallet), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body.as_array().map(|r| r.len()), Some(2));
+}
+
+#[test]
+fn a_statement_before_the_last_reconciled_one_is_refused() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let path = format!("/account/{}/reconcile", wallet);
+    let reply = app.post(&path, &alice, json!({ "statement_date": "2019-09-30T00:00:00Z", "statement_balance": 0.0 }));
+    assert_eq!(reply.body["difference"].as_f64(), Some(0.0));
+
+    let reply = app.post(&path, &alice, json!({ "statement_date": "2019-08-31T00:00:00Z", "statement_balance": 0.0 }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["statement_date"]);
+    let reply = app.post(&path, &bob, json!({ "statement_date": "2019-10-31T00:00:00Z", "statement_balance": 0.0 }));
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn each_side_of_a_giro_is_reconciled_by_its_account() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let reply = giro(&app, &alice, bank, wallet, (false, true));
+    assert_eq!(reply.status, Status::Ok);
+    let path = format!("/giro/{}", reply.body["id"]);
+
+    let reply = app.post(&format!("/account/{}/reconcile", wallet), &alice,
+                         json!({ "statement_date": "2019-09-30T00:00:00Z", "statement_balance": 50.0 }));
+    assert_eq!(reply.body["locked"], 1);
+    // the bank has not cleared it yet
+    let reply = app.get(&path, &alice);
+    assert_eq!(reply.body["id_reconciliation_source"], json!(null));
+    assert_eq!(app.patch(&path, &alice, json!({ "cleared_destination": false })).status, Status::Conflict);
+    assert_eq!(app.patch(&path, &alice, json!({ "amount": 60.0 })).status, Status::Conflict);
+    assert_eq!(app.patch(&path, &alice, json!({ "cleared_source": true })).status, Status::NoContent);
+
+    let reply = app.post(&format!("/account/{}/reconcile", bank), &alice,
+                         json!({ "statement_date": "2019-09-30T00:00:00Z", "statement_balance": -50.0 }));
+    assert_eq!(reply.body["difference"].as_f64(), Some(0.0));
+    assert_eq!(reply.body["locked"], 1);
+    assert_eq!(app.delete(&path, &alice).status, Status::Conflict);
+}
+
+#[test]
+fn a_cleared_movement_up_to_the_last_reconciled_statement_is_refused() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let other = app.causal(&alice, "Other");
+    let deleted = cleared(&app, &alice, wallet, other, 10.0);
+    assert_eq!(app.delete(&format!("/transaction/{}", deleted), &alice).status, Status::NoContent);
+    let reply = app.post(&format!("/account/{}/reconcile", wallet), &alice,
+                         json!({ "statement_date": "2019-09-30T00:00:00Z", "statement_balance": 0.0 }));
+    assert_eq!(reply.body["difference"].as_f64(), Some(0.0));
+
+    let mut body = common::transaction(wallet, other, 10.0);
+    body["cleared"] = json!(true);
+    let reply = app.post("/transaction", &alice, body);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["cleared"]);
+    let open = app.transaction(&alice, wallet, other, 10.0);
+    let reply = app.patch(&format!("/transaction/{}", open), &alice, json!({ "cleared": true }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    let reply = app.patch(&format!("/transaction/{}", open), &alice,
+                          json!({ "cleared": true, "data": "2019-10-01T12:00:00Z" }));
+    assert_eq!(reply.status, Status::NoContent);
+    let reply = app.request(Method::Post, &format!("/transaction/{}/restore", deleted), Some(&alice.token), None);
+    assert_eq!(reply.status, Status::Conflict);
+
+    // only the wallet has been reconciled
+    let reply = giro(&app, &alice, bank, wallet, (true, true));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["cleared_destination"]);
+    assert_eq!(giro(&app, &alice, wallet, bank, (false, true)).status, Status::Ok);
+}
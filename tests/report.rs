@@ -0,0 +1,112 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::{EUR, USD};
+
+#[test]
+fn the_net_worth_is_computed_from_the_ledger_for_every_month() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let other = app.causal(&alice, "Other");
+    app.expense(&alice, wallet, other, 10.0, "2019-09-20T12:00:00Z");
+    app.expense(&alice, wallet, other, 5.0, "2019-10-05T12:00:00Z");
+
+    let reply = app.get("/report/net-worth?from=2019-09-01&to=2019-11-15&interval=month&group_by=account_type", &alice);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    assert_eq!(reply.body["id_currency"].as_i64(), Some(EUR));
+    let periods = reply.body["periods"].as_array().unwrap();
+    let totals: Vec<_> = periods.iter().map(|p| p["total"].as_f64()).collect();
+    assert_eq!(totals, vec![Some(-10.0), Some(-15.0), Some(-15.0)]);
+    assert_eq!(periods[2]["from"], "2019-11-01");
+    assert_eq!(periods[2]["to"], "2019-11-15");
+    assert_eq!(periods[1]["account_types"][0]["total"].as_f64(), Some(-15.0));
+
+    // the current balance stored on the account does not count
+    let reply = app.patch(&format!("/account/{}", wallet), &alice, json!({ "current_balance": 1000.0 }));
+    assert_eq!(reply.status, Status::NoContent);
+    let reply = app.get("/report/net-worth?from=2019-09-01&to=2019-09-30", &alice);
+    assert_eq!(reply.body["periods"][0]["total"].as_f64(), Some(-10.0));
+    assert!(reply.body["periods"][0].get("account_types").is_none());
+}
+
+#[test]
+fn the_other_currencies_are_converted_with_the_exchange_rates() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let dollars = app.account(&alice, "Dollars", USD);
+    let other = app.causal(&alice, "Other");
+    app.expense(&alice, wallet, other, 10.0, "2019-09-20T12:00:00Z");
+    let mut body = common::transaction(dollars, other, 20.0);
+    body["id_currency"] = json!(USD);
+    assert_eq!(app.post("/transaction", &alice, body).status, Status::Ok);
+
+    let path = "/report/net-worth?from=2019-09-01&to=2019-09-30";
+    let reply = app.get(path, &alice);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["currency"]);
+    let path = format!("{}&currency={}", path, EUR);
+    let reply = app.get(&path, &alice);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+
+    let admin = app.register("admin@example.com");
+    app.promote(&admin);
+    let reply = app.post("/admin/currency/rate", &admin, json!({
+        "id_currency": USD,
+        "id_base_currency": EUR,
+        "date": "2019-09-01",
+        "rate": 0.9
+    }));
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    let reply = app.get(&path, &alice);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    assert_eq!(reply.body["periods"][0]["total"].as_f64(), Some(-28.0));
+}
+
+#[test]
+fn without_accounts_the_net_worth_has_no_periods() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.get(&format!("/report/net-worth?currency={}", EUR), &alice);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    assert_eq!(reply.body["periods"], json!([]));
+    assert_eq!(common::fields(&app.get("/report/net-worth", &alice)), vec!["currency".to_string()]);
+}
+
+#[test]
+fn a_movement_before_the_creation_opens_the_account() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let other = app.causal(&alice, "Other");
+    // the account has been created on 2019-09-19
+    app.expense(&alice, wallet, other, 10.0, "2019-08-10T12:00:00Z");
+
+    let reply = app.get("/report/net-worth?from=2019-08-01&to=2019-09-30", &alice);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    let totals: Vec<_> = reply.body["periods"].as_array().unwrap().iter().map(|p| p["total"].as_f64()).collect();
+    assert_eq!(totals, vec![Some(-10.0), Some(-10.0)]);
+    let reply = app.get("/report/net-worth?to=2019-09-30", &alice);
+    assert_eq!(reply.body["periods"][0]["from"], "2019-08-01");
+}
@@ -0,0 +1,104 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::EUR;
+
+#[test]
+fn a_rule_categorizes_the_new_transactions() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let other = app.causal(&alice, "Other");
+    let groceries = app.causal(&alice, "Groceries");
+    let reply = app.post("/rule", &alice, json!({
+        "name": "Supermarket",
+        "note_regex": "esselunga|coop\\b",
+        "set_causal": groceries
+    }));
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+
+    let mut body = common::transaction(wallet, other, 10.0);
+    body["note"] = json!("ESSELUNGA MILANO");
+    let reply = app.post("/transaction", &alice, body);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["id_causal"].as_i64(), Some(groceries));
+
+    let reply = app.post("/transaction", &alice, common::transaction(wallet, other, 10.0));
+    assert_eq!(reply.body["id_causal"].as_i64(), Some(other));
+}
+
+#[test]
+fn a_rule_is_applied_to_the_past_with_a_dry_run_first() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let other = app.causal(&alice, "Other");
+    let big = app.causal(&alice, "Big expenses");
+    app.transaction(&alice, wallet, other, 10.0);
+    let id = app.transaction(&alice, wallet, other, 500.0);
+    let rule = app.post("/rule", &alice, json!({
+        "name": "Big",
+        "min_amount": 100.0,
+        "set_causal": big
+    })).body["id"].as_i64().unwrap();
+
+    let reply = app.post(&format!("/rule/{}/apply?dry_run=true", rule), &alice, json!({}));
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["transactions"].as_array().map(|t| t.len()), Some(1));
+    assert_eq!(reply.body["transactions"][0]["transaction"]["id_causal"].as_i64(), Some(big));
+    let path = format!("/transaction/{}", id);
+    assert_eq!(app.get(&path, &alice).body["id_causal"].as_i64(), Some(other));
+
+    let reply = app.post(&format!("/rule/{}/apply", rule), &alice, json!({}));
+    assert_eq!(reply.body["dry_run"], false);
+    assert_eq!(app.get(&path, &alice).body["id_causal"].as_i64(), Some(big));
+    // nothing left to change
+    let reply = app.post(&format!("/rule/{}/apply", rule), &alice, json!({}));
+    assert_eq!(reply.body["transactions"].as_array().map(|t| t.len()), Some(0));
+}
+
+#[test]
+fn a_rule_needs_a_valid_regex_and_a_condition() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let causal = app.causal(&alice, "Groceries");
+    let reply = app.post("/rule", &alice, json!({ "name": "Broken", "note_regex": "(", "set_causal": causal }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    let reply = app.post("/rule", &alice, json!({ "name": "All", "set_causal": causal }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+}
+
+#[test]
+fn a_rule_on_the_beneficiary_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let savings = app.account(&alice, "Savings", EUR);
+    let groceries = app.causal(&bob, "Groceries");
+    let reply = app.post("/rule", &bob, json!({
+        "name": "To Alice",
+        "id_beneficiary": savings,
+        "set_causal": groceries
+    }));
+    assert_eq!(reply.status, Status::Forbidden);
+}
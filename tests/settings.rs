@@ -0,0 +1,74 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::{env, fs, process};
+use rocket::config::{Config, ConfigBuilder, Environment, Value};
+
+use money_manager_server::settings::{self, Settings};
+
+// the smallest configuration that starts the server
+fn config(environment: Environment) -> ConfigBuilder {
+    let mut db = HashMap::new();
+    db.insert("url", Value::from("postgres://localhost/money-manager"));
+    let mut databases = HashMap::new();
+    databases.insert("db", Value::from(db));
+    Config::build(environment)
+        .extra("databases", databases)
+        .extra("jwt_key", "a shared secret that is long enough")
+}
+
+#[test]
+fn the_missing_keys_take_the_defaults() {
+    let settings = Settings::from_config(&config(Environment::Production).finalize().unwrap()).unwrap();
+    assert_eq!(settings.jwt_exp, 900);
+    assert_eq!(settings.deletion_grace_days, 30);
+    assert_eq!(settings.migrations, "run");
+    assert_eq!(settings.cors_origins, vec!["http://rippetanks.ddns.net", "http://localhost"]);
+}
+
+#[test]
+fn an_empty_list_of_origins_is_kept() {
+    let config = config(Environment::Development)
+        .extra("cors_origins", Vec::<String>::new())
+        .finalize().unwrap();
+    assert!(Settings::from_config(&config).unwrap().cors_origins.is_empty());
+}
+
+#[test]
+fn all_the_errors_are_reported_together() {
+    let config = config(Environment::Production)
+        .extra("jwt_key", "short")
+        .extra("migrations", "sometimes")
+        .extra("cors_origins", vec!["ftp://example.com"])
+        .finalize().unwrap();
+    let error = Settings::from_config(&config).unwrap_err();
+    assert!(error.contains("jwt_key must be at least 32 characters"), "{}", error);
+    assert!(error.contains("migrations must be run, verify or off"), "{}", error);
+    assert!(error.contains("cors_origins: ftp://example.com"), "{}", error);
+}
+
+#[test]
+fn a_broken_rocket_toml_is_an_error() {
+    let dir = env::temp_dir().join(format!("mm-settings-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Rocket.toml"), "[development\njwt_key = ").unwrap();
+    // the only test that needs the working directory
+    env::set_current_dir(&dir).unwrap();
+    let error = settings::rocket_config().unwrap_err();
+    assert!(error.contains("Rocket.toml"), "{}", error);
+}
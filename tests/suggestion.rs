@@ -0,0 +1,72 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::{TestApp, EUR};
+
+fn transaction(app: &TestApp, session: &common::Session, id_account: i64, id_causal: i64, amount: f64, note: &str) {
+    let mut body = common::transaction(id_account, id_causal, amount);
+    body["note"] = json!(note);
+    assert_eq!(app.post("/transaction", session, body).status, Status::Ok);
+}
+
+#[test]
+fn the_similar_transactions_are_suggested_first() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let groceries = app.causal(&alice, "Groceries");
+    let fuel = app.causal(&alice, "Fuel");
+    transaction(&app, &alice, wallet, groceries, 45.0, "Esselunga Milano 12/03");
+    transaction(&app, &alice, wallet, groceries, 52.0, "ESSELUNGA viale Monza");
+    transaction(&app, &alice, wallet, fuel, 60.0, "Eni station A4");
+
+    let reply = app.get("/transaction/suggest?note=esselunga%20monza&amount=50", &alice);
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    let causals = reply.body["causals"].as_array().unwrap();
+    assert_eq!(causals[0]["id"].as_i64(), Some(groceries));
+    assert_eq!(causals[0]["name"], "Groceries");
+    assert_eq!(causals[0]["uses"], 2);
+    // only the amount is close
+    assert_eq!(causals[1]["id"].as_i64(), Some(fuel));
+    assert!(causals[1]["score"].as_f64() < causals[0]["score"].as_f64());
+
+    // without inputs the most used ones
+    let reply = app.get("/transaction/suggest", &alice);
+    assert_eq!(reply.body["causals"][0]["id"].as_i64(), Some(groceries));
+}
+
+#[test]
+fn the_suggestions_come_only_from_the_own_transactions() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let groceries = app.causal(&alice, "Groceries");
+    transaction(&app, &alice, wallet, groceries, 45.0, "Esselunga");
+
+    let reply = app.get("/transaction/suggest?note=esselunga", &bob);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["causals"].as_array().map(|c| c.len()), Some(0));
+    let reply = app.get(&format!("/transaction/suggest?account={}", wallet), &bob);
+    assert_eq!(reply.status, Status::Forbidden);
+}
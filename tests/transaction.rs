@@ -0,0 +1,184 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::{EUR, USD};
+
+#[test]
+fn the_owner_creates_reads_updates_and_deletes_a_transaction() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, account, causal, 12.5);
+    let reply = app.get(&format!("/transaction/{}", id), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["amount"].as_f64(), Some(12.5));
+    let reply = app.put(&format!("/transaction/{}", id), &alice, common::transaction(account, causal, 20.0));
+    assert_eq!(reply.status, Status::NoContent);
+    let reply = app.get(&format!("/transaction/account/{}", account), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body[0]["amount"].as_f64(), Some(20.0));
+    assert_eq!(app.delete(&format!("/transaction/{}", id), &alice).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/transaction/{}", id), &alice).status, Status::NotFound);
+}
+
+#[test]
+fn the_transaction_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, account, causal, 12.5);
+    assert_eq!(app.get(&format!("/transaction/{}", id), &bob).status, Status::Forbidden);
+    let bob_causal = app.causal(&bob, "Groceries");
+    let reply = app.put(&format!("/transaction/{}", id), &bob, common::transaction(account, bob_causal, 1.0));
+    assert_eq!(reply.status, Status::Forbidden);
+    assert_eq!(app.delete(&format!("/transaction/{}", id), &bob).status, Status::Forbidden);
+}
+
+#[test]
+fn a_transaction_on_the_account_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&bob, "Groceries");
+    let reply = app.post("/transaction", &bob, common::transaction(account, causal, 1.0));
+    assert_eq!(reply.status, Status::Forbidden);
+    assert_eq!(reply.body["code"], "forbidden");
+}
+
+#[test]
+fn a_transaction_with_the_causal_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&bob, "Wallet", EUR);
+    let causal = app.causal(&alice, "Private");
+    let reply = app.post("/transaction", &bob, common::transaction(account, causal, 1.0));
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn a_transaction_with_the_place_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&bob, "Wallet", EUR);
+    let causal = app.causal(&bob, "Groceries");
+    let place = app.place(&alice, "Home");
+    let mut body = common::transaction(account, causal, 1.0);
+    body["id_place"] = place.into();
+    assert_eq!(app.post("/transaction", &bob, body).status, Status::Forbidden);
+}
+
+#[test]
+fn a_transaction_with_the_beneficiary_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&bob, "Wallet", EUR);
+    let causal = app.causal(&bob, "Groceries");
+    let beneficiary = app.account(&alice, "Savings", EUR);
+    let mut body = common::transaction(account, causal, 1.0);
+    body["id_beneficiary"] = beneficiary.into();
+    assert_eq!(app.post("/transaction", &bob, body).status, Status::Forbidden);
+}
+
+#[test]
+fn a_transaction_with_missing_references_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let mut body = common::transaction(account, 999, -1.0);
+    body["id_place"] = 999.into();
+    let reply = app.post("/transaction", &alice, body);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(reply.body["code"], "validation_failed");
+    let fields = common::fields(&reply);
+    for field in &["amount", "id_causal", "id_place"] {
+        assert!(fields.contains(&field.to_string()), "{} not reported in {:?}", field, fields);
+    }
+}
+
+#[test]
+fn a_transaction_in_another_currency_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let mut body = common::transaction(account, causal, 1.0);
+    body["id_currency"] = USD.into();
+    let reply = app.post("/transaction", &alice, body);
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    assert_eq!(common::fields(&reply), vec!["id_currency".to_string()]);
+}
+
+#[test]
+fn an_update_without_cleared_keeps_it() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let mut body = common::transaction(account, causal, 12.5);
+    body["cleared"] = json!(true);
+    let reply = app.post("/transaction", &alice, body.clone());
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    let path = format!("/transaction/{}", reply.body["id"]);
+
+    let reply = app.put(&path, &alice, common::transaction(account, causal, 20.0));
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(app.get(&path, &alice).body["cleared"].as_bool(), Some(true));
+    body["cleared"] = json!(false);
+    assert_eq!(app.put(&path, &alice, body).status, Status::NoContent);
+    assert_eq!(app.get(&path, &alice).body["cleared"].as_bool(), Some(false));
+}
+
+#[test]
+fn a_co_owner_patches_a_transaction_with_the_references_of_the_other_owner() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&alice, "Joint", EUR);
+    app.share(&bob, account);
+    let causal = app.causal(&alice, "Groceries");
+    let place = app.place(&alice, "Market");
+    let mut body = common::transaction(account, causal, 1.0);
+    body["id_place"] = place.into();
+    let reply = app.post("/transaction", &alice, body);
+    assert_eq!(reply.status, Status::Ok);
+    let path = format!("/transaction/{}", reply.body["id"]);
+
+    // the causal and the place stay as they are
+    assert_eq!(app.patch(&path, &bob, json!({ "note": "for the week" })).status, Status::NoContent);
+    let reply = app.post("/transaction/bulk", &bob, json!({
+        "operation": "update",
+        "ids": [reply.body["id"]],
+        "fields": { "note": "for two weeks" }
+    }));
+    assert_eq!(reply.body["results"][0]["status"].as_u64(), Some(204), "{}", reply.body);
+    // a new one must be of the user
+    let other = app.causal(&alice, "Other");
+    assert_eq!(app.patch(&path, &bob, json!({ "id_causal": other })).status, Status::Forbidden);
+}
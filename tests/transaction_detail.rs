@@ -0,0 +1,97 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use rocket::http::Status;
+use serde_json::json;
+
+use common::EUR;
+
+#[test]
+fn a_detail_is_added_to_a_transaction() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let transaction = app.transaction(&alice, account, causal, 12.5);
+    let detail = app.detail(&alice, "Bread");
+    let reply = app.post("/transaction/detail", &alice, json!({
+        "id_detail": detail,
+        "id_transaction": transaction,
+        "amount": 2
+    }));
+    assert_eq!(reply.status, Status::NoContent);
+    let reply = app.get(&format!("/transaction/detail/transaction/{}", transaction), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body[0]["id_detail"].as_i64(), Some(detail));
+    let reply = app.delete(&format!("/transaction/detail/transaction/{}/detail/{}", transaction, detail), &alice);
+    assert_eq!(reply.status, Status::NoContent);
+}
+
+#[test]
+fn the_detail_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&bob, "Wallet", EUR);
+    let causal = app.causal(&bob, "Groceries");
+    let transaction = app.transaction(&bob, account, causal, 12.5);
+    let detail = app.detail(&alice, "Private");
+    let reply = app.post("/transaction/detail", &bob, json!({
+        "id_detail": detail,
+        "id_transaction": transaction,
+        "amount": null
+    }));
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn a_detail_on_the_transaction_of_another_user_is_forbidden() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let account = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let transaction = app.transaction(&alice, account, causal, 12.5);
+    let detail = app.detail(&bob, "Bread");
+    let reply = app.post("/transaction/detail", &bob, json!({
+        "id_detail": detail,
+        "id_transaction": transaction,
+        "amount": null
+    }));
+    assert_eq!(reply.status, Status::Forbidden);
+    let reply = app.get(&format!("/transaction/detail/transaction/{}", transaction), &bob);
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn a_detail_with_missing_references_is_unprocessable() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.post("/transaction/detail", &alice, json!({
+        "id_detail": 999,
+        "id_transaction": 999,
+        "amount": 0
+    }));
+    assert_eq!(reply.status, Status::UnprocessableEntity);
+    let fields = common::fields(&reply);
+    for field in &["amount", "id_detail", "id_transaction"] {
+        assert!(fields.contains(&field.to_string()), "{} not reported in {:?}", field, fields);
+    }
+}
@@ -0,0 +1,133 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+#[macro_use]
+mod common;
+
+use std::thread;
+use std::time::Duration;
+use rocket::config::Value;
+use rocket::http::{Method, Status};
+use serde_json::json;
+
+use common::{Session, TestApp, EUR};
+
+// a giro of 50 from the first account to the second one
+fn giro(app: &TestApp, session: &Session, source: i64, destination: i64) -> i64 {
+    let reply = app.post("/giro", session, json!({
+        "id_source_account": source,
+        "id_destination_account": destination,
+        "data": "2019-09-20T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR
+    }));
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    reply.body["id"].as_i64().unwrap()
+}
+
+#[test]
+fn a_deleted_transaction_goes_to_the_trash_and_comes_back() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, wallet, causal, 10.0);
+    assert_eq!(app.delete(&format!("/transaction/{}", id), &alice).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/transaction/account/{}", wallet), &alice).body.as_array().map(|t| t.len()), Some(0));
+
+    let reply = app.get("/trash", &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert_eq!(reply.body["transactions"][0]["id"].as_i64(), Some(id));
+    assert!(reply.body["transactions"][0]["deleted_at"].is_string());
+
+    let reply = app.request(Method::Post, &format!("/transaction/{}/restore", id), Some(&alice.token), None);
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(app.get(&format!("/transaction/{}", id), &alice).status, Status::Ok);
+    assert_eq!(app.get("/trash", &alice).body["transactions"].as_array().map(|t| t.len()), Some(0));
+}
+
+#[test]
+fn an_account_is_restored_with_its_transactions() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    let id = app.transaction(&alice, wallet, causal, 10.0);
+    assert_eq!(app.delete(&format!("/account/{}", wallet), &alice).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/transaction/{}", id), &alice).status, Status::NotFound);
+
+    // the transaction can not come back before its account
+    let restore = |path: String| app.request(Method::Post, &path, Some(&alice.token), None);
+    assert_eq!(restore(format!("/transaction/{}/restore", id)).status, Status::Conflict);
+    assert_eq!(restore(format!("/account/{}/restore", wallet)).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/transaction/{}", id), &alice).status, Status::Ok);
+}
+
+#[test]
+fn the_trash_of_another_user_can_not_be_restored() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let place = app.place(&alice, "Market");
+    assert_eq!(app.delete(&format!("/place/{}", place), &alice).status, Status::NoContent);
+    assert_eq!(app.get("/trash", &bob).body["places"].as_array().map(|p| p.len()), Some(0));
+    let reply = app.request(Method::Post, &format!("/place/{}/restore", place), Some(&bob.token), None);
+    assert_eq!(reply.status, Status::Forbidden);
+}
+
+#[test]
+fn the_giros_of_an_account_in_the_trash_stay_with_the_other_account() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let id = giro(&app, &alice, wallet, bank);
+    assert_eq!(app.delete(&format!("/account/{}", wallet), &alice).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/giro/{}", id), &alice).status, Status::Ok);
+    assert_eq!(app.get(&format!("/account/{}/balance", bank), &alice).body["balance"].as_f64(), Some(50.0));
+
+    // the last account takes it to the trash, the first one to come back brings it back
+    assert_eq!(app.delete(&format!("/account/{}", bank), &alice).status, Status::NoContent);
+    assert_eq!(app.get(&format!("/giro/{}", id), &alice).status, Status::NotFound);
+    assert_eq!(app.get("/trash", &alice).body["giros"][0]["id"].as_i64(), Some(id));
+    let reply = app.request(Method::Post, &format!("/account/{}/restore", bank), Some(&alice.token), None);
+    assert_eq!(reply.status, Status::NoContent);
+    assert_eq!(app.get(&format!("/giro/{}", id), &alice).status, Status::Ok);
+}
+
+#[test]
+fn the_purge_of_an_account_leaves_its_giros_to_the_other_account() {
+    let app = test_app!(TestApp::with_extras(vec![
+        ("trash_retention_days", Value::from(0)),
+        ("housekeeping_interval", Value::from(1))
+    ]));
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&alice, "Bank", EUR);
+    let id = giro(&app, &alice, wallet, bank);
+    assert_eq!(app.delete(&format!("/account/{}", wallet), &alice).status, Status::NoContent);
+    // the housekeeping runs every second
+    thread::sleep(Duration::from_millis(2500));
+    let reply = app.request(Method::Post, &format!("/account/{}/restore", wallet), Some(&alice.token), None);
+    assert_eq!(reply.status, Status::NotFound);
+    let reply = app.get(&format!("/giro/{}", id), &alice);
+    assert_eq!(reply.status, Status::Ok);
+    assert!(reply.body["id_source_account"].is_null());
+    assert_eq!(app.get(&format!("/account/{}/balance", bank), &alice).body["balance"].as_f64(), Some(50.0));
+}
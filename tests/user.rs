@@ -0,0 +1,121 @@
+/*
+    Copyright (C) 2019  Simone Martelli
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+
+#[macro_use]
+mod common;
+
+use rocket::config::Value;
+use rocket::http::Status;
+use serde_json::json;
+
+use common::{TestApp, EUR};
+
+// the erasure happens at once, without the grace period
+fn without_grace_period() -> Option<TestApp> {
+    TestApp::with_extras(vec![("deletion_grace_days", Value::from(0))])
+}
+
+#[test]
+fn the_deletion_is_scheduled_and_can_be_cancelled() {
+    let app = test_app!();
+    let alice = app.register("alice@example.com");
+    let reply = app.delete("/user/user", &alice);
+    assert_eq!(reply.status, Status::Accepted);
+    assert!(reply.body["deletion_date"].is_string());
+    assert!(app.get("/user/user", &alice).body["deletion_date"].is_string());
+
+    assert_eq!(app.delete("/user/user/deletion", &alice).status, Status::NoContent);
+    assert!(app.get("/user/user", &alice).body["deletion_date"].is_null());
+    assert_eq!(app.delete("/user/user/deletion", &alice).status, Status::NotFound);
+}
+
+#[test]
+fn without_grace_period_the_user_is_erased_at_once() {
+    let app = test_app!(without_grace_period());
+    let alice = app.register("alice@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let causal = app.causal(&alice, "Groceries");
+    app.transaction(&alice, wallet, causal, 10.0);
+    assert_eq!(app.delete("/user/user", &alice).status, Status::Ok);
+    assert_eq!(app.get("/user/user", &alice).status, Status::Unauthorized);
+    assert!(app.login(&alice.email, common::PASSWORD).is_err());
+    // the email can be used again
+    let again = app.register("alice@example.com");
+    assert_eq!(app.get(&format!("/account/{}", wallet), &again).status, Status::NotFound);
+}
+
+#[test]
+fn the_giros_with_a_shared_account_stay_with_it() {
+    let app = test_app!(without_grace_period());
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let wallet = app.account(&alice, "Wallet", EUR);
+    let bank = app.account(&bob, "Bank", EUR);
+    app.share(&alice, bank);
+    let reply = app.post("/giro", &alice, json!({
+        "id_source_account": wallet,
+        "id_destination_account": bank,
+        "data": "2019-09-20T12:00:00Z",
+        "note": null,
+        "amount": 50.0,
+        "expense": null,
+        "id_currency": EUR
+    }));
+    assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+    let giro = reply.body["id"].as_i64().unwrap();
+    assert_eq!(app.delete("/user/user", &alice).status, Status::Ok);
+
+    let reply = app.get(&format!("/giro/{}", giro), &bob);
+    assert_eq!(reply.status, Status::Ok);
+    assert!(reply.body["id_source_account"].is_null());
+    assert_eq!(reply.body["id_destination_account"].as_i64(), Some(bank));
+    let balance = app.get(&format!("/account/{}/balance", bank), &bob);
+    assert_eq!(balance.body["balance"].as_f64(), Some(50.0));
+    // the form needs both the accounts, it can only be deleted
+    let reply = app.patch(&format!("/giro/{}", giro), &bob, json!({ "note": "from Alice" }));
+    assert_eq!(reply.status, Status::Conflict);
+    assert_eq!(app.delete(&format!("/giro/{}", giro), &bob).status, Status::NoContent);
+}
+
+#[test]
+fn the_causals_used_by_the_shared_accounts_pass_to_their_owners() {
+    let app = test_app!(without_grace_period());
+    let alice = app.register("alice@example.com");
+    let bob = app.register("bob@example.com");
+    let carol = app.register("carol@example.com");
+    let bank = app.account(&bob, "Bank", EUR);
+    let savings = app.account(&carol, "Savings", EUR);
+    app.share(&alice, bank);
+    app.share(&alice, savings);
+    let causal = app.causal(&alice, "Rent");
+    let in_bank = app.transaction(&alice, bank, causal, 10.0);
+    let in_savings = app.transaction(&alice, savings, causal, 20.0);
+    assert_eq!(app.delete("/user/user", &alice).status, Status::Ok);
+
+    // each owner sees the causal of the transactions of its account
+    for (session, id) in vec![(&bob, in_bank), (&carol, in_savings)] {
+        let reply = app.get(&format!("/transaction/{}", id), session);
+        assert_eq!(reply.status, Status::Ok);
+        let id_causal = reply.body["id_causal"].as_i64().unwrap();
+        let reply = app.get(&format!("/causal/{}", id_causal), session);
+        assert_eq!(reply.status, Status::Ok, "{}", reply.body);
+        assert_eq!(reply.body["description"].as_str(), Some("Rent"));
+    }
+    // the shared accounts lose only the link with the user
+    assert_eq!(app.get(&format!("/account/{}", bank), &bob).status, Status::Ok);
+}